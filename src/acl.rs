@@ -0,0 +1,246 @@
+use std::ffi::CString;
+use std::io::{self, Error, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation};
+
+/// Name of the extended attribute holding the default ACL of a directory
+const DEFAULT_ACL_XATTR: &[u8] = b"system.posix_acl_default\0";
+
+/// Version of the ACL extended attribute format, from `linux/posix_acl_xattr.h`
+const ACL_XATTR_VERSION: u32 = 0x0002;
+
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+/// Sets the default ACL of a directory, inherited by files created inside it
+///
+/// The spec uses the short text form accepted by `setfacl`, with numeric ids, e.g. `u::rwx,g::r-x,g:1000:rwx,m::rwx,o::---`. On rollback the previous default ACL is restored, or removed if there was none
+pub struct SetDefaultAcl {
+    dir: PathBuf,
+    spec: String,
+    original: Option<Option<Vec<u8>>>,
+}
+
+impl SetDefaultAcl {
+    /// Constructs a new SetDefaultAcl operation
+    pub fn new<S: AsRef<Path>, T: AsRef<str>>(dir: S, spec: T) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+            spec: spec.as_ref().into(),
+            original: None,
+        }
+    }
+}
+
+fn invalid_spec(entry: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid ACL entry: {}", entry),
+    )
+}
+
+/// Encodes an ACL spec into the extended attribute format understood by the kernel
+fn encode_spec(spec: &str) -> io::Result<Vec<u8>> {
+    let mut entries = vec![];
+
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let fields: Vec<&str> = entry.split(':').collect();
+        let (kind, qualifier, perms) = match fields.as_slice() {
+            [kind, qualifier, perms] => (*kind, *qualifier, *perms),
+            _ => return Err(invalid_spec(entry)),
+        };
+
+        let named = !qualifier.is_empty();
+        let tag = match (kind, named) {
+            ("u" | "user", false) => ACL_USER_OBJ,
+            ("u" | "user", true) => ACL_USER,
+            ("g" | "group", false) => ACL_GROUP_OBJ,
+            ("g" | "group", true) => ACL_GROUP,
+            ("m" | "mask", false) => ACL_MASK,
+            ("o" | "other", false) => ACL_OTHER,
+            _ => return Err(invalid_spec(entry)),
+        };
+
+        let id = if named {
+            qualifier.parse().map_err(|_| invalid_spec(entry))?
+        } else {
+            ACL_UNDEFINED_ID
+        };
+
+        let mut perm = 0u16;
+        for (i, c) in perms.chars().enumerate() {
+            perm |= match (i, c) {
+                (_, '-') => 0,
+                (0, 'r') => 4,
+                (1, 'w') => 2,
+                (2, 'x') => 1,
+                _ => return Err(invalid_spec(entry)),
+            };
+        }
+
+        entries.push((tag, id, perm));
+    }
+
+    // The kernel expects entries ordered by tag, then by qualifier
+    entries.sort_unstable();
+
+    // The xattr is little-endian on every architecture
+    let mut value = ACL_XATTR_VERSION.to_le_bytes().to_vec();
+    for (tag, id, perm) in entries {
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&perm.to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+
+    Ok(value)
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+}
+
+fn get_default_acl(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let path = c_path(path)?;
+    let name = DEFAULT_ACL_XATTR.as_ptr() as *const libc::c_char;
+
+    // SAFETY: both strings are NUL-terminated and a null buffer of size 0 only queries the size
+    let size = unsafe { libc::getxattr(path.as_ptr(), name, std::ptr::null_mut(), 0) };
+    if size < 0 {
+        let e = Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENODATA) => Ok(None),
+            _ => Err(e),
+        };
+    }
+
+    let mut value = vec![0u8; size as usize];
+    // SAFETY: the buffer is valid for writes of its full length for the duration of the call
+    let size = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name,
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+        )
+    };
+    if size < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    value.truncate(size as usize);
+    Ok(Some(value))
+}
+
+fn set_default_acl(path: &Path, value: Option<&[u8]>) -> io::Result<()> {
+    let path = c_path(path)?;
+    let name = DEFAULT_ACL_XATTR.as_ptr() as *const libc::c_char;
+
+    // SAFETY: both strings are NUL-terminated and the value is valid for reads of its full length
+    let result = unsafe {
+        match value {
+            Some(value) => libc::setxattr(
+                path.as_ptr(),
+                name,
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+            ),
+            None => libc::removexattr(path.as_ptr(), name),
+        }
+    };
+
+    match result {
+        -1 => match Error::last_os_error() {
+            e if value.is_none() && e.raw_os_error() == Some(libc::ENODATA) => Ok(()),
+            e => Err(e),
+        },
+        _ => Ok(()),
+    }
+}
+
+impl RollbackableOperation for SetDefaultAcl {
+    fn execute(&mut self) -> io::Result<()> {
+        let value = encode_spec(&self.spec)?;
+
+        self.original = Some(get_default_acl(&self.dir)?);
+        set_default_acl(&self.dir, Some(&value))
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match &self.original {
+            Some(original) => set_default_acl(&self.dir, original.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.dir)]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SetDefaultAcl::new(&self.dir, &self.spec)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const DIR: &str = "./set_default_acl";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn set_default_acl_works() {
+        fs::create_dir_all(DIR).unwrap();
+
+        let mut op = SetDefaultAcl::new(DIR, "u::rwx,g::r-x,g:1000:rwx,m::rwx,o::---");
+
+        match op.execute() {
+            Ok(()) => {}
+            // The filesystem does not support ACLs
+            Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+                fs::remove_dir_all(DIR);
+                return;
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        let value = get_default_acl(Path::new(DIR)).unwrap().unwrap();
+        // Header followed by five entries
+        assert_eq!(4 + 5 * 8, value.len());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(None, get_default_acl(Path::new(DIR)).unwrap());
+
+        fs::remove_dir_all(DIR);
+    }
+
+    #[test]
+    fn encode_spec_rejects_invalid_entries() {
+        assert!(encode_spec("u::rwx,o::r--").is_ok());
+        assert!(encode_spec("u:alice:rwx").is_err());
+        assert!(encode_spec("m:1000:rwx").is_err());
+        assert!(encode_spec("u::wrx").is_err());
+    }
+
+    #[test]
+    fn encode_spec_is_little_endian() {
+        assert_eq!(
+            vec![2, 0, 0, 0, 1, 0, 7, 0, 0xff, 0xff, 0xff, 0xff],
+            encode_spec("u::rwx").unwrap()
+        );
+    }
+}
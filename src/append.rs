@@ -1,17 +1,27 @@
-use std::io::{self, Read, Write};
+use std::io::{self, Error, ErrorKind, Write};
 use std::{
-    fs::OpenOptions,
+    fs::{self, File, OpenOptions},
     path::{Path, PathBuf},
 };
 
-use crate::{RollbackableOperation, SingleFileOperation};
+use crate::backup::Backup;
+use crate::{
+    file_size, ignore_not_found, non_empty_path, Change, ChangeKind, DropPolicy, DropState,
+    RollbackableOperation, SingleFileOperation, SpecValue, UndoStep, DEFAULT_BACKUP_BUFFER_SIZE,
+};
 
 /// Appends data to a file
 pub struct AppendFile {
     source: PathBuf,
     temp_dir: PathBuf,
+    backup_dir: Option<PathBuf>,
     backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
     data: Vec<u8>,
+    append_from: Option<PathBuf>,
+    original_len: Option<u64>,
+    drop_state: DropState,
 }
 
 impl AppendFile {
@@ -20,15 +30,84 @@ impl AppendFile {
         Self {
             source: source.as_ref().into(),
             temp_dir: temp_dir.as_ref().into(),
+            backup_dir: None,
             backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            drop_state: DropState::default(),
             data,
+            append_from: None,
+            original_len: None,
         }
     }
+
+    /// Constructs a new AppendFile operation that streams the contents of another file onto the source
+    ///
+    /// Instead of a backup, the length of the source is recorded on execution and rollback truncates it back to that length.
+    /// Appending a file to itself fails on execution with `ErrorKind::InvalidInput`
+    pub fn from_file<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        append_from: PathBuf,
+    ) -> Self {
+        let mut op = Self::new(source, temp_dir, vec![]);
+        op.append_from = Some(append_from);
+        op
+    }
+
+    fn appended_len(&self) -> u64 {
+        match &self.append_from {
+            Some(append_from) => file_size(append_from).unwrap_or(0),
+            None => self.data.len() as u64,
+        }
+    }
+
+    fn append_file(&mut self, append_from: &Path) -> io::Result<()> {
+        let mut reader = File::open(append_from)?;
+        let mut file = OpenOptions::new().append(true).open(&self.source)?;
+
+        // Copying a file onto its own end would never reach the end
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let (a, b) = (reader.metadata()?, file.metadata()?);
+            if a.dev() == b.dev() && a.ino() == b.ino() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Cannot append {} to itself", self.source.display()),
+                ));
+            }
+        }
+
+        self.original_len = Some(file.metadata()?.len());
+        self.drop_state.executed();
+
+        io::copy(&mut reader, &mut file).map(|_| ())
+    }
+
+    /// Creates the backup in `dir` instead of the temp dir
+    ///
+    /// Unlike the temp dir, it is kept when the operation is added to a [scoped](struct.Transaction.html#method.scoped) transaction
+    pub fn with_backup_dir<S: AsRef<Path>>(mut self, dir: S) -> Self {
+        self.backup_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Sets the policy applied when the operation is dropped
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_state.set_policy(policy);
+    }
 }
 
 impl RollbackableOperation for AppendFile {
     fn execute(&mut self) -> io::Result<()> {
+        if let Some(append_from) = self.append_from.clone() {
+            return self.append_file(&append_from);
+        }
+
         self.create_backup_file()?;
+        self.drop_state.executed();
 
         OpenOptions::new()
             .append(true)
@@ -37,16 +116,124 @@ impl RollbackableOperation for AppendFile {
     }
 
     fn rollback(&self) -> io::Result<()> {
-        let mut buffer = Vec::<u8>::new();
-        let mut backup_file = OpenOptions::new().read(true).open(self.get_backup_path())?;
+        self.drop_state.rolled_back();
 
-        backup_file.read_to_end(&mut buffer)?;
+        if self.append_from.is_some() {
+            return match self.original_len {
+                Some(len) => OpenOptions::new()
+                    .write(true)
+                    .open(&self.source)?
+                    .set_len(len),
+                None => Ok(()),
+            };
+        }
 
-        OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(self.get_path())?
-            .write_all(&buffer)
+        Backup::existing_file(self.get_backup_path()).restore(self.get_path())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        match self.append_from {
+            Some(_) => self.original_len.is_none() || self.source.exists(),
+            None => self.get_backup_path().exists(),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let size_before = match self.append_from {
+            Some(_) => self.original_len,
+            None => file_size(self.get_backup_path()),
+        };
+        let size_after = size_before.map(|size| size + self.appended_len());
+
+        vec![Change::new(ChangeKind::Modified, &self.source).with_sizes(size_before, size_after)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.appended_len()
+    }
+
+    fn mark_committed(&mut self) {
+        self.drop_state.committed();
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        // Truncating back to the recorded length has no matching step
+        if self.append_from.is_some() {
+            return None;
+        }
+
+        Some(
+            self.backup_path()
+                .map(|backup| UndoStep::RestoreFile {
+                    backup: backup.into(),
+                    path: self.source.clone(),
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        if self.append_from.is_some() {
+            return vec![(self.source.clone(), self.appended_len())];
+        }
+
+        let backup_dir = self.get_backup_dir().unwrap_or(&self.temp_dir);
+
+        vec![
+            (backup_dir.into(), file_size(&self.source).unwrap_or(0)),
+            (self.source.clone(), self.data.len() as u64),
+        ]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.temp_dir];
+        if let Some(append_from) = &mut self.append_from {
+            paths.push(append_from);
+        }
+        if let Some(backup_dir) = &mut self.backup_dir {
+            paths.push(backup_dir);
+        }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        let mut spec = vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ];
+
+        match &self.append_from {
+            Some(append_from) => spec.push(("append_from", SpecValue::Path(append_from.clone()))),
+            None => spec.push(("data", SpecValue::Bytes(self.data.len()))),
+        }
+
+        spec
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op = AppendFile::new(&self.source, &self.temp_dir, self.data.clone());
+        op.append_from = self.append_from.clone();
+        op.backup_dir = self.backup_dir.clone();
+        op.drop_state = self.drop_state.reset();
+
+        Some(Box::new(op))
     }
 }
 
@@ -66,13 +253,274 @@ impl SingleFileOperation for AppendFile {
     fn get_temp_dir(&self) -> &Path {
         &self.temp_dir
     }
+
+    fn get_backup_dir(&self) -> Option<&Path> {
+        self.backup_dir.as_deref()
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
 }
 
 impl Drop for AppendFile {
     fn drop(&mut self) {
-        match self.dispose() {
-            Err(e) => eprintln!("{}", e),
-            _ => {}
+        if self.drop_state.should_rollback() {
+            if let Err(e) = self.rollback() {
+                eprintln!("{}", e)
+            }
+        }
+
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Appends data to a file, rotating it first when the append would exceed a size cap
+///
+/// On rotation the file is renamed to `<name>.1` and the data is written to a fresh file. An existing `<name>.1` is backed up
+/// in the temp dir before being replaced. A missing file is created. Rollback undoes both the append and the rotation
+pub struct AppendWithRotation {
+    source: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
+    data: Vec<u8>,
+    max_size: u64,
+    created: bool,
+    rotated: bool,
+    replaced_rotation: Option<Backup>,
+}
+
+impl AppendWithRotation {
+    /// Constructs a new AppendWithRotation operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        data: Vec<u8>,
+        max_size: u64,
+    ) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            data,
+            max_size,
+            created: false,
+            rotated: false,
+            replaced_rotation: None,
+        }
+    }
+
+    /// Returns the path the file is rotated to
+    pub fn rotation_path(&self) -> PathBuf {
+        let mut path = self.source.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+
+    /// Returns whether the file was rotated during execution
+    pub fn rotated(&self) -> bool {
+        self.rotated
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotation_path = self.rotation_path();
+
+        if rotation_path.exists() {
+            self.replaced_rotation = Some(Backup::of_file(&rotation_path, &self.temp_dir)?);
+        }
+
+        fs::rename(&self.source, &rotation_path)?;
+        self.rotated = true;
+
+        Ok(())
+    }
+}
+
+impl RollbackableOperation for AppendWithRotation {
+    fn execute(&mut self) -> io::Result<()> {
+        match file_size(&self.source) {
+            Some(size) if size > 0 && size + self.data.len() as u64 > self.max_size => {
+                self.rotate()?
+            }
+            Some(_) => self.create_backup_file()?,
+            None => self.created = true,
+        }
+
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.source)?
+            .write_all(&self.data)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.rotated {
+            ignore_not_found(fs::remove_file(&self.source))?;
+            fs::rename(self.rotation_path(), &self.source)?;
+
+            return match &self.replaced_rotation {
+                Some(backup) => backup.restore(self.rotation_path()),
+                None => Ok(()),
+            };
+        }
+
+        if self.created {
+            return ignore_not_found(fs::remove_file(&self.source));
+        }
+
+        match self.backup_path() {
+            Some(backup) => Backup::existing_file(backup).restore(&self.source),
+            None => Ok(()),
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backup_path().is_none_or(Path::exists)
+            && self.replaced_rotation.as_ref().is_none_or(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backup_path()
+            .into_iter()
+            .chain(self.replaced_rotation.as_ref().map(Backup::path))
+            .collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let size = Some(self.data.len() as u64);
+
+        if self.rotated {
+            return vec![
+                Change::new(ChangeKind::Moved, &self.source).with_dest(self.rotation_path()),
+                Change::new(ChangeKind::Created, &self.source).with_sizes(None, size),
+            ];
+        }
+
+        if self.created {
+            return vec![Change::new(ChangeKind::Created, &self.source).with_sizes(None, size)];
+        }
+
+        let size_before = file_size(self.get_backup_path());
+        let size_after = size_before.map(|before| before + self.data.len() as u64);
+
+        vec![Change::new(ChangeKind::Modified, &self.source).with_sizes(size_before, size_after)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        if self.rotated {
+            steps.push(UndoStep::RemoveFile(self.source.clone()));
+            steps.push(UndoStep::Rename {
+                from: self.rotation_path(),
+                to: self.source.clone(),
+            });
+
+            if let Some(backup) = &self.replaced_rotation {
+                steps.push(UndoStep::RestoreFile {
+                    backup: backup.path().into(),
+                    path: self.rotation_path(),
+                });
+            }
+        } else if self.created {
+            steps.push(UndoStep::RemoveFile(self.source.clone()));
+        } else if let Some(backup) = self.backup_path() {
+            steps.push(UndoStep::RestoreFile {
+                backup: backup.into(),
+                path: self.source.clone(),
+            });
+        }
+
+        Some(steps)
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        vec![
+            (self.temp_dir.clone(), file_size(&self.source).unwrap_or(0)),
+            (self.source.clone(), self.data.len() as u64),
+        ]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(AppendWithRotation::new(
+            &self.source,
+            &self.temp_dir,
+            self.data.clone(),
+            self.max_size,
+        )))
+    }
+}
+
+impl SingleFileOperation for AppendWithRotation {
+    fn get_path(&self) -> &Path {
+        &self.source
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
+}
+
+impl Drop for AppendWithRotation {
+    fn drop(&mut self) {
+        if let Some(backup) = &self.replaced_rotation {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
+        }
+
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
         }
     }
 }
@@ -114,4 +562,78 @@ mod tests {
 
         fs::remove_file(FILE_SOURCE);
     }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn append_from_file_works() {
+        const TARGET: &str = "./append_from_file_target.txt";
+        const APPENDED: &str = "./append_from_file_appended.txt";
+
+        fs::write(TARGET, "Hello ").unwrap();
+        fs::write(APPENDED, "World").unwrap();
+
+        let mut op = AppendFile::from_file(TARGET, TEMP_DIR, APPENDED.into());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(TARGET).unwrap());
+        assert_eq!(5, op.bytes_written());
+        assert!(op.backup_path().is_none());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello ", fs::read_to_string(TARGET).unwrap());
+        assert_eq!("World", fs::read_to_string(APPENDED).unwrap());
+
+        fs::remove_file(TARGET);
+        fs::remove_file(APPENDED);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[allow(unused_must_use)]
+    fn append_from_same_file_fails() {
+        const TARGET: &str = "./append_from_same_file.txt";
+        const LINK: &str = "./append_from_same_file_link.txt";
+
+        fs::write(TARGET, "Hello").unwrap();
+        fs::hard_link(TARGET, LINK).unwrap();
+
+        let mut op = AppendFile::from_file(TARGET, TEMP_DIR, LINK.into());
+        assert_eq!(ErrorKind::InvalidInput, op.execute().unwrap_err().kind());
+        assert_eq!("Hello", fs::read_to_string(TARGET).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello", fs::read_to_string(TARGET).unwrap());
+
+        fs::remove_file(TARGET);
+        fs::remove_file(LINK);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn append_with_rotation_works() {
+        const SOURCE: &str = "./append_with_rotation.log";
+        const ROTATED: &str = "./append_with_rotation.log.1";
+
+        fs::write(SOURCE, "0123456789").unwrap();
+        fs::write(ROTATED, "older").unwrap();
+
+        let mut op = AppendWithRotation::new(SOURCE, TEMP_DIR, b"abcdef".to_vec(), 20);
+        assert_eq!((), op.execute().unwrap());
+        assert!(!op.rotated());
+        assert_eq!("0123456789abcdef", fs::read_to_string(SOURCE).unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("0123456789", fs::read_to_string(SOURCE).unwrap());
+
+        let mut op = AppendWithRotation::new(SOURCE, TEMP_DIR, b"abcdefghijk".to_vec(), 20);
+        assert_eq!((), op.execute().unwrap());
+        assert!(op.rotated());
+        assert_eq!("abcdefghijk", fs::read_to_string(SOURCE).unwrap());
+        assert_eq!("0123456789", fs::read_to_string(ROTATED).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("0123456789", fs::read_to_string(SOURCE).unwrap());
+        assert_eq!("older", fs::read_to_string(ROTATED).unwrap());
+
+        fs::remove_file(SOURCE);
+        fs::remove_file(ROTATED);
+    }
 }
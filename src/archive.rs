@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tar::{Archive, Builder};
+
+/// Size of the two zero blocks that terminate a tar archive
+const TERMINATOR_LEN: u64 = 1024;
+
+/// Appends the backup at `path` to the archive as an entry named `<index>/<slot>`
+///
+/// `slot` tells apart the backups of an operation that keeps several. If appending fails the archive is truncated back to its previous entries
+pub(crate) fn append_backup<S: AsRef<Path>, T: AsRef<Path>>(
+    archive: S,
+    index: usize,
+    slot: usize,
+    path: T,
+) -> io::Result<()> {
+    let archive = archive.as_ref();
+
+    if let Some(parent) = archive.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(archive)?;
+
+    // Overwrite the terminator so the new entry is part of the archive
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(TERMINATOR_LEN);
+    file.seek(SeekFrom::Start(start))?;
+
+    let result = {
+        let mut builder = Builder::new(file);
+        let name = Path::new(&index.to_string()).join(slot.to_string());
+        let path = path.as_ref();
+
+        match path.is_dir() {
+            true => builder.append_dir_all(&name, path),
+            false => builder.append_path_with_name(path, &name),
+        }
+        .and_then(|_| builder.finish())
+    };
+
+    if let Err(e) = result {
+        // Cut off the partial entry, growing the file back fills the terminator with zeros
+        let file = OpenOptions::new().write(true).open(archive)?;
+        file.set_len(start)?;
+        file.set_len(len)?;
+
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn parse_entry(entry_path: &Path) -> io::Result<(usize, usize, &Path)> {
+    let mut components = entry_path.components();
+    let mut next_number = || {
+        components
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .and_then(|c| c.parse::<usize>().ok())
+    };
+
+    match (next_number(), next_number()) {
+        (Some(index), Some(slot)) => Ok((index, slot, components.as_path())),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid backup archive entry",
+        )),
+    }
+}
+
+/// Returns the index and slot of every backup stored in the archive
+pub(crate) fn list_backups<S: AsRef<Path>>(archive: S) -> io::Result<HashSet<(usize, usize)>> {
+    let file = OpenOptions::new().read(true).open(archive)?;
+    let mut backups = HashSet::new();
+
+    for entry in Archive::new(file).entries()? {
+        let entry_path = entry?.path()?.into_owned();
+        let (index, slot, _) = parse_entry(&entry_path)?;
+        backups.insert((index, slot));
+    }
+
+    Ok(backups)
+}
+
+/// Extracts every backup entry from the archive to the path returned by `dest` for its index and slot
+///
+/// Entries for which `dest` returns `None` are skipped
+pub(crate) fn extract_backups<S: AsRef<Path>, F: Fn(usize, usize) -> Option<PathBuf>>(
+    archive: S,
+    dest: F,
+) -> io::Result<()> {
+    let file = OpenOptions::new().read(true).open(archive)?;
+    let mut archive = Archive::new(file);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let (index, slot, rest) = parse_entry(&entry_path)?;
+
+        if let Some(backup_path) = dest(index, slot) {
+            let target = if rest.as_os_str().is_empty() {
+                backup_path
+            } else {
+                backup_path.join(rest)
+            };
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            entry.unpack(&target)?;
+        }
+    }
+
+    Ok(())
+}
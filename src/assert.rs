@@ -0,0 +1,537 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[cfg(feature = "hash")]
+use crate::hash_file;
+use crate::{existing_ancestor, RollbackableOperation};
+
+/// Checks that a file exists and optionally matches an expected size and SHA-256 digest
+///
+/// The operation makes no changes, so it can be used to encode preconditions inside a transaction.
+/// Checking the digest requires the `hash` feature
+pub struct AssertFile {
+    path: PathBuf,
+    expected_size: Option<u64>,
+    expected_hash: Option<String>,
+}
+
+impl AssertFile {
+    /// Constructs a new AssertFile operation
+    pub fn new<S: AsRef<Path>>(
+        path: S,
+        expected_size: Option<u64>,
+        expected_hash: Option<String>,
+    ) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            expected_size,
+            expected_hash: expected_hash.map(|hex| hex.to_lowercase()),
+        }
+    }
+
+    #[cfg(feature = "hash")]
+    fn check_hash(&self, expected: &str) -> io::Result<()> {
+        let actual = hash_file(&self.path)?;
+
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Hash mismatch for {}: expected {}, found {}",
+                    self.path.display(),
+                    expected,
+                    actual
+                ),
+            ))
+        }
+    }
+
+    #[cfg(not(feature = "hash"))]
+    fn check_hash(&self, _expected: &str) -> io::Result<()> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "Checking the hash requires the hash feature",
+        ))
+    }
+}
+
+impl RollbackableOperation for AssertFile {
+    fn execute(&mut self) -> io::Result<()> {
+        let metadata = fs::metadata(&self.path)?;
+
+        if !metadata.is_file() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a file", self.path.display()),
+            ));
+        }
+
+        if let Some(expected) = self.expected_size {
+            if metadata.len() != expected {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Size mismatch for {}: expected {}, found {}",
+                        self.path.display(),
+                        expected,
+                        metadata.len()
+                    ),
+                ));
+            }
+        }
+
+        match &self.expected_hash {
+            Some(expected) => self.check_hash(expected),
+            None => Ok(()),
+        }
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(AssertFile::new(
+            &self.path,
+            self.expected_size,
+            self.expected_hash.clone(),
+        )))
+    }
+}
+
+/// Checks that the filesystem containing a path has at least a number of bytes available
+///
+/// Placed right before a large write, it aborts the transaction before the disk fills up.
+/// The path does not need to exist yet, its closest existing ancestor is checked
+pub struct RequireFreeSpace {
+    path: PathBuf,
+    bytes: u64,
+}
+
+impl RequireFreeSpace {
+    /// Constructs a new RequireFreeSpace operation
+    pub fn new<S: AsRef<Path>>(path: S, bytes: u64) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            bytes,
+        }
+    }
+}
+
+impl RollbackableOperation for RequireFreeSpace {
+    fn execute(&mut self) -> io::Result<()> {
+        let existing = existing_ancestor(&self.path);
+        let available = fs2::available_space(&existing)?;
+
+        if available < self.bytes {
+            return Err(Error::other(format!(
+                "insufficient space on {}: {} bytes required, {} available",
+                existing.display(),
+                self.bytes,
+                available
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(RequireFreeSpace::new(&self.path, self.bytes)))
+    }
+}
+
+/// Kind of filesystem entry expected by [AssertPathType](struct.AssertPathType.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathType {
+    /// A regular file
+    File,
+    /// A directory
+    Dir,
+    /// A symlink, which is not followed
+    Symlink,
+}
+
+/// Checks that a path exists and is of the expected kind
+///
+/// Symlinks are not followed, so a symlink to a directory only matches [PathType::Symlink](enum.PathType.html#variant.Symlink).
+/// Fails with `ErrorKind::Other` on a mismatch or if the path does not exist
+pub struct AssertPathType {
+    path: PathBuf,
+    expected: PathType,
+}
+
+impl AssertPathType {
+    /// Constructs a new AssertPathType operation
+    pub fn new<S: AsRef<Path>>(path: S, expected: PathType) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            expected,
+        }
+    }
+}
+
+impl RollbackableOperation for AssertPathType {
+    fn execute(&mut self) -> io::Result<()> {
+        let file_type = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) => metadata.file_type(),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(Error::other(format!(
+                    "{} does not exist, expected {:?}",
+                    self.path.display(),
+                    self.expected
+                )));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let actual = if file_type.is_symlink() {
+            PathType::Symlink
+        } else if file_type.is_dir() {
+            PathType::Dir
+        } else {
+            PathType::File
+        };
+
+        if actual != self.expected {
+            return Err(Error::other(format!(
+                "{} is a {:?}, expected {:?}",
+                self.path.display(),
+                actual,
+                self.expected
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(AssertPathType::new(&self.path, self.expected)))
+    }
+}
+
+/// Checks that a directory exists and has no entries
+///
+/// Fails with `ErrorKind::Other` if the directory is not empty, does not exist or is not a directory
+pub struct AssertEmptyDir {
+    path: PathBuf,
+}
+
+impl AssertEmptyDir {
+    /// Constructs a new AssertEmptyDir operation
+    pub fn new<S: AsRef<Path>>(path: S) -> Self {
+        Self {
+            path: path.as_ref().into(),
+        }
+    }
+}
+
+impl RollbackableOperation for AssertEmptyDir {
+    fn execute(&mut self) -> io::Result<()> {
+        if !self.path.is_dir() {
+            return Err(Error::other(format!(
+                "{} does not exist or is not a directory",
+                self.path.display()
+            )));
+        }
+
+        if fs::read_dir(&self.path)?.next().is_some() {
+            return Err(Error::other(format!(
+                "{}: directory not empty",
+                self.path.display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(AssertEmptyDir::new(&self.path)))
+    }
+}
+
+/// Checks that a file was not modified since its state was captured
+///
+/// Acts as an optimistic lock: placed before a write, it aborts the transaction with `ErrorKind::Other` if another process
+/// changed the file's modification time or size in the meantime. With the `hash` feature a SHA-256 digest can be checked as well
+pub struct GuardUnchanged {
+    path: PathBuf,
+    mtime: SystemTime,
+    size: u64,
+    hash: Option<String>,
+}
+
+impl GuardUnchanged {
+    /// Constructs a new GuardUnchanged operation from a previously observed modification time and size
+    pub fn new<S: AsRef<Path>>(path: S, mtime: SystemTime, size: u64) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            mtime,
+            size,
+            hash: None,
+        }
+    }
+
+    /// Constructs a new GuardUnchanged operation from the current state of the file
+    pub fn capture<S: AsRef<Path>>(path: S) -> io::Result<Self> {
+        let metadata = fs::metadata(&path)?;
+
+        Ok(Self::new(path, metadata.modified()?, metadata.len()))
+    }
+
+    /// Also requires the file to still have the given SHA-256 digest
+    #[cfg(feature = "hash")]
+    pub fn with_hash<S: AsRef<str>>(mut self, hex: S) -> Self {
+        self.hash = Some(hex.as_ref().to_lowercase());
+        self
+    }
+
+    fn changed(&self, what: &str) -> Error {
+        Error::other(format!(
+            "{} was modified since it was captured: {} changed",
+            self.path.display(),
+            what
+        ))
+    }
+}
+
+impl RollbackableOperation for GuardUnchanged {
+    fn execute(&mut self) -> io::Result<()> {
+        let metadata = fs::metadata(&self.path)?;
+
+        if metadata.len() != self.size {
+            return Err(self.changed("size"));
+        }
+
+        if metadata.modified()? != self.mtime {
+            return Err(self.changed("modification time"));
+        }
+
+        #[cfg(feature = "hash")]
+        if let Some(expected) = &self.hash {
+            if hash_file(&self.path)? != *expected {
+                return Err(self.changed("content"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(GuardUnchanged {
+            path: self.path.clone(),
+            mtime: self.mtime,
+            size: self.size,
+            hash: self.hash.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "./assert_file.txt";
+    const MISSING: &str = "./assert_file_missing.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn assert_file_works() {
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        assert_eq!((), AssertFile::new(SOURCE, None, None).execute().unwrap());
+        assert_eq!(
+            (),
+            AssertFile::new(SOURCE, Some(11), None).execute().unwrap()
+        );
+        assert_eq!(
+            ErrorKind::InvalidData,
+            AssertFile::new(SOURCE, Some(5), None)
+                .execute()
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::NotFound,
+            AssertFile::new(MISSING, None, None)
+                .execute()
+                .unwrap_err()
+                .kind()
+        );
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[cfg(feature = "hash")]
+    #[allow(unused_must_use)]
+    fn assert_file_hash_works() {
+        const HASH_SOURCE: &str = "./assert_file_hash.txt";
+        const HASH: &str = "A591A6D40BF420404A011733CFB7B190D62C65BF0BCDA32B57B277D9AD9F146E";
+
+        fs::write(HASH_SOURCE, "Hello World").unwrap();
+
+        let mut op = AssertFile::new(HASH_SOURCE, Some(11), Some(HASH.into()));
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        let mut op = AssertFile::new(HASH_SOURCE, None, Some("00".into()));
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+
+        fs::remove_file(HASH_SOURCE);
+    }
+
+    const EMPTY_DIR: &str = "./assert_empty_dir";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn assert_empty_dir_works() {
+        fs::create_dir_all(EMPTY_DIR).unwrap();
+
+        let mut op = AssertEmptyDir::new(EMPTY_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        fs::remove_dir_all(EMPTY_DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn assert_empty_dir_fails_on_entries() {
+        const NON_EMPTY_DIR: &str = "./assert_empty_dir_non_empty";
+
+        fs::create_dir_all(NON_EMPTY_DIR).unwrap();
+        fs::write(Path::new(NON_EMPTY_DIR).join(".hidden"), "").unwrap();
+
+        let err = AssertEmptyDir::new(NON_EMPTY_DIR).execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("directory not empty"));
+
+        fs::remove_dir_all(NON_EMPTY_DIR);
+    }
+
+    #[test]
+    fn assert_empty_dir_fails_on_missing() {
+        let err = AssertEmptyDir::new(MISSING).execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn assert_path_type_works() {
+        const TYPED_FILE: &str = "./assert_path_type.txt";
+        const TYPED_DIR: &str = "./assert_path_type_dir";
+
+        fs::write(TYPED_FILE, "Hello World").unwrap();
+        fs::create_dir_all(TYPED_DIR).unwrap();
+
+        let mut op = AssertPathType::new(TYPED_FILE, PathType::File);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(
+            (),
+            AssertPathType::new(TYPED_DIR, PathType::Dir)
+                .execute()
+                .unwrap()
+        );
+
+        for (path, expected) in &[
+            (TYPED_FILE, PathType::Dir),
+            (TYPED_DIR, PathType::File),
+            (TYPED_DIR, PathType::Symlink),
+            (MISSING, PathType::File),
+        ] {
+            let err = AssertPathType::new(path, *expected).execute().unwrap_err();
+            assert_eq!(ErrorKind::Other, err.kind());
+        }
+
+        #[cfg(unix)]
+        {
+            let link = Path::new(TYPED_DIR).join("link");
+            std::os::unix::fs::symlink("..", &link).unwrap();
+
+            assert_eq!(
+                (),
+                AssertPathType::new(&link, PathType::Symlink)
+                    .execute()
+                    .unwrap()
+            );
+            assert!(AssertPathType::new(&link, PathType::Dir).execute().is_err());
+        }
+
+        fs::remove_file(TYPED_FILE);
+        fs::remove_dir_all(TYPED_DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn guard_unchanged_works() {
+        const GUARDED: &str = "./guard_unchanged.txt";
+
+        fs::write(GUARDED, "Hello World").unwrap();
+
+        let mut op = GuardUnchanged::capture(GUARDED).unwrap();
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        fs::write(GUARDED, "Hello World, again").unwrap();
+
+        let err = op.execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("was modified"));
+
+        fs::remove_file(GUARDED);
+    }
+
+    #[test]
+    fn require_free_space_works() {
+        let mut op = RequireFreeSpace::new("./require_free_space/missing.txt", 1);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        let mut op = RequireFreeSpace::new("./require_free_space/missing.txt", u64::MAX);
+        let err = op.execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("insufficient space"));
+    }
+}
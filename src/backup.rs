@@ -0,0 +1,292 @@
+//! Creation, restoration and disposal of backups
+//!
+//! Operations that need to restore a file or directory on rollback keep a [Backup](struct.Backup.html) of it
+//! inside their temp dir. The type can also be used directly to manage backups outside of an operation
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use uuid::Uuid;
+
+#[cfg(feature = "hash")]
+use crate::hash_file;
+use crate::{
+    copy_dir, copy_file_contents, ignore_not_found, unique_path, DEFAULT_BACKUP_BUFFER_SIZE,
+};
+
+/// A backup of a file or directory
+///
+/// Backups are stored under a unique name inside a temp dir and are not removed automatically
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    path: PathBuf,
+    is_dir: bool,
+}
+
+impl Backup {
+    /// Backs up the file at `source` into `temp_dir`
+    pub fn of_file<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T) -> io::Result<Self> {
+        Self::of_file_with_buffer_size(source, temp_dir, DEFAULT_BACKUP_BUFFER_SIZE)
+    }
+
+    /// Backs up the file at `source` into `temp_dir`, streaming it through a buffer of `buffer_size` bytes
+    pub fn of_file_with_buffer_size<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        buffer_size: usize,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&temp_dir)?;
+
+        let backup = Self::existing_file(unique_path(temp_dir));
+        copy_file_contents(source, &backup.path, buffer_size)?;
+
+        Ok(backup)
+    }
+
+    /// Backs up the file at `source` into `temp_dir`, sharing its content with identical files backed up in `store`
+    ///
+    /// The file is copied into `store` under its SHA-256 digest unless an identical file is already there, and the
+    /// backup is a hard link to that copy. Falls back to copying the file if the link cannot be created
+    #[cfg(feature = "hash")]
+    pub fn of_file_in_store<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        store: U,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&temp_dir)?;
+        fs::create_dir_all(&store)?;
+
+        let blob = store.as_ref().join(hash_file(&source)?);
+
+        if !blob.exists() {
+            // Copy under a unique name first so a partial copy is never mistaken for the blob
+            let partial = unique_path(&store);
+            copy_file_contents(&source, &partial, DEFAULT_BACKUP_BUFFER_SIZE)?;
+            fs::rename(&partial, &blob)?;
+        }
+
+        let backup = Self::existing_file(unique_path(temp_dir));
+        if fs::hard_link(&blob, &backup.path).is_err() {
+            copy_file_contents(&blob, &backup.path, DEFAULT_BACKUP_BUFFER_SIZE)?;
+        }
+
+        Ok(backup)
+    }
+
+    /// Backs up the directory at `source` and its contents into `temp_dir`
+    pub fn of_dir<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T) -> io::Result<Self> {
+        fs::create_dir_all(&temp_dir)?;
+
+        let backup = Self::existing_dir(unique_path(temp_dir));
+        copy_dir(source, &backup.path)?;
+
+        Ok(backup)
+    }
+
+    /// Refers to an existing backup file
+    pub fn existing_file<S: AsRef<Path>>(path: S) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            is_dir: false,
+        }
+    }
+
+    /// Refers to an existing backup directory
+    pub fn existing_dir<S: AsRef<Path>>(path: S) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            is_dir: true,
+        }
+    }
+
+    /// Returns path to the backup
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the backup is of a directory
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns whether the backup still exists
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Restores the backup to `dest`
+    ///
+    /// A file backup overwrites the contents of `dest`. A directory backup replaces `dest` entirely
+    pub fn restore<S: AsRef<Path>>(&self, dest: S) -> io::Result<()> {
+        if self.is_dir {
+            ignore_not_found(fs::remove_dir_all(&dest))?;
+            copy_dir(&self.path, dest)
+        } else {
+            copy_file_contents(&self.path, dest, DEFAULT_BACKUP_BUFFER_SIZE)
+        }
+    }
+
+    /// Removes the backup
+    ///
+    /// A backup that does not exist is not an `Error`
+    pub fn dispose(&self) -> io::Result<()> {
+        if self.is_dir {
+            ignore_not_found(fs::remove_dir_all(&self.path))
+        } else {
+            ignore_not_found(fs::remove_file(&self.path))
+        }
+    }
+}
+
+/// Lists the backups left in `temp_dir`
+///
+/// Every entry named like a backup, i.e. a UUID, is listed. Backups cannot be traced back to a transaction,
+/// so this is only meaningful once no transaction using `temp_dir` is alive, e.g. after a crash
+pub fn list_orphaned_backups(temp_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut backups = vec![];
+
+    for entry in fs::read_dir(temp_dir)? {
+        let entry = entry?;
+        let is_backup = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| Uuid::parse_str(name).is_ok());
+
+        if is_backup {
+            backups.push(entry.path());
+        }
+    }
+
+    backups.sort();
+
+    Ok(backups)
+}
+
+/// Removes the [orphaned backups](fn.list_orphaned_backups.html) in `temp_dir` last modified more than `older_than` ago
+///
+/// Returns the number of backups removed
+pub fn clean_orphaned_backups(temp_dir: &Path, older_than: Duration) -> io::Result<usize> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+
+    for path in list_orphaned_backups(temp_dir)? {
+        let metadata = fs::symlink_metadata(&path)?;
+        let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+
+        if age <= older_than {
+            continue;
+        }
+
+        let backup = match metadata.is_dir() {
+            true => Backup::existing_dir(&path),
+            false => Backup::existing_file(&path),
+        };
+
+        backup.dispose()?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_SOURCE: &str = "./backup_file_source.txt";
+    const DIR_SOURCE: &str = "./backup_dir_source";
+    const TEMP_DIR: &str = "./tmp/";
+    const ORPHAN_DIR: &str = "./backup_orphans";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn clean_orphaned_backups_works() {
+        let dir = Path::new(ORPHAN_DIR);
+        fs::create_dir_all(dir).unwrap();
+
+        let old = unique_path(dir);
+        let new = unique_path(dir);
+        let unrelated = dir.join("notes.txt");
+        fs::write(&old, "old").unwrap();
+        fs::write(&new, "new").unwrap();
+        fs::write(&unrelated, "notes").unwrap();
+
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&unrelated)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+
+        let mut expected = vec![old.clone(), new.clone()];
+        expected.sort();
+        assert_eq!(expected, list_orphaned_backups(dir).unwrap());
+
+        assert_eq!(
+            1,
+            clean_orphaned_backups(dir, Duration::from_secs(60 * 60)).unwrap()
+        );
+        assert!(!old.exists());
+        assert!(new.exists());
+        assert!(unrelated.exists());
+
+        fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn backup_file_lifecycle_works() {
+        fs::write(FILE_SOURCE, "Hello World").unwrap();
+
+        let backup = Backup::of_file(FILE_SOURCE, TEMP_DIR).unwrap();
+        assert!(!backup.is_dir());
+        assert!(backup.path().starts_with(TEMP_DIR));
+        assert_eq!("Hello World", fs::read_to_string(backup.path()).unwrap());
+
+        fs::write(FILE_SOURCE, "Goodbye").unwrap();
+        assert_eq!((), backup.restore(FILE_SOURCE).unwrap());
+        assert_eq!("Hello World", fs::read_to_string(FILE_SOURCE).unwrap());
+
+        assert_eq!((), backup.dispose().unwrap());
+        assert!(!backup.exists());
+        assert_eq!((), backup.dispose().unwrap());
+
+        fs::remove_file(FILE_SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn backup_dir_lifecycle_works() {
+        fs::create_dir_all(Path::new(DIR_SOURCE).join("inner")).unwrap();
+        fs::write(Path::new(DIR_SOURCE).join("inner/file.txt"), "Hello").unwrap();
+
+        let backup = Backup::of_dir(DIR_SOURCE, TEMP_DIR).unwrap();
+        assert!(backup.is_dir());
+        assert!(backup.path().join("inner/file.txt").exists());
+
+        fs::remove_dir_all(DIR_SOURCE).unwrap();
+        fs::create_dir(DIR_SOURCE).unwrap();
+        fs::write(Path::new(DIR_SOURCE).join("stray.txt"), "Stray").unwrap();
+
+        assert_eq!((), backup.restore(DIR_SOURCE).unwrap());
+        assert!(!Path::new(DIR_SOURCE).join("stray.txt").exists());
+        assert_eq!(
+            "Hello",
+            fs::read_to_string(Path::new(DIR_SOURCE).join("inner/file.txt")).unwrap()
+        );
+
+        assert_eq!((), backup.dispose().unwrap());
+        assert!(!backup.exists());
+
+        fs::remove_dir_all(DIR_SOURCE);
+    }
+}
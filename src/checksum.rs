@@ -0,0 +1,352 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{hash_file, hash_reader, Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Returns the path of the checksum sidecar of `source`, ie. `source` with `.sha256` appended
+fn sidecar_path(source: &Path) -> PathBuf {
+    let mut sidecar = source.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    sidecar.into()
+}
+
+/// Writes the SHA-256 digest of a file to a `.sha256` sidecar next to it
+///
+/// The sidecar uses the `sha256sum` format, ie. the hex digest followed by two spaces and the file name.
+/// An existing sidecar is backed up and restored on rollback, otherwise the sidecar is removed
+pub struct WriteChecksum {
+    source: PathBuf,
+    file: FileBackup,
+}
+
+impl WriteChecksum {
+    /// Constructs a new WriteChecksum operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            file: FileBackup::new(sidecar_path(source.as_ref()), temp_dir),
+        }
+    }
+
+    /// Returns the path of the sidecar
+    pub fn sidecar_path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+impl RollbackableOperation for WriteChecksum {
+    fn execute(&mut self) -> io::Result<()> {
+        let name = self
+            .source
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let line = format!("{}  {}\n", hash_file(&self.source)?, name);
+
+        if self.file.path().exists() {
+            self.file.create()?;
+        }
+
+        self.file.write(line.as_bytes())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.revert(self.file.written().is_some())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(self.file.written())]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.file.written().unwrap_or(0)
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.revert_steps(self.file.written().is_some()))
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = self.file.paths_mut();
+        paths.push(&mut self.source);
+        paths
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(WriteChecksum::new(
+            &self.source,
+            self.file.temp_dir(),
+        )))
+    }
+}
+
+/// Checks a file against the digest in its `.sha256` sidecar
+///
+/// The sidecar is read as written by [WriteChecksum](struct.WriteChecksum.html) or `sha256sum`: only the first
+/// whitespace separated field is used. A mismatch fails with `ErrorKind::InvalidData`, a missing sidecar with `ErrorKind::NotFound`.
+/// The operation makes no changes
+pub struct VerifyChecksum {
+    source: PathBuf,
+    sidecar: PathBuf,
+}
+
+impl VerifyChecksum {
+    /// Constructs a new VerifyChecksum operation
+    pub fn new<S: AsRef<Path>>(source: S) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            sidecar: sidecar_path(source.as_ref()),
+        }
+    }
+}
+
+impl RollbackableOperation for VerifyChecksum {
+    fn execute(&mut self) -> io::Result<()> {
+        let contents = fs::read_to_string(&self.sidecar)?;
+        let expected = contents
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let actual = hash_file(&self.source)?;
+
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Checksum mismatch for {}: expected {}, found {}",
+                    self.source.display(),
+                    expected,
+                    actual
+                ),
+            ))
+        }
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.sidecar]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(VerifyChecksum::new(&self.source)))
+    }
+}
+
+/// Verifies the SHA-256 digest of a file against an expected hex digest
+///
+/// The file is streamed through the hasher, so it is never loaded whole. A mismatch fails with `ErrorKind::InvalidData`
+pub struct VerifyHash {
+    source: PathBuf,
+    expected: String,
+    progress: Option<Box<dyn FnMut(u64)>>,
+}
+
+impl VerifyHash {
+    /// Constructs a new VerifyHash operation
+    pub fn new<S: AsRef<Path>, T: AsRef<str>>(source: S, expected: T) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            expected: expected.as_ref().to_lowercase(),
+            progress: None,
+        }
+    }
+
+    /// Calls `progress` with the number of bytes hashed so far as the file is streamed
+    pub fn with_progress(mut self, progress: Box<dyn FnMut(u64)>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+/// Reports the number of bytes read so far to a callback
+struct ProgressReader<'a, R> {
+    inner: R,
+    read: u64,
+    progress: &'a mut dyn FnMut(u64),
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if n > 0 {
+            self.read += n as u64;
+            (self.progress)(self.read);
+        }
+
+        Ok(n)
+    }
+}
+
+impl RollbackableOperation for VerifyHash {
+    fn execute(&mut self) -> io::Result<()> {
+        let file = fs::File::open(&self.source)?;
+
+        let actual = match &mut self.progress {
+            Some(progress) => hash_reader(ProgressReader {
+                inner: file,
+                read: 0,
+                progress: progress.as_mut(),
+            })?,
+            None => hash_reader(file)?,
+        };
+
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Hash mismatch for {}: expected {}, found {}",
+                    self.source.display(),
+                    self.expected,
+                    actual
+                ),
+            ))
+        }
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("expected", SpecValue::Text(self.expected.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        match self.progress {
+            Some(_) => None,
+            None => Some(Box::new(VerifyHash::new(&self.source, &self.expected))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "./write_checksum.txt";
+    const SIDECAR: &str = "./write_checksum.txt.sha256";
+    const TEMP_DIR: &str = "./tmp/";
+    const HASH: &str = "a591a6d40bf420404a011733cfb7b190d62c65bf0bcda32b57b277d9ad9f146e";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_checksum_works() {
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        let mut op = WriteChecksum::new(SOURCE, TEMP_DIR);
+        assert_eq!(Path::new(SIDECAR), op.sidecar_path());
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            format!("{}  write_checksum.txt\n", HASH),
+            fs::read_to_string(SIDECAR).unwrap()
+        );
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(SIDECAR).exists());
+
+        fs::write(SIDECAR, "stale").unwrap();
+        let mut op = WriteChecksum::new(SOURCE, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert!(fs::read_to_string(SIDECAR).unwrap().starts_with(HASH));
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("stale", fs::read_to_string(SIDECAR).unwrap());
+
+        fs::remove_file(SOURCE);
+        fs::remove_file(SIDECAR);
+    }
+
+    const VERIFY_SOURCE: &str = "./verify_checksum.txt";
+    const VERIFY_SIDECAR: &str = "./verify_checksum.txt.sha256";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn verify_checksum_works() {
+        fs::write(VERIFY_SOURCE, "Hello World").unwrap();
+
+        let mut op = VerifyChecksum::new(VERIFY_SOURCE);
+        assert_eq!(ErrorKind::NotFound, op.execute().unwrap_err().kind());
+
+        fs::write(
+            VERIFY_SIDECAR,
+            format!("{}  verify_checksum.txt\n", HASH.to_uppercase()),
+        )
+        .unwrap();
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        fs::write(VERIFY_SOURCE, "Hello World!").unwrap();
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+
+        fs::write(VERIFY_SIDECAR, "").unwrap();
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+
+        fs::remove_file(VERIFY_SOURCE);
+        fs::remove_file(VERIFY_SIDECAR);
+    }
+
+    const LARGE_SOURCE: &str = "./verify_hash_large.bin";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn verify_hash_works() {
+        let data: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        fs::write(LARGE_SOURCE, &data).unwrap();
+        let expected = hash_file(LARGE_SOURCE).unwrap();
+
+        let progress = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let reported = progress.clone();
+
+        let mut op = VerifyHash::new(LARGE_SOURCE, expected.to_uppercase())
+            .with_progress(Box::new(move |hashed| reported.borrow_mut().push(hashed)));
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        let progress = progress.borrow();
+        assert!(progress.len() > 1);
+        assert!(progress.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Some(&(data.len() as u64)), progress.last());
+
+        let mut op = VerifyHash::new(LARGE_SOURCE, HASH);
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+
+        fs::remove_file(LARGE_SOURCE);
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::util::contents_equal;
+use crate::{Change, ChangeKind, RollbackableOperation, UndoStep};
+
+/// Writes data to a file only if its current content matches the expected content
+///
+/// Fails with `ErrorKind::Other` if the content does not match, which makes it suitable for optimistic concurrency
+pub struct CompareAndWrite {
+    file: FileBackup,
+    expected: Vec<u8>,
+    new: Vec<u8>,
+}
+
+impl CompareAndWrite {
+    /// Constructs a new CompareAndWrite operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        expected: Vec<u8>,
+        new: Vec<u8>,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            expected,
+            new,
+        }
+    }
+}
+
+impl RollbackableOperation for CompareAndWrite {
+    fn execute(&mut self) -> io::Result<()> {
+        if !contents_equal(self.file.path(), &self.expected)? {
+            return Err(Error::other("precondition failed"));
+        }
+
+        self.file.create()?;
+        self.file.write(&self.new)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.restore()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, self.file.path())
+            .with_sizes(self.file.backup_size(), Some(self.new.len() as u64))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.file.written().unwrap_or(0)
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.restore_step().into_iter().collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CompareAndWrite::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.expected.clone(),
+            self.new.clone(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::ErrorKind;
+
+    const FILE_SOURCE: &str = "./compare_and_write_source.txt";
+    const MISMATCH_SOURCE: &str = "./compare_and_write_mismatch.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const INITIAL_DATA: &[u8] = "Yellow World".as_bytes();
+    const WRITTEN_DATA: &[u8] = "Hello".as_bytes();
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn compare_and_write_works() {
+        fs::write(FILE_SOURCE, INITIAL_DATA).unwrap();
+
+        let mut op = CompareAndWrite::new(
+            FILE_SOURCE,
+            TEMP_DIR,
+            INITIAL_DATA.to_vec(),
+            WRITTEN_DATA.to_vec(),
+        );
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(WRITTEN_DATA, fs::read(FILE_SOURCE).unwrap().as_slice());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(FILE_SOURCE).unwrap().as_slice());
+
+        fs::remove_file(FILE_SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn compare_and_write_mismatch_fails() {
+        fs::write(MISMATCH_SOURCE, INITIAL_DATA).unwrap();
+
+        let mut op = CompareAndWrite::new(
+            MISMATCH_SOURCE,
+            TEMP_DIR,
+            WRITTEN_DATA.to_vec(),
+            WRITTEN_DATA.to_vec(),
+        );
+
+        let err = op.execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert_eq!("precondition failed", err.to_string());
+        assert_eq!(INITIAL_DATA, fs::read(MISMATCH_SOURCE).unwrap().as_slice());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(MISMATCH_SOURCE).unwrap().as_slice());
+
+        fs::remove_file(MISMATCH_SOURCE);
+    }
+}
@@ -4,12 +4,25 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{copy_dir, DirectoryOperation, RollbackableOperation};
+use crate::util::{files_equal, glob_matches};
+use crate::{
+    copy_dir, file_size, non_empty_path, Change, ChangeKind, DirectoryOperation,
+    RollbackableOperation, SpecValue, UndoStep,
+};
 
 /// Copies a file to destination
 pub struct CopyFile {
     source: PathBuf,
     dest: PathBuf,
+    copied: u64,
+    skip_if_identical: bool,
+    skipped: bool,
+    #[cfg(target_os = "linux")]
+    direct_io: bool,
+    #[cfg(target_os = "linux")]
+    sparse: bool,
+    #[cfg(target_os = "linux")]
+    sequential_hint: bool,
 }
 
 impl CopyFile {
@@ -18,21 +31,311 @@ impl CopyFile {
         Self {
             source: source.as_ref().into(),
             dest: dest.as_ref().into(),
+            copied: 0,
+            skip_if_identical: false,
+            skipped: false,
+            #[cfg(target_os = "linux")]
+            direct_io: false,
+            #[cfg(target_os = "linux")]
+            sparse: false,
+            #[cfg(target_os = "linux")]
+            sequential_hint: false,
         }
     }
+
+    /// Skips the copy when the destination already has the same contents as the source
+    ///
+    /// A skipped copy leaves the destination in place on rollback
+    pub fn with_skip_if_identical(mut self, skip_if_identical: bool) -> Self {
+        self.skip_if_identical = skip_if_identical;
+        self
+    }
+
+    /// Copies the file with `O_DIRECT`, bypassing the page cache
+    ///
+    /// Falls back to a buffered copy if the filesystem does not support direct IO
+    #[cfg(target_os = "linux")]
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// Copies only the data regions of a sparse file and recreates its holes in the destination
+    ///
+    /// Falls back to a regular copy if the filesystem cannot report holes. Takes precedence over [with_direct_io](#method.with_direct_io)
+    #[cfg(target_os = "linux")]
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Advises the kernel that the source is read sequentially and drops its pages from the cache once copied
+    ///
+    /// Avoids evicting other data when copying large files. The hint is best effort and ignored where unsupported
+    #[cfg(target_os = "linux")]
+    pub fn with_sequential_hint(mut self, sequential_hint: bool) -> Self {
+        self.sequential_hint = sequential_hint;
+        self
+    }
 }
 
 impl RollbackableOperation for CopyFile {
     fn execute(&mut self) -> io::Result<()> {
-        match fs::copy(&self.source, &self.dest) {
-            Ok(_v) => Ok(()),
-            Err(e) => Err(e),
+        if self.skip_if_identical && self.dest.is_file() && files_equal(&self.source, &self.dest)? {
+            self.skipped = true;
+            return Ok(());
         }
+
+        #[cfg(target_os = "linux")]
+        if self.sparse {
+            match sparse::copy(&self.source, &self.dest) {
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                result => {
+                    self.copied = result?;
+                    return Ok(());
+                }
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.direct_io {
+            match direct::copy(&self.source, &self.dest) {
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                result => {
+                    self.copied = result?;
+                    return Ok(());
+                }
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.sequential_hint {
+            self.copied = sequential::copy(&self.source, &self.dest)?;
+            return Ok(());
+        }
+
+        self.copied = fs::copy(&self.source, &self.dest)?;
+
+        Ok(())
     }
 
     fn rollback(&self) -> io::Result<()> {
+        if self.skipped {
+            return Ok(());
+        }
+
         fs::remove_file(&self.dest)
     }
+
+    fn changes(&self) -> Vec<Change> {
+        if self.skipped {
+            return vec![];
+        }
+
+        vec![Change::new(ChangeKind::Created, &self.dest).with_sizes(None, Some(self.copied))]
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.copied
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if self.skipped {
+            return Some(vec![]);
+        }
+
+        Some(vec![UndoStep::RemoveFile(self.dest.clone())])
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        vec![(self.dest.clone(), file_size(&self.source).unwrap_or(0))]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.dest]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("dest", SpecValue::Path(self.dest.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CopyFile {
+            skip_if_identical: self.skip_if_identical,
+            #[cfg(target_os = "linux")]
+            direct_io: self.direct_io,
+            #[cfg(target_os = "linux")]
+            sparse: self.sparse,
+            #[cfg(target_os = "linux")]
+            sequential_hint: self.sequential_hint,
+            ..CopyFile::new(&self.source, &self.dest)
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod direct {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::Path;
+
+    const ALIGNMENT: usize = 4096;
+    const BUFFER_SIZE: usize = 256 * ALIGNMENT;
+
+    /// Copies `source` to `dest` with `O_DIRECT`, returning the number of bytes copied
+    ///
+    /// Fails with `EINVAL` if the filesystem cannot honour the alignment requirements
+    pub(super) fn copy(source: &Path, dest: &Path) -> io::Result<u64> {
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(source)?;
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(dest)?;
+
+        let mut storage = vec![0; BUFFER_SIZE + ALIGNMENT];
+        let offset = storage.as_ptr().align_offset(ALIGNMENT);
+        let buffer = &mut storage[offset..offset + BUFFER_SIZE];
+        let mut copied = 0;
+
+        loop {
+            let read = read_full(&mut reader, buffer)?;
+
+            if read == 0 {
+                break;
+            }
+
+            let padded = read.div_ceil(ALIGNMENT) * ALIGNMENT;
+            buffer[read..padded].iter_mut().for_each(|byte| *byte = 0);
+            writer.write_all(&buffer[..padded])?;
+            copied += read as u64;
+
+            if read < buffer.len() {
+                break;
+            }
+        }
+
+        writer.set_len(copied)?;
+        fs::set_permissions(dest, fs::metadata(source)?.permissions())?;
+
+        Ok(copied)
+    }
+
+    fn read_full(reader: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+
+        while read < buffer.len() {
+            match reader.read(&mut buffer[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sparse {
+    use std::fs::{self, File, OpenOptions};
+    use std::io;
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    const BUFFER_SIZE: usize = 1024 * 1024;
+
+    /// Copies the data regions of `source` to `dest`, leaving holes unallocated, and returns the size of the file
+    ///
+    /// Fails with `EINVAL` if the filesystem does not support `SEEK_DATA`
+    pub(super) fn copy(source: &Path, dest: &Path) -> io::Result<u64> {
+        let reader = File::open(source)?;
+        let len = reader.metadata()?.len();
+        let writer = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(dest)?;
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut offset = 0;
+
+        while offset < len {
+            let data = match seek(&reader, offset, libc::SEEK_DATA) {
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => break,
+                result => result?,
+            };
+            let hole = seek(&reader, data, libc::SEEK_HOLE)?;
+
+            let mut position = data;
+            while position < hole {
+                let chunk = BUFFER_SIZE.min((hole - position) as usize);
+                reader.read_exact_at(&mut buffer[..chunk], position)?;
+                writer.write_all_at(&buffer[..chunk], position)?;
+                position += chunk as u64;
+            }
+
+            offset = hole;
+        }
+
+        writer.set_len(len)?;
+        fs::set_permissions(dest, reader.metadata()?.permissions())?;
+
+        Ok(len)
+    }
+
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<u64> {
+        // SAFETY: lseek only repositions the offset of a valid descriptor owned by `file`
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result as u64)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sequential {
+    use std::fs::{self, File, OpenOptions};
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    /// Copies `source` to `dest` with `POSIX_FADV_SEQUENTIAL` set on the source, then drops the source pages from the cache
+    pub(super) fn copy(source: &Path, dest: &Path) -> io::Result<u64> {
+        let mut reader = File::open(source)?;
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(dest)?;
+
+        advise(&reader, libc::POSIX_FADV_SEQUENTIAL);
+        let copied = io::copy(&mut reader, &mut writer);
+        advise(&reader, libc::POSIX_FADV_DONTNEED);
+
+        let copied = copied?;
+        fs::set_permissions(dest, reader.metadata()?.permissions())?;
+
+        Ok(copied)
+    }
+
+    fn advise(file: &File, advice: libc::c_int) {
+        // SAFETY: posix_fadvise only reads the descriptor owned by `file`. Failure leaves the copy unaffected
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice);
+        }
+    }
 }
 
 /// Copies a directory to destination
@@ -41,6 +344,9 @@ pub struct CopyDirectory {
     dest: PathBuf,
     backup_path: PathBuf,
     temp_dir: PathBuf,
+    backup_dir: Option<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
 }
 
 impl CopyDirectory {
@@ -55,19 +361,134 @@ impl CopyDirectory {
             dest: dest.as_ref().into(),
             temp_dir: temp_dir.as_ref().into(),
             backup_path: PathBuf::new(),
+            backup_dir: None,
+            include: vec![],
+            exclude: vec![],
         }
     }
+
+    /// Creates the backup in `dir` instead of the temp dir
+    ///
+    /// Unlike the temp dir, it is kept when the operation is added to a [scoped](struct.Transaction.html#method.scoped) transaction
+    pub fn with_backup_dir<S: AsRef<Path>>(mut self, dir: S) -> Self {
+        self.backup_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Only copies files matching at least one of the [glob patterns](util/fn.glob_matches.html)
+    ///
+    /// Patterns are matched against paths relative to the source dir. Directories are only created in the destination
+    /// if they end up containing a copied file
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Skips files and directories matching any of the [glob patterns](util/fn.glob_matches.html)
+    ///
+    /// Applied after [with_include](#method.with_include), so a file matching both is not copied
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    fn matches_any(patterns: &[String], relative: &str) -> bool {
+        patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern, relative))
+    }
+
+    fn copy_filtered(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.dest)?;
+
+        let mut stack = vec![PathBuf::new()];
+
+        while let Some(relative_dir) = stack.pop() {
+            for entry in fs::read_dir(self.source.join(&relative_dir))? {
+                let entry = entry?;
+                let relative = relative_dir.join(entry.file_name());
+                let relative_str = relative.to_string_lossy().replace('\\', "/");
+
+                if Self::matches_any(&self.exclude, &relative_str) {
+                    continue;
+                }
+
+                if entry.file_type()?.is_dir() {
+                    stack.push(relative);
+                    continue;
+                }
+
+                if !self.include.is_empty() && !Self::matches_any(&self.include, &relative_str) {
+                    continue;
+                }
+
+                let dest = self.dest.join(&relative);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(entry.path(), dest)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl RollbackableOperation for CopyDirectory {
     fn execute(&mut self) -> io::Result<()> {
         self.create_backup_folder()?;
-        copy_dir(&self.source, &self.dest)
+
+        if self.include.is_empty() && self.exclude.is_empty() {
+            copy_dir(&self.source, &self.dest)
+        } else {
+            self.copy_filtered()
+        }
     }
 
     fn rollback(&self) -> io::Result<()> {
         fs::remove_dir_all(&self.dest)
     }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Created, &self.dest)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(vec![UndoStep::RemoveDir(self.dest.clone())])
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.dest, &mut self.temp_dir];
+        if let Some(backup_dir) = &mut self.backup_dir {
+            paths.push(backup_dir);
+        }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("dest", SpecValue::Path(self.dest.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op = CopyDirectory::new(&self.source, &self.dest, &self.temp_dir)
+            .with_include(self.include.clone())
+            .with_exclude(self.exclude.clone());
+        op.backup_dir = self.backup_dir.clone();
+
+        Some(Box::new(op))
+    }
 }
 
 impl DirectoryOperation for CopyDirectory {
@@ -86,13 +507,16 @@ impl DirectoryOperation for CopyDirectory {
     fn get_temp_dir(&self) -> &Path {
         &self.temp_dir
     }
+
+    fn get_backup_dir(&self) -> Option<&Path> {
+        self.backup_dir.as_deref()
+    }
 }
 
 impl Drop for CopyDirectory {
     fn drop(&mut self) {
-        match self.dispose() {
-            Err(e) => eprintln!("{}", e),
-            _ => {}
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
         }
     }
 }
@@ -119,19 +543,124 @@ mod tests {
 
         let mut op = CopyFile::new(FILE_SOURCE, FILE_DEST);
 
-        assert_eq!(false, Path::new(FILE_DEST).exists());
+        assert!(!Path::new(FILE_DEST).exists());
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
-        assert_eq!(true, Path::new(FILE_DEST).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
+        assert!(Path::new(FILE_DEST).exists());
 
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
-        assert_eq!(false, Path::new(FILE_DEST).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
+        assert!(!Path::new(FILE_DEST).exists());
 
         fs::remove_file(FILE_SOURCE);
         fs::remove_dir_all(DEST_DIR);
     }
 
+    const DIRECT_SOURCE: &str = "./copy_file_direct_source.bin";
+    const DIRECT_DEST: &str = "./copy_file_direct_dest.bin";
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[allow(unused_must_use)]
+    fn copy_file_direct_io_works() {
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        fs::write(DIRECT_SOURCE, &data).unwrap();
+
+        let mut op = CopyFile::new(DIRECT_SOURCE, DIRECT_DEST).with_direct_io(true);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(data.len() as u64, op.bytes_written());
+        assert_eq!(data, fs::read(DIRECT_DEST).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(DIRECT_DEST).exists());
+
+        fs::remove_file(DIRECT_SOURCE);
+    }
+
+    const SEQUENTIAL_SOURCE: &str = "./copy_file_sequential_source.bin";
+    const SEQUENTIAL_DEST: &str = "./copy_file_sequential_dest.bin";
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[allow(unused_must_use)]
+    fn copy_file_sequential_hint_works() {
+        let data: Vec<u8> = (0..8 * 1024 * 1024 + 77).map(|i| (i % 241) as u8).collect();
+        fs::write(SEQUENTIAL_SOURCE, &data).unwrap();
+
+        let mut op = CopyFile::new(SEQUENTIAL_SOURCE, SEQUENTIAL_DEST).with_sequential_hint(true);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(data.len() as u64, op.bytes_written());
+        assert_eq!(data, fs::read(SEQUENTIAL_DEST).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(SEQUENTIAL_DEST).exists());
+
+        fs::remove_file(SEQUENTIAL_SOURCE);
+    }
+
+    const SPARSE_SOURCE: &str = "./copy_file_sparse_source.bin";
+    const SPARSE_DEST: &str = "./copy_file_sparse_dest.bin";
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[allow(unused_must_use)]
+    fn copy_file_sparse_works() {
+        use std::os::unix::fs::{FileExt, MetadataExt};
+
+        const SIZE: u64 = 64 * 1024 * 1024;
+
+        let file = File::create(SPARSE_SOURCE).unwrap();
+        file.set_len(SIZE).unwrap();
+        file.write_all_at(b"head", 0).unwrap();
+        file.write_all_at(b"middle", SIZE / 2).unwrap();
+        drop(file);
+
+        let mut op = CopyFile::new(SPARSE_SOURCE, SPARSE_DEST).with_sparse(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(SIZE, op.bytes_written());
+        assert!(files_equal(Path::new(SPARSE_SOURCE), Path::new(SPARSE_DEST)).unwrap());
+
+        // Only meaningful where the filesystem kept the source sparse
+        if fs::metadata(SPARSE_SOURCE).unwrap().blocks() * 512 < SIZE / 8 {
+            assert!(fs::metadata(SPARSE_DEST).unwrap().blocks() * 512 < SIZE / 8);
+        }
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(SPARSE_DEST).exists());
+
+        fs::remove_file(SPARSE_SOURCE);
+    }
+
+    const IDENTICAL_SOURCE: &str = "./copy_file_identical_source.bin";
+    const IDENTICAL_DEST: &str = "./copy_file_identical_dest.bin";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn copy_file_skip_if_identical_works() {
+        fs::write(IDENTICAL_SOURCE, [0x80, 0xff, 0x00]).unwrap();
+        fs::write(IDENTICAL_DEST, [0x80, 0xff, 0x00]).unwrap();
+
+        let mut op = CopyFile::new(IDENTICAL_SOURCE, IDENTICAL_DEST).with_skip_if_identical(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(0, op.bytes_written());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(Path::new(IDENTICAL_DEST).exists());
+
+        fs::write(IDENTICAL_DEST, [0x80, 0xfe, 0x00]).unwrap();
+
+        let mut op = CopyFile::new(IDENTICAL_SOURCE, IDENTICAL_DEST).with_skip_if_identical(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(3, op.bytes_written());
+        assert_eq!(vec![0x80, 0xff, 0x00], fs::read(IDENTICAL_DEST).unwrap());
+
+        fs::remove_file(IDENTICAL_SOURCE);
+        fs::remove_file(IDENTICAL_DEST);
+    }
+
     const DIR_SOURCE: &str = "./copy_dir_source";
     const DIR_DIR: &str = "./copy_dest_dir";
     const DIR_DEST: &str = "./copy_dest_dir/copy_dir_source";
@@ -150,14 +679,45 @@ mod tests {
         let mut op = CopyDirectory::new(DIR_SOURCE, DIR_DEST, DIR_TEMP);
 
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
-        assert_eq!(true, Path::new(DIR_DEST).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
+        assert!(Path::new(DIR_DEST).exists());
 
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
-        assert_eq!(false, Path::new(DIR_DEST).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_DEST).exists());
 
         fs::remove_dir_all(DIR_SOURCE);
         fs::remove_dir(DIR_DIR);
     }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn copy_dir_include_works() {
+        let source = Path::new("./copy_dir_include_source");
+        let dest = Path::new("./copy_dir_include_dest");
+
+        fs::create_dir_all(source.join("docs/drafts")).unwrap();
+        fs::create_dir_all(source.join("images")).unwrap();
+        fs::write(source.join("readme.txt"), "readme").unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(source.join("docs/guide.txt"), "guide").unwrap();
+        fs::write(source.join("docs/drafts/wip.txt"), "wip").unwrap();
+        fs::write(source.join("images/logo.png"), "png").unwrap();
+
+        let mut op = CopyDirectory::new(source, dest, DIR_TEMP)
+            .with_include(vec!["*.txt".into()])
+            .with_exclude(vec!["docs/drafts".into()]);
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(dest.join("readme.txt").exists());
+        assert!(dest.join("docs/guide.txt").exists());
+        assert!(!dest.join("main.rs").exists());
+        assert!(!dest.join("docs/drafts").exists());
+        assert!(!dest.join("images").exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!dest.exists());
+
+        fs::remove_dir_all(source);
+    }
 }
@@ -0,0 +1,100 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+/// Increments an integer stored as UTF-8 text in a file
+///
+/// Surrounding whitespace is ignored when reading the counter. A negative delta decrements it
+pub struct IncrementCounter {
+    file: FileBackup,
+    delta: i64,
+}
+
+impl IncrementCounter {
+    /// Constructs a new IncrementCounter operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, delta: i64) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            delta,
+        }
+    }
+}
+
+impl Rewrite for IncrementCounter {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let contents = String::from_utf8(contents)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Counter is not UTF-8"))?;
+        let value = contents
+            .trim()
+            .parse::<i64>()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let value = value
+            .checked_add(self.delta)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Counter overflow"))?;
+
+        Ok(Some(value.to_string().into_bytes()))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(IncrementCounter::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.delta,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./increment_counter.txt";
+    const INVALID_SOURCE: &str = "./increment_counter_invalid.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn increment_counter_works() {
+        fs::write(SOURCE, "41\n").unwrap();
+
+        let mut op = IncrementCounter::new(SOURCE, TEMP_DIR, 1);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("42", fs::read_to_string(SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("41\n", fs::read_to_string(SOURCE).unwrap());
+
+        let mut op = IncrementCounter::new(SOURCE, TEMP_DIR, -50);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("-9", fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn increment_counter_invalid_data() {
+        fs::write(INVALID_SOURCE, "forty-one").unwrap();
+
+        let mut op = IncrementCounter::new(INVALID_SOURCE, TEMP_DIR, 1);
+
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("forty-one", fs::read_to_string(INVALID_SOURCE).unwrap());
+
+        fs::remove_file(INVALID_SOURCE);
+    }
+}
@@ -1,14 +1,27 @@
 use std::fs::{self, File};
 use std::{
-    io,
+    io::{self, Error, Read},
     path::{Path, PathBuf},
 };
 
-use crate::RollbackableOperation;
+use crate::backup::Backup;
+use crate::{
+    file_size, ignore_not_found, Change, ChangeKind, DeleteDirectory, DeleteFile,
+    RollbackableOperation, SpecValue, UndoStep,
+};
 
 /// Creates a new file
 pub struct CreateFile {
     path: PathBuf,
+    reader: Option<ReaderSource>,
+}
+
+/// State of a [CreateFile](struct.CreateFile.html) constructed with [from_reader](struct.CreateFile.html#method.from_reader)
+struct ReaderSource {
+    reader: Option<Box<dyn Read>>,
+    temp_dir: PathBuf,
+    backup: Option<Backup>,
+    executed: bool,
 }
 
 impl CreateFile {
@@ -16,12 +29,72 @@ impl CreateFile {
     pub fn new<S: AsRef<Path>>(path: S) -> Self {
         Self {
             path: path.as_ref().into(),
+            reader: None,
+        }
+    }
+
+    /// Constructs a new CreateFile operation that streams the reader into the file on execution
+    ///
+    /// An existing file is backed up into [std::env::temp_dir](std::env::temp_dir), or the transaction's temp dir, and restored on rollback.
+    /// The reader is consumed by the first execution, so executing again fails
+    pub fn from_reader<S: AsRef<Path>, R: Read + 'static>(path: S, reader: R) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            reader: Some(ReaderSource {
+                reader: Some(Box::new(reader)),
+                temp_dir: std::env::temp_dir(),
+                backup: None,
+                executed: false,
+            }),
+        }
+    }
+
+    fn backup(&self) -> Option<&Backup> {
+        self.reader
+            .as_ref()
+            .and_then(|source| source.backup.as_ref())
+    }
+}
+
+impl ReaderSource {
+    fn execute(&mut self, path: &Path) -> io::Result<()> {
+        let mut reader = match self.reader.take() {
+            Some(reader) => reader,
+            None => {
+                return Err(Error::other(format!(
+                    "The reader for {} was already consumed by a previous execution",
+                    path.display()
+                )))
+            }
+        };
+
+        if path.exists() {
+            self.backup = Some(Backup::of_file(path, &self.temp_dir)?);
+        }
+
+        self.executed = true;
+
+        let mut file = File::create(path)?;
+        io::copy(&mut reader, &mut file)?;
+
+        Ok(())
+    }
+
+    fn rollback(&self, path: &Path) -> io::Result<()> {
+        match &self.backup {
+            Some(backup) => backup.restore(path),
+            None if self.executed => ignore_not_found(fs::remove_file(path)),
+            None => Ok(()),
         }
     }
 }
 
 impl RollbackableOperation for CreateFile {
     fn execute(&mut self) -> io::Result<()> {
+        if let Some(source) = &mut self.reader {
+            return source.execute(&self.path);
+        }
+
         match File::create(&self.path) {
             Ok(_f) => Ok(()),
             Err(e) => Err(e),
@@ -29,7 +102,86 @@ impl RollbackableOperation for CreateFile {
     }
 
     fn rollback(&self) -> io::Result<()> {
-        fs::remove_file(&self.path)
+        match &self.reader {
+            Some(source) => source.rollback(&self.path),
+            None => fs::remove_file(&self.path),
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        if let Some(source) = &mut self.reader {
+            source.temp_dir = temp_dir.into();
+        }
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backup().is_none_or(Backup::exists)
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        if self.reader.is_none() {
+            return vec![Change::new(ChangeKind::Created, &self.path).with_sizes(None, Some(0))];
+        }
+
+        match self.backup() {
+            Some(backup) => vec![Change::new(ChangeKind::Modified, &self.path)
+                .with_sizes(file_size(backup.path()), file_size(&self.path))],
+            None => vec![Change::new(ChangeKind::Created, &self.path)
+                .with_sizes(None, file_size(&self.path))],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.backup().map(Backup::path)
+    }
+
+    fn bytes_written(&self) -> u64 {
+        match &self.reader {
+            Some(_) => file_size(&self.path).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        match self.backup() {
+            Some(backup) => Some(vec![UndoStep::RestoreFile {
+                backup: backup.path().into(),
+                path: self.path.clone(),
+            }]),
+            None => Some(vec![UndoStep::RemoveFile(self.path.clone())]),
+        }
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![("path", SpecValue::Path(self.path.clone()))]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        match &self.reader {
+            Some(_) => None,
+            None => Some(Box::new(CreateFile::new(&self.path))),
+        }
+    }
+
+    fn inverse(&self, temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        match self.backup() {
+            Some(_) => None,
+            None => Some(Box::new(DeleteFile::new(&self.path, temp_dir))),
+        }
+    }
+}
+
+impl Drop for CreateFile {
+    fn drop(&mut self) {
+        if let Some(backup) = self.backup() {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
+        }
     }
 }
 
@@ -56,6 +208,392 @@ impl RollbackableOperation for CreateDirectory {
         // TODO: So bad
         fs::remove_dir_all(&self.path)
     }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Created, &self.path)]
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(vec![UndoStep::RemoveDir(self.path.clone())])
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![("path", SpecValue::Path(self.path.clone()))]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CreateDirectory::new(&self.path)))
+    }
+
+    fn inverse(&self, temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DeleteDirectory::new(&self.path, temp_dir)))
+    }
+}
+
+/// Creates an empty file with a unique name in a directory
+///
+/// The file is named by the prefix followed by a random UUID and created with `create_new`, retrying with a new name if it already exists.
+/// Its path is available from [path](#method.path) once executed. The file is removed on rollback
+pub struct CreateTempFile {
+    dir: PathBuf,
+    prefix: String,
+    path: Option<PathBuf>,
+}
+
+/// Number of names tried by [CreateTempFile](struct.CreateTempFile.html) before giving up
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+impl CreateTempFile {
+    /// Constructs a new CreateTempFile operation
+    pub fn new<S: AsRef<Path>, T: AsRef<str>>(dir: S, prefix: T) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+            prefix: prefix.as_ref().into(),
+            path: None,
+        }
+    }
+
+    /// Returns the path of the created file
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl RollbackableOperation for CreateTempFile {
+    fn execute(&mut self) -> io::Result<()> {
+        for _ in 0..TEMP_FILE_ATTEMPTS {
+            let path = self.dir.join(format!(
+                "{}{}",
+                self.prefix,
+                uuid::Uuid::new_v4().to_simple()
+            ));
+
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => {
+                    self.path = Some(path);
+                    return Ok(());
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "Could not find a unique name in {} after {} attempts",
+                self.dir.display(),
+                TEMP_FILE_ATTEMPTS
+            ),
+        ))
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => ignore_not_found(fs::remove_file(path)),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.path
+            .iter()
+            .map(|path| Change::new(ChangeKind::Created, path).with_sizes(None, Some(0)))
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.path
+                .iter()
+                .cloned()
+                .map(UndoStep::RemoveFile)
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.dir]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("dir", SpecValue::Path(self.dir.clone())),
+            ("prefix", SpecValue::Text(self.prefix.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CreateTempFile::new(&self.dir, &self.prefix)))
+    }
+}
+
+/// Creates a directory containing an empty sentinel file, like `.keep`
+///
+/// Only the directories and the sentinel created by the operation are removed on rollback. An existing sentinel is left untouched
+pub struct CreateMarkedDir {
+    path: PathBuf,
+    sentinel: PathBuf,
+    created_dirs: Vec<PathBuf>,
+    created_sentinel: bool,
+}
+
+impl CreateMarkedDir {
+    /// Constructs a new CreateMarkedDir operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(path: S, sentinel: T) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            sentinel: sentinel.as_ref().into(),
+            created_dirs: vec![],
+            created_sentinel: false,
+        }
+    }
+
+    fn sentinel_path(&self) -> PathBuf {
+        self.path.join(&self.sentinel)
+    }
+}
+
+impl RollbackableOperation for CreateMarkedDir {
+    fn execute(&mut self) -> io::Result<()> {
+        let missing: Vec<PathBuf> = self
+            .path
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .map(PathBuf::from)
+            .collect();
+
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(&dir)?;
+            self.created_dirs.push(dir);
+        }
+
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.sentinel_path())
+        {
+            Ok(_) => {
+                self.created_sentinel = true;
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.created_sentinel {
+            ignore_not_found(fs::remove_file(self.sentinel_path()))?;
+        }
+
+        for dir in self.created_dirs.iter().rev() {
+            ignore_not_found(fs::remove_dir(dir))?;
+        }
+
+        Ok(())
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let mut changes: Vec<Change> = self
+            .created_dirs
+            .iter()
+            .map(|dir| Change::new(ChangeKind::Created, dir))
+            .collect();
+
+        if self.created_sentinel {
+            changes.push(
+                Change::new(ChangeKind::Created, self.sentinel_path()).with_sizes(None, Some(0)),
+            );
+        }
+
+        changes
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        if self.created_sentinel {
+            steps.push(UndoStep::RemoveFile(self.sentinel_path()));
+        }
+
+        for dir in self.created_dirs.iter().rev() {
+            steps.push(UndoStep::RemoveDir(dir.clone()));
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("path", SpecValue::Path(self.path.clone())),
+            ("sentinel", SpecValue::Path(self.sentinel.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CreateMarkedDir::new(&self.path, &self.sentinel)))
+    }
+}
+
+/// Creates many files with the given contents in a single operation
+///
+/// Files that already exist are backed up into the temp dir and overwritten. On rollback newly created files are removed and overwritten ones restored
+pub struct CreateManyFiles {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    temp_dir: PathBuf,
+    backups: Vec<Option<Backup>>,
+}
+
+impl CreateManyFiles {
+    /// Constructs a new CreateManyFiles operation
+    pub fn new<S: AsRef<Path>>(files: Vec<(PathBuf, Vec<u8>)>, temp_dir: S) -> Self {
+        Self {
+            files,
+            temp_dir: temp_dir.as_ref().into(),
+            backups: vec![],
+        }
+    }
+
+    /// Returns the number of files written so far
+    pub fn written(&self) -> usize {
+        self.backups.len()
+    }
+}
+
+impl RollbackableOperation for CreateManyFiles {
+    fn execute(&mut self) -> io::Result<()> {
+        for i in self.written()..self.files.len() {
+            let (path, data) = &self.files[i];
+
+            let backup = match path.exists() {
+                true => Some(Backup::of_file(path, &self.temp_dir)?),
+                false => None,
+            };
+
+            // Tracked before writing so a partially written file is still undone
+            self.backups.push(backup);
+            fs::write(path, data)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for ((path, _), backup) in self.files.iter().zip(&self.backups).rev() {
+            match backup {
+                Some(backup) => backup.restore(path)?,
+                None => match fs::remove_file(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backups.iter().flatten().all(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backups.iter().flatten().map(Backup::path).collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.files
+            .iter()
+            .zip(&self.backups)
+            .map(|((path, data), backup)| match backup {
+                Some(backup) => Change::new(ChangeKind::Modified, path)
+                    .with_sizes(file_size(backup.path()), Some(data.len() as u64)),
+                None => {
+                    Change::new(ChangeKind::Created, path).with_sizes(None, Some(data.len() as u64))
+                }
+            })
+            .collect()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        let backups: u64 = self
+            .backups
+            .iter()
+            .flatten()
+            .filter_map(|backup| file_size(backup.path()))
+            .sum();
+        let files: u64 = self.files[..self.written()]
+            .iter()
+            .map(|(_, data)| data.len() as u64)
+            .sum();
+
+        backups + files
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.files
+                .iter()
+                .zip(&self.backups)
+                .rev()
+                .map(|((path, _), backup)| match backup {
+                    Some(backup) => UndoStep::RestoreFile {
+                        backup: backup.path().into(),
+                        path: path.clone(),
+                    },
+                    None => UndoStep::RemoveFile(path.clone()),
+                })
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.temp_dir];
+        for (path, _) in self.files.iter_mut() {
+            paths.push(path);
+        }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("files", SpecValue::Number(self.files.len() as u64)),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CreateManyFiles::new(
+            self.files.clone(),
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl Drop for CreateManyFiles {
+    fn drop(&mut self) {
+        for backup in self.backups.iter().flatten() {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -69,11 +607,73 @@ mod tests {
     fn create_file_works() {
         let mut op = CreateFile::new(FILE_SOURCE);
 
-        assert_eq!(false, Path::new(FILE_SOURCE).exists());
+        assert!(!Path::new(FILE_SOURCE).exists());
+        assert_eq!((), op.execute().unwrap());
+        assert!(Path::new(FILE_SOURCE).exists());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(FILE_SOURCE).exists());
+    }
+
+    const READER_SOURCE: &str = "./create_file_from_reader.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn create_file_from_reader_works() {
+        let mut op =
+            CreateFile::from_reader(READER_SOURCE, io::Cursor::new(b"Hello World".to_vec()));
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(READER_SOURCE).unwrap());
+
+        // The reader was consumed by the first execution
+        assert!(op.execute().is_err());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(READER_SOURCE).exists());
+
+        fs::write(READER_SOURCE, "original").unwrap();
+
+        let mut op = CreateFile::from_reader(READER_SOURCE, io::Cursor::new(b"new".to_vec()));
+        op.set_temp_dir(Path::new("./tmp/"));
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("new", fs::read_to_string(READER_SOURCE).unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("original", fs::read_to_string(READER_SOURCE).unwrap());
+
+        fs::remove_file(READER_SOURCE);
+    }
+
+    const MANY_DIR: &str = "./create_many_files";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn create_many_files_works() {
+        fs::create_dir_all(MANY_DIR).unwrap();
+        let existing = Path::new(MANY_DIR).join("file_0.txt");
+        fs::write(&existing, "original").unwrap();
+
+        let files = (0..100)
+            .map(|i| {
+                let path = Path::new(MANY_DIR).join(format!("file_{}.txt", i));
+                (path, format!("content {}", i).into_bytes())
+            })
+            .collect();
+
+        let mut op = CreateManyFiles::new(files, "./tmp/");
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
+        assert_eq!(100, op.written());
+        assert_eq!(100, fs::read_dir(MANY_DIR).unwrap().count());
+        assert_eq!(
+            "content 42",
+            fs::read_to_string(Path::new(MANY_DIR).join("file_42.txt")).unwrap()
+        );
+
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(false, Path::new(FILE_SOURCE).exists());
+        assert_eq!(1, fs::read_dir(MANY_DIR).unwrap().count());
+        assert_eq!("original", fs::read_to_string(&existing).unwrap());
+
+        fs::remove_dir_all(MANY_DIR);
     }
 
     const DIR_SOURCE: &str = "./create_dir";
@@ -82,10 +682,65 @@ mod tests {
     fn create_dir_works() {
         let mut op = CreateDirectory::new(DIR_SOURCE);
 
-        assert_eq!(false, Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_SOURCE).exists());
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(false, Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_SOURCE).exists());
+    }
+
+    const TEMP_FILE_DIR: &str = "./create_temp_file";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn create_temp_file_works() {
+        fs::create_dir_all(TEMP_FILE_DIR).unwrap();
+
+        let mut first = CreateTempFile::new(TEMP_FILE_DIR, "stage-");
+        let mut second = CreateTempFile::new(TEMP_FILE_DIR, "stage-");
+        assert_eq!(None, first.path());
+
+        assert_eq!((), first.execute().unwrap());
+        assert_eq!((), second.execute().unwrap());
+
+        let path = first.path().unwrap().to_path_buf();
+        assert!(path.is_file());
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("stage-"));
+        assert_ne!(first.path(), second.path());
+
+        assert_eq!((), first.rollback().unwrap());
+        assert!(!path.exists());
+        assert!(second.path().unwrap().exists());
+
+        fs::remove_dir_all(TEMP_FILE_DIR);
+    }
+
+    const MARKED_PARENT: &str = "./create_marked_dir";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn create_marked_dir_works() {
+        let parent = Path::new(MARKED_PARENT);
+        let sibling = parent.join("sibling.txt");
+        let dir = parent.join("data/cache");
+
+        fs::create_dir_all(parent).unwrap();
+        fs::write(&sibling, "Hello").unwrap();
+
+        let mut op = CreateMarkedDir::new(&dir, ".keep");
+        assert_eq!((), op.execute().unwrap());
+        assert!(dir.is_dir());
+        assert!(dir.join(".keep").is_file());
+        assert_eq!(3, op.changes().len());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!parent.join("data").exists());
+        assert_eq!("Hello", fs::read_to_string(&sibling).unwrap());
+
+        fs::remove_dir_all(parent);
     }
 }
@@ -0,0 +1,478 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::backup::Backup;
+use crate::{
+    file_size, hash_file, non_empty_path, unique_path, Change, ChangeKind, DirectoryOperation,
+    RollbackableOperation, SingleFileOperation, UndoStep, DEFAULT_BACKUP_BUFFER_SIZE,
+};
+
+/// Deduplicates a file against a content-addressable store
+///
+/// Files in the store are named after the SHA-256 digest of their content. If the store already holds the content of the source file, the source is replaced with a hardlink to it. Otherwise a copy of the source is added to the store
+pub struct DeduplicateFile {
+    source: PathBuf,
+    store_dir: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
+    linked: bool,
+    stored_path: Option<PathBuf>,
+}
+
+impl DeduplicateFile {
+    /// Constructs a new DeduplicateFile operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        source: S,
+        store_dir: T,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            store_dir: store_dir.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            linked: false,
+            stored_path: None,
+        }
+    }
+}
+
+impl RollbackableOperation for DeduplicateFile {
+    fn execute(&mut self) -> io::Result<()> {
+        let store_path = self.store_dir.join(hash_file(&self.source)?);
+
+        if store_path.exists() {
+            self.create_backup_file()?;
+
+            // Link next to the source first so it is never missing if linking fails
+            let staging = unique_path(self.source.parent().unwrap_or(Path::new(".")));
+            fs::hard_link(&store_path, &staging)?;
+
+            if let Err(e) = fs::rename(&staging, &self.source) {
+                let _ = fs::remove_file(&staging);
+                return Err(e);
+            }
+
+            self.linked = true;
+        } else {
+            fs::create_dir_all(&self.store_dir)?;
+            fs::copy(&self.source, &store_path)?;
+            self.stored_path = Some(store_path);
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.linked {
+            fs::remove_file(&self.source)?;
+            fs::copy(self.get_backup_path(), &self.source)?;
+        }
+
+        if let Some(stored_path) = &self.stored_path {
+            fs::remove_file(stored_path)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        !self.linked || self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let mut changes = vec![];
+
+        if self.linked {
+            let size = file_size(self.get_backup_path());
+            changes.push(Change::new(ChangeKind::Modified, &self.source).with_sizes(size, size));
+        }
+
+        if let Some(stored_path) = &self.stored_path {
+            changes.push(
+                Change::new(ChangeKind::Created, stored_path)
+                    .with_sizes(None, file_size(stored_path)),
+            );
+        }
+
+        changes
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        if self.linked {
+            // The hardlink must be removed first so the store entry is left untouched
+            steps.push(UndoStep::RemoveFile(self.source.clone()));
+            steps.push(UndoStep::RestoreFile {
+                backup: self.backup_path.clone(),
+                path: self.source.clone(),
+            });
+        }
+
+        if let Some(stored_path) = &self.stored_path {
+            steps.push(UndoStep::RemoveFile(stored_path.clone()));
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.store_dir, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DeduplicateFile::new(
+            &self.source,
+            &self.store_dir,
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl SingleFileOperation for DeduplicateFile {
+    fn get_path(&self) -> &Path {
+        &self.source
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
+}
+
+impl Drop for DeduplicateFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Replaces duplicate files inside a directory tree with hardlinks
+///
+/// Files are grouped by the SHA-256 digest of their content. Within each group the first file in path order is kept and every other file is replaced with a hardlink to it. The whole tree is backed up beforehand and restored on rollback
+pub struct DeduplicateDirectory {
+    root: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    linked: Vec<PathBuf>,
+}
+
+impl DeduplicateDirectory {
+    /// Constructs a new DeduplicateDirectory operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(root: S, temp_dir: T) -> Self {
+        Self {
+            root: root.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+            linked: vec![],
+        }
+    }
+
+    /// Files that were replaced with a hardlink during execution
+    pub fn linked(&self) -> &[PathBuf] {
+        &self.linked
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        let mut stack = vec![self.root.clone()];
+
+        while let Some(dir) = stack.pop() {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+
+                if file_type.is_dir() {
+                    stack.push(entry.path());
+                } else if file_type.is_file() {
+                    files.push(entry.path());
+                }
+            }
+        }
+
+        files.sort();
+
+        Ok(files)
+    }
+}
+
+impl RollbackableOperation for DeduplicateDirectory {
+    fn execute(&mut self) -> io::Result<()> {
+        let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+        for file in self.files()? {
+            groups.entry(hash_file(&file)?).or_default().push(file);
+        }
+
+        self.create_backup_folder()?;
+
+        for (kept, duplicates) in groups.values().filter_map(|group| group.split_first()) {
+            for duplicate in duplicates {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+
+                    let (a, b) = (fs::metadata(kept)?, fs::metadata(duplicate)?);
+                    if a.dev() == b.dev() && a.ino() == b.ino() {
+                        continue;
+                    }
+                }
+
+                // Link next to the duplicate first so it is never missing from the tree
+                let staging = unique_path(duplicate.parent().unwrap_or(&self.root));
+                fs::hard_link(kept, &staging)?;
+
+                if let Err(e) = fs::rename(&staging, duplicate) {
+                    let _ = fs::remove_file(&staging);
+                    return Err(e);
+                }
+
+                self.linked.push(duplicate.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.linked.is_empty() {
+            return Ok(());
+        }
+
+        Backup::existing_dir(self.get_backup_path()).restore(&self.root)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.linked.is_empty() || self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.linked
+            .iter()
+            .map(|path| {
+                let size = file_size(path);
+                Change::new(ChangeKind::Modified, path).with_sizes(size, size)
+            })
+            .collect()
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if self.linked.is_empty() {
+            return Some(vec![]);
+        }
+
+        Some(vec![UndoStep::RestoreDir {
+            backup: self.backup_path.clone(),
+            path: self.root.clone(),
+        }])
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.root, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DeduplicateDirectory::new(
+            &self.root,
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl DirectoryOperation for DeduplicateDirectory {
+    fn get_path(&self) -> &Path {
+        &self.root
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+}
+
+impl Drop for DeduplicateDirectory {
+    fn drop(&mut self) {
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    const FILE_SOURCE: &str = "./deduplicate_file_source.txt";
+    const FILE_STORED: &str = "./deduplicate_file_stored.txt";
+    const STORE_DIR: &str = "./deduplicate_store";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &[u8] = "Hello World".as_bytes();
+
+    fn setup() -> std::io::Result<()> {
+        fs::write(FILE_SOURCE, DATA)?;
+        fs::write(FILE_STORED, DATA)
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn deduplicate_file_works() {
+        assert_eq!((), setup().unwrap());
+
+        let mut store_op = DeduplicateFile::new(FILE_STORED, STORE_DIR, TEMP_DIR);
+        assert_eq!((), store_op.execute().unwrap());
+        assert_eq!(1, fs::read_dir(STORE_DIR).unwrap().count());
+
+        let mut op = DeduplicateFile::new(FILE_SOURCE, STORE_DIR, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(1, fs::read_dir(STORE_DIR).unwrap().count());
+        assert_eq!(DATA, fs::read(FILE_SOURCE).unwrap().as_slice());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let entry = fs::read_dir(STORE_DIR).unwrap().next().unwrap().unwrap();
+            assert_eq!(
+                entry.metadata().unwrap().ino(),
+                fs::metadata(FILE_SOURCE).unwrap().ino()
+            );
+        }
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(FILE_SOURCE).unwrap().as_slice());
+        assert_eq!(1, fs::read_dir(STORE_DIR).unwrap().count());
+
+        assert_eq!((), store_op.rollback().unwrap());
+        assert!(Path::new(STORE_DIR).exists());
+        assert_eq!(0, fs::read_dir(STORE_DIR).unwrap().count());
+
+        fs::remove_file(FILE_SOURCE);
+        fs::remove_file(FILE_STORED);
+        fs::remove_dir_all(STORE_DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn deduplicate_file_link_failure_keeps_source() {
+        const SOURCE: &str = "./deduplicate_file_link_failure.txt";
+        const STORE: &str = "./deduplicate_link_failure_store";
+
+        fs::write(SOURCE, DATA).unwrap();
+
+        // A directory cannot be hardlinked, so the store entry exists but linking it fails
+        fs::create_dir_all(Path::new(STORE).join(hash_file(SOURCE).unwrap())).unwrap();
+
+        let mut op = DeduplicateFile::new(SOURCE, STORE, TEMP_DIR);
+        assert!(op.execute().is_err());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SOURCE);
+        fs::remove_dir_all(STORE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn deduplicate_dir_works() {
+        const ROOT: &str = "./deduplicate_dir_root";
+
+        fs::create_dir_all(Path::new(ROOT).join("nested")).unwrap();
+        fs::write(Path::new(ROOT).join("a.txt"), DATA).unwrap();
+        fs::write(Path::new(ROOT).join("b.txt"), DATA).unwrap();
+        fs::write(Path::new(ROOT).join("nested/c.txt"), DATA).unwrap();
+        fs::write(Path::new(ROOT).join("unique.txt"), "unique").unwrap();
+
+        let mut op = DeduplicateDirectory::new(ROOT, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(2, op.linked().len());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let ino = |name: &str| fs::metadata(Path::new(ROOT).join(name)).unwrap().ino();
+            assert_eq!(ino("a.txt"), ino("b.txt"));
+            assert_eq!(ino("a.txt"), ino("nested/c.txt"));
+            assert_ne!(ino("a.txt"), ino("unique.txt"));
+        }
+
+        assert_eq!((), op.rollback().unwrap());
+
+        for name in &["a.txt", "b.txt", "nested/c.txt"] {
+            let path = Path::new(ROOT).join(name);
+            assert_eq!(DATA, fs::read(&path).unwrap().as_slice());
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                assert_eq!(1, fs::metadata(&path).unwrap().nlink());
+            }
+        }
+        assert_eq!(
+            "unique",
+            fs::read_to_string(Path::new(ROOT).join("unique.txt")).unwrap()
+        );
+
+        fs::remove_dir_all(ROOT);
+    }
+}
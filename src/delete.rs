@@ -4,13 +4,21 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{DirectoryOperation, RollbackableOperation, SingleFileOperation};
+use crate::backup::Backup;
+use crate::{
+    file_size, non_empty_path, Change, ChangeKind, DirectoryOperation, DropPolicy, DropState,
+    RollbackableOperation, SingleFileOperation, SpecValue, UndoStep, DEFAULT_BACKUP_BUFFER_SIZE,
+};
 
 /// Deletes a file
 pub struct DeleteFile {
     source: PathBuf,
     temp_dir: PathBuf,
+    backup_dir: Option<PathBuf>,
     backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
+    drop_state: DropState,
 }
 
 impl DeleteFile {
@@ -19,23 +27,110 @@ impl DeleteFile {
         Self {
             source: source.as_ref().into(),
             temp_dir: temp_dir.as_ref().into(),
+            backup_dir: None,
             backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            drop_state: DropState::default(),
         }
     }
+
+    /// Creates the backup in `dir` instead of the temp dir
+    ///
+    /// Unlike the temp dir, it is kept when the operation is added to a [scoped](struct.Transaction.html#method.scoped) transaction
+    pub fn with_backup_dir<S: AsRef<Path>>(mut self, dir: S) -> Self {
+        self.backup_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Sets the policy applied when the operation is dropped
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_state.set_policy(policy);
+    }
 }
 
 impl RollbackableOperation for DeleteFile {
     fn execute(&mut self) -> io::Result<()> {
         self.create_backup_file()?;
+        self.drop_state.executed();
 
         fs::remove_file(self.get_path())
     }
 
     fn rollback(&self) -> io::Result<()> {
-        match fs::copy(self.get_backup_path(), self.get_path()) {
-            Ok(_v) => Ok(()),
-            Err(e) => Err(e),
+        self.drop_state.rolled_back();
+
+        Backup::existing_file(self.get_backup_path()).restore(self.get_path())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Deleted, &self.source)
+            .with_sizes(file_size(self.get_backup_path()), None)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn mark_committed(&mut self) {
+        self.drop_state.committed();
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.backup_path()
+                .map(|backup| UndoStep::RestoreFile {
+                    backup: backup.into(),
+                    path: self.source.clone(),
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        let backup_dir = self.get_backup_dir().unwrap_or(&self.temp_dir);
+
+        vec![(backup_dir.into(), file_size(&self.source).unwrap_or(0))]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.temp_dir];
+        if let Some(backup_dir) = &mut self.backup_dir {
+            paths.push(backup_dir);
         }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op = DeleteFile::new(&self.source, &self.temp_dir);
+        op.backup_dir = self.backup_dir.clone();
+        op.drop_state = self.drop_state.reset();
+
+        Some(Box::new(op))
     }
 }
 
@@ -55,13 +150,30 @@ impl SingleFileOperation for DeleteFile {
     fn get_temp_dir(&self) -> &Path {
         &self.temp_dir
     }
+
+    fn get_backup_dir(&self) -> Option<&Path> {
+        self.backup_dir.as_deref()
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
 }
 
 impl Drop for DeleteFile {
     fn drop(&mut self) {
-        match self.dispose() {
-            Err(e) => eprintln!("{}", e),
-            _ => {}
+        if self.drop_state.should_rollback() {
+            if let Err(e) = self.rollback() {
+                eprintln!("{}", e)
+            }
+        }
+
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
         }
     }
 }
@@ -71,6 +183,8 @@ pub struct DeleteDirectory {
     source: PathBuf,
     backup_path: PathBuf,
     temp_dir: PathBuf,
+    backup_dir: Option<PathBuf>,
+    drop_state: DropState,
 }
 
 impl DeleteDirectory {
@@ -79,19 +193,99 @@ impl DeleteDirectory {
         Self {
             source: source.as_ref().into(),
             temp_dir: temp_dir.as_ref().into(),
+            backup_dir: None,
             backup_path: PathBuf::new(),
+            drop_state: DropState::default(),
         }
     }
+
+    /// Creates the backup in `dir` instead of the temp dir
+    ///
+    /// Unlike the temp dir, it is kept when the operation is added to a [scoped](struct.Transaction.html#method.scoped) transaction
+    pub fn with_backup_dir<S: AsRef<Path>>(mut self, dir: S) -> Self {
+        self.backup_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Sets the policy applied when the operation is dropped
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_state.set_policy(policy);
+    }
 }
 
 impl RollbackableOperation for DeleteDirectory {
     fn execute(&mut self) -> io::Result<()> {
         self.create_backup_folder()?;
+        self.drop_state.executed();
         fs::remove_dir_all(&self.source)
     }
 
     fn rollback(&self) -> io::Result<()> {
-        fs::rename(self.get_backup_path(), &self.source)
+        self.drop_state.rolled_back();
+
+        // The backup cannot be renamed back if the temp dir is on another filesystem, so copy it
+        // instead and leave it for dispose
+        match fs::rename(self.get_backup_path(), &self.source) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                Backup::existing_dir(self.get_backup_path()).restore(&self.source)
+            }
+            result => result,
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Deleted, &self.source)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn mark_committed(&mut self) {
+        self.drop_state.committed();
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.backup_path()
+                .map(|backup| UndoStep::RestoreDir {
+                    backup: backup.into(),
+                    path: self.source.clone(),
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.temp_dir];
+        if let Some(backup_dir) = &mut self.backup_dir {
+            paths.push(backup_dir);
+        }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op = DeleteDirectory::new(&self.source, &self.temp_dir);
+        op.backup_dir = self.backup_dir.clone();
+        op.drop_state = self.drop_state.reset();
+
+        Some(Box::new(op))
     }
 }
 
@@ -111,13 +305,165 @@ impl DirectoryOperation for DeleteDirectory {
     fn get_temp_dir(&self) -> &Path {
         &self.temp_dir
     }
+
+    fn get_backup_dir(&self) -> Option<&Path> {
+        self.backup_dir.as_deref()
+    }
 }
 
 impl Drop for DeleteDirectory {
     fn drop(&mut self) {
-        match self.dispose() {
-            Err(e) => eprintln!("{}", e),
-            _ => {}
+        if self.drop_state.should_rollback() {
+            if let Err(e) = self.rollback() {
+                eprintln!("{}", e)
+            }
+        }
+
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Deletes many files in a single operation
+///
+/// Every file is backed up into the temp dir before it is deleted. If a deletion fails, rollback only restores the files deleted so far
+pub struct DeleteMany {
+    paths: Vec<PathBuf>,
+    temp_dir: PathBuf,
+    backups: Vec<Backup>,
+}
+
+impl DeleteMany {
+    /// Constructs a new DeleteMany operation
+    pub fn new<S: AsRef<Path>>(paths: Vec<PathBuf>, temp_dir: S) -> Self {
+        Self {
+            paths,
+            temp_dir: temp_dir.as_ref().into(),
+            backups: vec![],
+        }
+    }
+
+    /// Returns the number of files deleted so far
+    pub fn deleted(&self) -> usize {
+        self.backups.len()
+    }
+
+    /// Returns the backup of every deleted file along with its original path
+    pub fn backups(&self) -> Vec<(&Path, &Path)> {
+        self.backups
+            .iter()
+            .zip(&self.paths)
+            .map(|(backup, path)| (backup.path(), path.as_path()))
+            .collect()
+    }
+
+    fn delete_one(&self, path: &Path) -> io::Result<Backup> {
+        let backup = Backup::of_file(path, &self.temp_dir)?;
+
+        if let Err(e) = fs::remove_file(path) {
+            backup.dispose()?;
+            return Err(e);
+        }
+
+        Ok(backup)
+    }
+}
+
+impl RollbackableOperation for DeleteMany {
+    fn execute(&mut self) -> io::Result<()> {
+        for i in self.deleted()..self.paths.len() {
+            let backup = self.delete_one(&self.paths[i])?;
+            self.backups.push(backup);
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (backup, path) in self.backups.iter().zip(&self.paths).rev() {
+            backup.restore(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backups.iter().all(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backups.iter().map(Backup::path).collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.backups
+            .iter()
+            .zip(&self.paths)
+            .map(|(backup, path)| {
+                Change::new(ChangeKind::Deleted, path).with_sizes(file_size(backup.path()), None)
+            })
+            .collect()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.backups
+            .iter()
+            .filter_map(|backup| file_size(backup.path()))
+            .sum()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.backups
+                .iter()
+                .zip(&self.paths)
+                .rev()
+                .map(|(backup, path)| UndoStep::RestoreFile {
+                    backup: backup.path().into(),
+                    path: path.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        let total = self.paths.iter().filter_map(file_size).sum();
+
+        vec![(self.temp_dir.clone(), total)]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.temp_dir];
+        paths.extend(self.paths.iter_mut());
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("files", SpecValue::Number(self.paths.len() as u64)),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DeleteMany::new(
+            self.paths.clone(),
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl Drop for DeleteMany {
+    fn drop(&mut self) {
+        for backup in &self.backups {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
         }
     }
 }
@@ -145,15 +491,80 @@ mod tests {
 
         let mut op = DeleteFile::new(FILE_SOURCE, TEMP_DIR);
 
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(false, Path::new(FILE_SOURCE).exists());
+        assert!(!Path::new(FILE_SOURCE).exists());
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
 
         fs::remove_file(FILE_SOURCE);
     }
 
+    const MANY_DIR: &str = "./delete_many";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn delete_many_works() {
+        fs::create_dir_all(MANY_DIR).unwrap();
+
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|i| Path::new(MANY_DIR).join(format!("file_{}.txt", i)))
+            .collect();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, format!("content {}", i)).unwrap();
+        }
+
+        let mut op = DeleteMany::new(paths.clone(), TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(5, op.deleted());
+        assert_eq!(0, fs::read_dir(MANY_DIR).unwrap().count());
+
+        assert_eq!((), op.rollback().unwrap());
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(format!("content {}", i), fs::read_to_string(path).unwrap());
+        }
+
+        // A missing file stops the deletion partway, rollback only restores the files before it
+        let mut partial = paths.clone();
+        partial.insert(2, Path::new(MANY_DIR).join("missing.txt"));
+
+        let mut op = DeleteMany::new(partial, TEMP_DIR);
+        assert!(op.execute().is_err());
+        assert_eq!(2, op.deleted());
+        assert_eq!(3, fs::read_dir(MANY_DIR).unwrap().count());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(5, fs::read_dir(MANY_DIR).unwrap().count());
+
+        fs::remove_dir_all(MANY_DIR);
+    }
+
+    const POLICY_SOURCE: &str = "./delete_file_drop_policy";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn delete_file_drop_policy_works() {
+        fs::write(POLICY_SOURCE, b"Hello World").unwrap();
+
+        let mut op = DeleteFile::new(POLICY_SOURCE, TEMP_DIR);
+        op.set_drop_policy(DropPolicy::RollbackIfNotCommitted);
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!Path::new(POLICY_SOURCE).exists());
+
+        drop(op);
+        assert_eq!(b"Hello World".to_vec(), fs::read(POLICY_SOURCE).unwrap());
+
+        let mut op = DeleteFile::new(POLICY_SOURCE, TEMP_DIR);
+        op.set_drop_policy(DropPolicy::RollbackIfNotCommitted);
+
+        assert_eq!((), op.execute().unwrap());
+        op.mark_committed();
+
+        drop(op);
+        assert!(!Path::new(POLICY_SOURCE).exists());
+    }
+
     const DIR_SOURCE: &str = "./delete_dir_source";
 
     fn dir_setup() -> std::io::Result<()> {
@@ -167,12 +578,45 @@ mod tests {
 
         let mut op = DeleteDirectory::new(DIR_SOURCE, TEMP_DIR);
 
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(false, Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_SOURCE).exists());
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
 
         fs::remove_dir_all(DIR_SOURCE);
     }
+
+    /// Runs against a temp dir on another filesystem than the crate, e.g. `TFIO_CROSS_DEVICE_TEMP_DIR=/dev/shm/tfio`
+    #[test]
+    #[allow(unused_must_use)]
+    fn delete_dir_cross_device_works() {
+        const CROSS_SOURCE: &str = "./delete_dir_cross_device";
+
+        let temp_dir = match std::env::var("TFIO_CROSS_DEVICE_TEMP_DIR") {
+            Ok(temp_dir) => temp_dir,
+            Err(_) => return,
+        };
+
+        fs::create_dir_all(Path::new(CROSS_SOURCE).join("inner")).unwrap();
+        fs::write(Path::new(CROSS_SOURCE).join("inner/file.txt"), "Hello").unwrap();
+
+        let mut op = DeleteDirectory::new(CROSS_SOURCE, &temp_dir);
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!Path::new(CROSS_SOURCE).exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(
+            "Hello",
+            fs::read_to_string(Path::new(CROSS_SOURCE).join("inner/file.txt")).unwrap()
+        );
+        assert!(op.get_backup_path().exists());
+
+        let backup_path = op.get_backup_path().to_path_buf();
+        drop(op);
+        assert!(!backup_path.exists());
+
+        fs::remove_dir_all(CROSS_SOURCE);
+    }
 }
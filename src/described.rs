@@ -0,0 +1,166 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Wraps an operation to give it a custom [description](trait.RollbackableOperation.html#method.description)
+///
+/// Everything else is delegated to the wrapped operation
+pub struct Described {
+    op: Box<dyn RollbackableOperation>,
+    description: String,
+}
+
+impl Described {
+    /// Constructs a new Described operation
+    pub fn new<S: Into<String>>(description: S, op: Box<dyn RollbackableOperation>) -> Self {
+        Self {
+            op,
+            description: description.into(),
+        }
+    }
+}
+
+impl RollbackableOperation for Described {
+    fn execute(&mut self) -> io::Result<()> {
+        self.op.execute()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.op.rollback()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.op.set_temp_dir(temp_dir)
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.op.set_backup_store(store)
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.op.set_backup_buffer_size(size)
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.op.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.op.changes()
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.op.backup_path()
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.op.owned_backups()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.op.bytes_written()
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        self.op.space_required()
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.op.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let op = self.op.clone_box()?;
+        Some(Box::new(Described::new(self.description.clone(), op)))
+    }
+
+    fn mark_committed(&mut self) {
+        self.op.mark_committed()
+    }
+
+    fn is_reversible(&self) -> bool {
+        self.op.is_reversible()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        self.op.undo_steps()
+    }
+
+    fn inverse(&self, temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        self.op.inverse(temp_dir)
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        self.op.spec()
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+}
+
+/// Stands in for an operation of a group committed by [Transaction::execute_groups](struct.Transaction.html#method.execute_groups)
+///
+/// Keeps what the operation reported so the transaction can still describe it, while its backups are gone
+pub(crate) struct Committed {
+    description: String,
+    changes: Vec<Change>,
+    bytes_written: u64,
+}
+
+impl Committed {
+    pub(crate) fn of(op: &dyn RollbackableOperation) -> Self {
+        Self {
+            description: op.description(),
+            changes: op.changes(),
+            bytes_written: op.bytes_written(),
+        }
+    }
+}
+
+impl RollbackableOperation for Committed {
+    fn execute(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.changes.clone()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(vec![])
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CreateFile;
+
+    const SOURCE: &str = "./described_source.txt";
+
+    #[test]
+    fn described_works() {
+        let mut op = Described::new("create the config", Box::new(CreateFile::new(SOURCE)));
+        assert_eq!("create the config", op.description());
+        assert_eq!("CreateFile", CreateFile::new(SOURCE).description());
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(Path::new(SOURCE).exists());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(SOURCE).exists());
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{Change, RollbackableOperation, UndoStep};
+
+/// Downloads a URL into a file
+///
+/// A pre-existing destination is backed up and restored on rollback, otherwise the downloaded file is removed
+pub struct DownloadFile {
+    url: String,
+    file: FileBackup,
+    written: bool,
+    downloaded: u64,
+}
+
+impl DownloadFile {
+    /// Constructs a new DownloadFile operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(url: &str, dest: S, temp_dir: T) -> Self {
+        Self {
+            url: url.into(),
+            file: FileBackup::new(dest, temp_dir),
+            written: false,
+            downloaded: 0,
+        }
+    }
+}
+
+impl RollbackableOperation for DownloadFile {
+    fn execute(&mut self) -> io::Result<()> {
+        let response = match ureq::get(&self.url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) => {
+                return Err(Error::other(format!(
+                    "{} returned status {}",
+                    self.url, status
+                )))
+            }
+            Err(e) => return Err(Error::other(e)),
+        };
+
+        if !(200..300).contains(&response.status()) {
+            return Err(Error::other(format!(
+                "{} returned status {}",
+                self.url,
+                response.status()
+            )));
+        }
+
+        if self.file.path().exists() {
+            self.file.create()?;
+        }
+
+        let mut file = File::create(self.file.path())?;
+        self.written = true;
+        self.downloaded = io::copy(&mut response.into_reader(), &mut file)?;
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.revert(self.written)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(Some(self.downloaded))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.downloaded
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.revert_steps(self.written))
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DownloadFile::new(
+            &self.url,
+            self.file.path(),
+            self.file.temp_dir(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    const DEST: &str = "./download_file.txt";
+    const MISSING_DEST: &str = "./download_file_missing.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const BODY: &str = "Downloaded World";
+
+    fn serve_once(status: &'static str, body: &'static str) -> (String, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn download_file_works() {
+        fs::write(DEST, "Old World").unwrap();
+
+        let (url, server) = serve_once("200 OK", BODY);
+        let mut op = DownloadFile::new(&url, DEST, TEMP_DIR);
+
+        assert_eq!((), op.execute().unwrap());
+        server.join().unwrap();
+        assert_eq!(BODY, fs::read_to_string(DEST).unwrap());
+        assert_eq!(BODY.len() as u64, op.bytes_written());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Old World", fs::read_to_string(DEST).unwrap());
+
+        fs::remove_file(DEST);
+    }
+
+    #[test]
+    fn download_file_error_status() {
+        let (url, server) = serve_once("404 Not Found", "");
+        let mut op = DownloadFile::new(&url, MISSING_DEST, TEMP_DIR);
+
+        assert_eq!(ErrorKind::Other, op.execute().unwrap_err().kind());
+        server.join().unwrap();
+        assert!(!Path::new(MISSING_DEST).exists());
+
+        assert_eq!((), op.rollback().unwrap());
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{file_size, preserve_metadata, Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Writes data to a file so that the new content survives a crash once execution returns
+///
+/// The data is written to a sibling, which is fsynced and renamed over the file. The parent dir is then fsynced so the rename
+/// itself is durable. An existing file is backed up and restored on rollback, and its permissions and owner carry over to
+/// the new file. Syncing the parent dir is only supported on Unix
+pub struct DurableWrite {
+    file: FileBackup,
+    data: Vec<u8>,
+    written: bool,
+}
+
+impl DurableWrite {
+    /// Constructs a new DurableWrite operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, data: Vec<u8>) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            data,
+            written: false,
+        }
+    }
+
+    fn write_staging(&self, staging: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(staging)?;
+
+        file.write_all(&self.data)?;
+        file.sync_all()
+    }
+
+    fn sync_parent(&self) -> io::Result<()> {
+        match self.file.path().parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => sync_dir(parent),
+            _ => sync_dir(Path::new(".")),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl RollbackableOperation for DurableWrite {
+    fn execute(&mut self) -> io::Result<()> {
+        let mut staging = self.file.path().as_os_str().to_owned();
+        staging.push(format!(".{}.tmp", uuid::Uuid::new_v4().to_simple()));
+        let staging = PathBuf::from(staging);
+
+        let original = fs::metadata(self.file.path()).ok();
+        let staged = self.write_staging(&staging).and_then(|_| match &original {
+            Some(original) => preserve_metadata(original, &staging),
+            None => Ok(()),
+        });
+
+        if let Err(e) = staged {
+            let _ = fs::remove_file(&staging);
+            return Err(e);
+        }
+
+        let result = match self.file.path().exists() {
+            true => self.file.create(),
+            false => Ok(()),
+        }
+        .and_then(|_| fs::rename(&staging, self.file.path()));
+
+        if let Err(e) = result {
+            let _ = fs::remove_file(&staging);
+            return Err(e);
+        }
+
+        self.written = true;
+        self.sync_parent()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if !self.written {
+            return Ok(());
+        }
+
+        self.file.revert(true)?;
+        self.sync_parent()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(Some(self.data.len() as u64))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.revert_steps(self.written))
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        vec![
+            (
+                self.file.temp_dir().into(),
+                file_size(self.file.path()).unwrap_or(0),
+            ),
+            (self.file.path().into(), self.data.len() as u64),
+        ]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.file.path().into())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+            ("data", SpecValue::Bytes(self.data.len())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(DurableWrite::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.data.clone(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "./durable_write.txt";
+    const NEW_SOURCE: &str = "./durable_write_new.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn durable_write_works() {
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        let mut op = DurableWrite::new(SOURCE, TEMP_DIR, b"Goodbye World".to_vec());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("Goodbye World", fs::read_to_string(SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(SOURCE).unwrap());
+
+        let mut op = DurableWrite::new(NEW_SOURCE, TEMP_DIR, b"Hello".to_vec());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("Hello", fs::read_to_string(NEW_SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(NEW_SOURCE).exists());
+
+        fs::remove_file(SOURCE);
+    }
+
+    const MODE_SOURCE: &str = "./durable_write_mode.txt";
+
+    #[test]
+    #[cfg(unix)]
+    #[allow(unused_must_use)]
+    fn durable_write_preserves_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::write(MODE_SOURCE, "Hello World").unwrap();
+        fs::set_permissions(MODE_SOURCE, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut op = DurableWrite::new(MODE_SOURCE, TEMP_DIR, b"Goodbye World".to_vec());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("Goodbye World", fs::read_to_string(MODE_SOURCE).unwrap());
+        assert_eq!(
+            0o600,
+            fs::metadata(MODE_SOURCE).unwrap().permissions().mode() & 0o777
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(
+            0o600,
+            fs::metadata(MODE_SOURCE).unwrap().permissions().mode() & 0o777
+        );
+
+        fs::remove_file(MODE_SOURCE);
+    }
+}
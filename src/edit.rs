@@ -0,0 +1,91 @@
+use std::io;
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+
+/// Edits a file by passing its contents to a closure and writing back what it returns
+///
+/// The file is only backed up and written once the closure succeeds, an `Error` returned by the closure fails the operation
+pub struct EditFile {
+    file: FileBackup,
+    editor: Box<dyn FnMut(Vec<u8>) -> io::Result<Vec<u8>>>,
+}
+
+impl EditFile {
+    /// Constructs a new EditFile operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        editor: Box<dyn FnMut(Vec<u8>) -> io::Result<Vec<u8>>>,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            editor,
+        }
+    }
+}
+
+impl Rewrite for EditFile {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        (self.editor)(contents).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RollbackableOperation;
+    use std::fs;
+    use std::io::{Error, ErrorKind};
+
+    const SOURCE: &str = "./edit_file_source.txt";
+    const FAILING_SOURCE: &str = "./edit_file_failing.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn edit_file_works() {
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        let mut op = EditFile::new(
+            SOURCE,
+            TEMP_DIR,
+            Box::new(|data| Ok(data.to_ascii_uppercase())),
+        );
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("HELLO WORLD", fs::read_to_string(SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn edit_file_editor_error_works() {
+        fs::write(FAILING_SOURCE, "Hello World").unwrap();
+
+        let mut op = EditFile::new(
+            FAILING_SOURCE,
+            TEMP_DIR,
+            Box::new(|_| Err(Error::new(ErrorKind::InvalidData, "cannot edit"))),
+        );
+
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+        assert_eq!(None, op.backup_path());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(FAILING_SOURCE).unwrap());
+
+        fs::remove_file(FAILING_SOURCE);
+    }
+}
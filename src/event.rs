@@ -0,0 +1,16 @@
+use std::io;
+
+/// Event emitted by a [Transaction](struct.Transaction.html) to its [event sender](struct.Transaction.html#method.with_event_sender)
+#[derive(Debug)]
+pub enum TransactionEvent {
+    /// The first operation is about to be executed
+    Started,
+    /// The operation at the index was executed
+    OpSucceeded(usize),
+    /// The operation at the index failed with the `Error`
+    OpFailed(usize, io::Error),
+    /// The operation at the index was rollbacked
+    RolledBack(usize),
+    /// The transaction was committed
+    Committed,
+}
@@ -0,0 +1,191 @@
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+
+use crate::{Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Wraps an operation to make its execution fail, for testing rollback paths
+///
+/// By default the wrapped operation is executed before failing, so its effects must be rollbacked.
+/// With [with_fail_before](#method.with_fail_before) it fails without executing the wrapped operation.
+/// Everything else is delegated to the wrapped operation. Only available with the `test-util` feature
+pub struct FailAt {
+    op: Box<dyn RollbackableOperation>,
+    fail_before: bool,
+    executed: bool,
+}
+
+impl FailAt {
+    /// Constructs a new FailAt operation
+    pub fn new(op: Box<dyn RollbackableOperation>) -> Self {
+        Self {
+            op,
+            fail_before: false,
+            executed: false,
+        }
+    }
+
+    /// Fails before executing the wrapped operation instead of after
+    pub fn with_fail_before(mut self, fail_before: bool) -> Self {
+        self.fail_before = fail_before;
+        self
+    }
+}
+
+impl RollbackableOperation for FailAt {
+    fn execute(&mut self) -> io::Result<()> {
+        if !self.fail_before {
+            self.op.execute()?;
+            self.executed = true;
+        }
+
+        Err(Error::other("injected failure"))
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.executed {
+            self.op.rollback()?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.op.set_temp_dir(temp_dir)
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.op.set_backup_store(store)
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.op.set_backup_buffer_size(size)
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.op.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        match self.executed {
+            true => self.op.changes(),
+            false => vec![],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.op.backup_path()
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.op.owned_backups()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        match self.executed {
+            true => self.op.bytes_written(),
+            false => 0,
+        }
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        self.op.space_required()
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.op.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let op = self.op.clone_box()?;
+        Some(Box::new(FailAt::new(op).with_fail_before(self.fail_before)))
+    }
+
+    fn mark_committed(&mut self) {
+        self.op.mark_committed()
+    }
+
+    fn is_reversible(&self) -> bool {
+        self.op.is_reversible()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        match self.executed {
+            true => self.op.undo_steps(),
+            false => Some(vec![]),
+        }
+    }
+
+    fn inverse(&self, temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        self.op.inverse(temp_dir)
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        self.op.spec()
+    }
+
+    fn description(&self) -> String {
+        format!("FailAt({})", self.op.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CreateFile, Transaction, WriteFile};
+    use std::fs;
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn fail_at_works() {
+        const WRITTEN: &str = "./fail_at_written.txt";
+        const CREATED: &str = "./fail_at_created.txt";
+        const NEVER: &str = "./fail_at_never.txt";
+
+        fs::write(WRITTEN, "Hello").unwrap();
+
+        let mut tr = Transaction::new()
+            .write_file(WRITTEN, "./tmp", b"Jello".to_vec())
+            .add_failing(Box::new(CreateFile::new(CREATED)))
+            .create_file(NEVER);
+
+        assert_eq!("injected failure", tr.execute().unwrap_err().to_string());
+        assert!(Path::new(CREATED).exists());
+        assert!(!Path::new(NEVER).exists());
+
+        assert_eq!((), tr.rollback().unwrap());
+        assert_eq!("Hello", fs::read_to_string(WRITTEN).unwrap());
+        assert!(!Path::new(CREATED).exists());
+
+        let mut op = FailAt::new(Box::new(CreateFile::new(CREATED))).with_fail_before(true);
+        assert!(op.execute().is_err());
+        assert!(!Path::new(CREATED).exists());
+        assert_eq!((), op.rollback().unwrap());
+
+        fs::remove_file(WRITTEN);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn fail_at_delegates() {
+        const WRITTEN: &str = "./fail_at_delegates.txt";
+
+        fs::write(WRITTEN, "Hello").unwrap();
+
+        let inner = WriteFile::new(WRITTEN, "./tmp", b"Jello World".to_vec());
+        let space_required = inner.space_required();
+        let reversible = inner.is_reversible();
+
+        let mut op = FailAt::new(Box::new(inner));
+        assert_eq!(space_required, op.space_required());
+        assert_eq!(reversible, op.is_reversible());
+        assert_eq!(0, op.bytes_written());
+
+        assert!(op.execute().is_err());
+        assert_eq!(11, op.bytes_written());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello", fs::read_to_string(WRITTEN).unwrap());
+
+        fs::remove_file(WRITTEN);
+    }
+}
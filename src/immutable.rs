@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::io::{self, Error};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation};
+
+/// Value of the immutable attribute in the inode flags, from `linux/fs.h`
+const FS_IMMUTABLE_FL: libc::c_int = 0x0000_0010;
+
+/// Sets or clears the immutable attribute of a file or directory
+///
+/// Supported by filesystems like ext4, XFS and Btrfs. Changing the attribute requires `CAP_LINUX_IMMUTABLE`
+pub struct SetImmutable {
+    path: PathBuf,
+    immutable: bool,
+    original_flags: Option<libc::c_int>,
+}
+
+impl SetImmutable {
+    /// Constructs a new SetImmutable operation
+    pub fn new<S: AsRef<Path>>(path: S, immutable: bool) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            immutable,
+            original_flags: None,
+        }
+    }
+}
+
+fn get_flags(file: &File) -> io::Result<libc::c_int> {
+    let mut flags: libc::c_int = 0;
+
+    // SAFETY: FS_IOC_GETFLAGS writes a single int to the pointer, which is valid for the duration of the call
+    match unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } {
+        -1 => Err(Error::last_os_error()),
+        _ => Ok(flags),
+    }
+}
+
+fn set_flags(file: &File, flags: libc::c_int) -> io::Result<()> {
+    // SAFETY: FS_IOC_SETFLAGS reads a single int from the pointer, which is valid for the duration of the call
+    match unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } {
+        -1 => Err(Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+impl RollbackableOperation for SetImmutable {
+    fn execute(&mut self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        let flags = get_flags(&file)?;
+
+        self.original_flags = Some(flags);
+
+        if self.immutable {
+            set_flags(&file, flags | FS_IMMUTABLE_FL)
+        } else {
+            set_flags(&file, flags & !FS_IMMUTABLE_FL)
+        }
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match self.original_flags {
+            Some(flags) => set_flags(&File::open(&self.path)?, flags),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.path)]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SetImmutable::new(&self.path, self.immutable)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+
+    const SOURCE: &str = "./set_immutable.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn set_immutable_works() {
+        // SAFETY: geteuid has no preconditions
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        let mut op = SetImmutable::new(SOURCE, true);
+
+        match op.execute() {
+            Ok(()) => {}
+            // The filesystem does not support inode flags, or the capability was dropped
+            Err(e)
+                if [libc::ENOTTY, libc::EOPNOTSUPP, libc::EPERM]
+                    .contains(&e.raw_os_error().unwrap_or(0)) =>
+            {
+                fs::remove_file(SOURCE);
+                return;
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        assert!(OpenOptions::new().write(true).open(SOURCE).is_err());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(OpenOptions::new().write(true).open(SOURCE).is_ok());
+
+        fs::remove_file(SOURCE);
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::{RollbackableOperation, SpecValue};
+
+/// Deep-merges a JSON patch file into a target JSON file
+///
+/// Objects are merged recursively, any other value in the patch, arrays included, replaces the one in the target.
+/// Both files are parsed before the target is backed up, invalid JSON fails with `ErrorKind::InvalidData`
+pub struct MergeJson {
+    file: FileBackup,
+    patch: PathBuf,
+}
+
+impl MergeJson {
+    /// Constructs a new MergeJson operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        target: S,
+        patch: T,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(target, temp_dir),
+            patch: patch.as_ref().into(),
+        }
+    }
+}
+
+fn parse_json(path: &Path, contents: &[u8]) -> io::Result<Value> {
+    serde_json::from_slice(contents).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not valid JSON: {}", path.display(), e),
+        )
+    })
+}
+
+fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+impl Rewrite for MergeJson {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let mut target = parse_json(self.file.path(), &contents)?;
+        let patch = parse_json(&self.patch, &fs::read(&self.patch)?)?;
+
+        merge(&mut target, patch);
+
+        serde_json::to_vec_pretty(&target)
+            .map(Some)
+            .map_err(Error::other)
+    }
+
+    fn paths(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = self.file.paths_mut();
+        paths.push(&mut self.patch);
+        paths
+    }
+
+    fn params(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("target", SpecValue::Path(self.file.path().into())),
+            ("patch", SpecValue::Path(self.patch.clone())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+        ]
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MergeJson::new(
+            self.file.path(),
+            &self.patch,
+            self.file.temp_dir(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TARGET: &str = "./merge_json_target.json";
+    const PATCH: &str = "./merge_json_patch.json";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &str = r#"{"name":"app","server":{"host":"localhost","port":80,"tags":["a","b"]}}"#;
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn merge_json_works() {
+        fs::write(TARGET, DATA).unwrap();
+        fs::write(
+            PATCH,
+            r#"{"server":{"port":8080,"tags":["c"],"tls":{"enabled":true}}}"#,
+        )
+        .unwrap();
+
+        let mut op = MergeJson::new(TARGET, PATCH, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+
+        let merged: Value = serde_json::from_slice(&fs::read(TARGET).unwrap()).unwrap();
+        assert_eq!(
+            json!({
+                "name": "app",
+                "server": {
+                    "host": "localhost",
+                    "port": 8080,
+                    "tags": ["c"],
+                    "tls": {"enabled": true}
+                }
+            }),
+            merged
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read_to_string(TARGET).unwrap());
+
+        fs::write(PATCH, "{ not json").unwrap();
+        let mut op = MergeJson::new(TARGET, PATCH, TEMP_DIR);
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+        assert_eq!(DATA, fs::read_to_string(TARGET).unwrap());
+
+        fs::remove_file(TARGET);
+        fs::remove_file(PATCH);
+    }
+}
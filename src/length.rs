@@ -0,0 +1,230 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{Change, RollbackableOperation, UndoStep};
+
+/// Sets the length of a file
+///
+/// The file is truncated when shrinking and extended with zeroes when growing
+pub struct SetFileLength {
+    file: FileBackup,
+    len: u64,
+}
+
+impl SetFileLength {
+    /// Constructs a new SetFileLength operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, len: u64) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            len,
+        }
+    }
+}
+
+impl RollbackableOperation for SetFileLength {
+    fn execute(&mut self) -> io::Result<()> {
+        self.file.create()?;
+
+        OpenOptions::new()
+            .write(true)
+            .open(self.file.path())?
+            .set_len(self.len)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.restore()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        match self.file.backup_path() {
+            Some(_) => vec![self.file.change(Some(self.len))],
+            None => vec![],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.restore_step().into_iter().collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SetFileLength::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.len,
+        )))
+    }
+}
+
+/// Truncates a file to at most a number of bytes, keeping its beginning
+///
+/// Files within the limit are neither backed up nor modified
+pub struct CapFileSize {
+    file: FileBackup,
+    max_len: u64,
+}
+
+impl CapFileSize {
+    /// Constructs a new CapFileSize operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, max_len: u64) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            max_len,
+        }
+    }
+}
+
+impl RollbackableOperation for CapFileSize {
+    fn execute(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new().write(true).open(self.file.path())?;
+
+        if file.metadata()?.len() <= self.max_len {
+            return Ok(());
+        }
+
+        self.file.create()?;
+        file.set_len(self.max_len)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.restore()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        match self.file.backup_path() {
+            Some(_) => vec![self.file.change(Some(self.max_len))],
+            None => vec![],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.restore_step().into_iter().collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CapFileSize::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.max_len,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const GROW_SOURCE: &str = "./set_file_length_grow.txt";
+    const SHRINK_SOURCE: &str = "./set_file_length_shrink.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &[u8] = "Hello World".as_bytes();
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn set_file_length_grow_works() {
+        fs::write(GROW_SOURCE, DATA).unwrap();
+
+        let mut op = SetFileLength::new(GROW_SOURCE, TEMP_DIR, 16);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!([DATA, &[0; 5]].concat(), fs::read(GROW_SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(GROW_SOURCE).unwrap().as_slice());
+
+        fs::remove_file(GROW_SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn set_file_length_shrink_works() {
+        fs::write(SHRINK_SOURCE, DATA).unwrap();
+
+        let mut op = SetFileLength::new(SHRINK_SOURCE, TEMP_DIR, 5);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(b"Hello".to_vec(), fs::read(SHRINK_SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(SHRINK_SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SHRINK_SOURCE);
+    }
+
+    const OVER_SOURCE: &str = "./cap_file_size_over.txt";
+    const UNDER_SOURCE: &str = "./cap_file_size_under.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn cap_file_size_works() {
+        fs::write(OVER_SOURCE, DATA).unwrap();
+        fs::write(UNDER_SOURCE, DATA).unwrap();
+
+        let mut over = CapFileSize::new(OVER_SOURCE, TEMP_DIR, 5);
+        assert_eq!((), over.execute().unwrap());
+        assert_eq!(b"Hello".to_vec(), fs::read(OVER_SOURCE).unwrap());
+
+        let mut under = CapFileSize::new(UNDER_SOURCE, TEMP_DIR, 11);
+        assert_eq!((), under.execute().unwrap());
+        assert_eq!(None, under.backup_path());
+        assert_eq!(DATA, fs::read(UNDER_SOURCE).unwrap().as_slice());
+
+        assert_eq!((), over.rollback().unwrap());
+        assert_eq!((), under.rollback().unwrap());
+        assert_eq!(DATA, fs::read(OVER_SOURCE).unwrap().as_slice());
+        assert_eq!(DATA, fs::read(UNDER_SOURCE).unwrap().as_slice());
+
+        fs::remove_file(OVER_SOURCE);
+        fs::remove_file(UNDER_SOURCE);
+    }
+}
@@ -9,24 +9,24 @@
 //! use tfio::*;
 //!
 //! fn main() -> io::Result<()> {
-//! 	let temp_dir = "./PATH_TO_TEMP_DIR";
-//! 	let mut tr = Transaction::new()
-//! 				.create_file("./foo.txt")
-//! 				.create_dir("./bar")
-//! 				.write_file("./foo.txt", temp_dir, b"Hello World".to_vec())
-//! 				.move_file("./foo.txt", "./bar/foo.txt")
-//! 				.append_file("./bar/foo.txt", temp_dir, b"dlroW olleH".to_vec());
+//!     let temp_dir = "./PATH_TO_TEMP_DIR";
+//!     let mut tr = Transaction::new()
+//!                 .create_file("./foo.txt")
+//!                 .create_dir("./bar")
+//!                 .write_file("./foo.txt", temp_dir, b"Hello World".to_vec())
+//!                 .move_file("./foo.txt", "./bar/foo.txt")
+//!                 .append_file("./bar/foo.txt", temp_dir, b"dlroW olleH".to_vec());
 //!
-//! 	// Execute the transaction
-//! 	if let Err(e) = tr.execute() {
-//! 		eprintln!("Error during execution: {}", e);
+//!     // Execute the transaction
+//!     if let Err(e) = tr.execute() {
+//!         eprintln!("Error during execution: {}", e);
 //!
-//! 		// All operations can be reverted in reverse-order if `Error` is encountered
-//! 		if let Err(ee) = tr.rollback() {
-//! 			panic!("Error during transaction rollback: {}", ee);
-//! 		}
-//! 	}
-//! 	Ok(())
+//!         // All operations can be reverted in reverse-order if `Error` is encountered
+//!         if let Err(ee) = tr.rollback() {
+//!             panic!("Error during transaction rollback: {}", ee);
+//!         }
+//!     }
+//!     Ok(())
 //! }
 //! ```
 //!
@@ -37,45 +37,177 @@
 //! use tfio::{CopyFile, RollbackableOperation};
 //!
 //! fn main() -> io::Result<()> {
-//! 	fs::File::create("./foo.txt")?;
-//! 	fs::create_dir_all("./bar/baz")?;
+//!     fs::File::create("./foo.txt")?;
+//!     fs::create_dir_all("./bar/baz")?;
 //!
-//! 	let mut copy_operation = CopyFile::new("./foo.txt", "./bar/baz/foo.txt");
+//!     let mut copy_operation = CopyFile::new("./foo.txt", "./bar/baz/foo.txt");
 //!
-//! 	// Execute the operation
-//! 	if let Err(e) = copy_operation.execute() {
-//! 		eprintln!("Error during execution: {}", e);
+//!     // Execute the operation
+//!     if let Err(e) = copy_operation.execute() {
+//!         eprintln!("Error during execution: {}", e);
 //!
-//! 		// Rollback the operation
-//! 		if let Err(ee) = copy_operation.rollback() {
-//! 			panic!("Error during rollback: {}", ee);
-//! 		}
-//! 	}
-//! 	Ok(())
+//!         // Rollback the operation
+//!         if let Err(ee) = copy_operation.rollback() {
+//!             panic!("Error during rollback: {}", ee);
+//!         }
+//!     }
+//!     Ok(())
 //! }
 //! ```
 
 #![deny(missing_docs)]
 
+#[cfg(all(target_os = "linux", feature = "posix-acl"))]
+mod acl;
 mod append;
+#[cfg(feature = "archive")]
+mod archive;
+mod assert;
+pub mod backup;
+#[cfg(feature = "hash")]
+mod checksum;
+mod compare;
 mod copy;
+mod counter;
 mod create;
+#[cfg(feature = "hash")]
+mod deduplicate;
 mod delete;
+mod described;
+#[cfg(feature = "download")]
+mod download;
+mod durable;
+mod edit;
+mod event;
+#[cfg(feature = "test-util")]
+mod fail;
+#[cfg(target_os = "linux")]
+mod immutable;
+#[cfg(feature = "serde_json")]
+mod json;
+mod length;
+mod lockfile;
+mod map;
+mod metrics;
 mod r#move;
+mod newline;
+mod patch;
+#[cfg(unix)]
+mod permissions;
+mod plan;
+mod policy;
+mod precondition;
+mod prepend;
+mod prune;
+mod rename;
+mod replace;
+mod report;
+mod retab;
+mod rewrite;
+#[cfg(unix)]
+mod secure;
+mod snapshot;
+mod sort;
+mod stage;
+#[cfg(feature = "regex")]
+mod substitute;
+#[cfg(unix)]
+mod symlink;
+#[cfg(feature = "archive-gz")]
+mod tarball;
+#[cfg(feature = "template")]
+mod template;
+mod times;
+#[cfg(feature = "encoding_rs")]
+mod transcode;
+mod trim;
+mod undo;
+pub mod util;
 mod write;
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, OpenOptions};
-use std::io::{self, Error, ErrorKind, Read, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, BufReader, Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant, SystemTime};
 
 use uuid::Uuid;
 
-pub use append::AppendFile;
+#[cfg(all(target_os = "linux", feature = "posix-acl"))]
+pub use acl::SetDefaultAcl;
+pub use append::{AppendFile, AppendWithRotation};
+pub use assert::{
+    AssertEmptyDir, AssertFile, AssertPathType, GuardUnchanged, PathType, RequireFreeSpace,
+};
+use backup::Backup;
+pub use backup::{clean_orphaned_backups, list_orphaned_backups};
+#[cfg(feature = "hash")]
+pub use checksum::{VerifyChecksum, VerifyHash, WriteChecksum};
+pub use compare::CompareAndWrite;
 pub use copy::{CopyDirectory, CopyFile};
-pub use create::{CreateDirectory, CreateFile};
-pub use delete::{DeleteDirectory, DeleteFile};
-pub use r#move::{MoveDirectory, MoveFile, MoveOperation};
-pub use write::WriteFile;
+pub use counter::IncrementCounter;
+pub use create::{CreateDirectory, CreateFile, CreateManyFiles, CreateMarkedDir, CreateTempFile};
+#[cfg(feature = "hash")]
+pub use deduplicate::{DeduplicateDirectory, DeduplicateFile};
+pub use delete::{DeleteDirectory, DeleteFile, DeleteMany};
+use described::Committed;
+pub use described::Described;
+#[cfg(feature = "download")]
+pub use download::DownloadFile;
+pub use durable::DurableWrite;
+pub use edit::EditFile;
+pub use event::TransactionEvent;
+#[cfg(feature = "test-util")]
+pub use fail::FailAt;
+#[cfg(target_os = "linux")]
+pub use immutable::SetImmutable;
+#[cfg(feature = "serde_json")]
+pub use json::MergeJson;
+pub use length::{CapFileSize, SetFileLength};
+pub use lockfile::WriteLockfile;
+pub use map::MapLines;
+pub use metrics::TagMetrics;
+pub use newline::{EnsureFinalNewline, NormalizeTextFile};
+pub use patch::MultiPatch;
+#[cfg(unix)]
+pub use permissions::{CopyOwner, CopyPermissions, HardenPermissions};
+pub use plan::SpecValue;
+use policy::DropState;
+pub use policy::{DropPolicy, RollbackStrategy};
+pub use precondition::Precondition;
+pub use prepend::PrependHeaderIfMissing;
+pub use prune::PruneEmptyDirs;
+pub use r#move::{
+    FlattenDir, MoveAndRewrite, MoveDirectory, MoveFile, MoveIntoDir, MoveMany, MoveOperation,
+};
+pub use rename::{RenamePattern, RenumberFiles};
+pub use replace::ReplaceDirectory;
+pub use report::{Change, ChangeKind, ChangeReport};
+pub use retab::{Retab, RetabDirection};
+#[cfg(unix)]
+pub use secure::WriteSecure;
+pub use snapshot::SnapshotDirectory;
+pub use sort::SortLines;
+pub use stage::StageWrite;
+#[cfg(feature = "regex")]
+pub use substitute::RegexReplace;
+#[cfg(unix)]
+pub use symlink::RetargetSymlink;
+#[cfg(feature = "archive-gz")]
+pub use tarball::SnapshotToTar;
+#[cfg(feature = "template")]
+pub use template::RenderTemplate;
+pub use times::CopyTimes;
+#[cfg(feature = "encoding_rs")]
+pub use transcode::Transcode;
+pub use trim::TrimWhitespace;
+pub use undo::{replay_undo, UndoStep};
+pub use write::{WriteFile, WriteMany};
+
+/// Default size of the buffer used to stream files into their backups (64 KiB)
+pub const DEFAULT_BACKUP_BUFFER_SIZE: usize = 64 * 1024;
 
 /// Trait that represents a Rollbackable operation
 pub trait RollbackableOperation {
@@ -84,9 +216,126 @@ pub trait RollbackableOperation {
 
     /// Rollbacks the operation
     fn rollback(&self) -> io::Result<()>;
+
+    /// Sets the temp dir used to store backups
+    ///
+    /// Does nothing for operations that do not create backups
+    fn set_temp_dir(&mut self, _temp_dir: &Path) {}
+
+    /// Sets the content-addressed store used to share identical backups between operations
+    ///
+    /// Does nothing for operations that do not back up files
+    fn set_backup_store(&mut self, _store: &Path) {}
+
+    /// Sets the size of the buffer used to stream files into their backups
+    ///
+    /// Does nothing for operations that do not back up files. Defaults to [DEFAULT_BACKUP_BUFFER_SIZE](constant.DEFAULT_BACKUP_BUFFER_SIZE.html)
+    fn set_backup_buffer_size(&mut self, _size: usize) {}
+
+    /// Returns whether the operation can currently be rollbacked
+    ///
+    /// Operations that rely on a backup should check that it still exists. Defaults to `true`
+    fn can_rollback(&self) -> bool {
+        true
+    }
+
+    /// Returns the changes made to the filesystem by the operation
+    ///
+    /// Only meaningful after the operation was executed. Defaults to no changes
+    fn changes(&self) -> Vec<Change> {
+        vec![]
+    }
+
+    /// Returns path to the backup created by the operation, if any
+    fn backup_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns paths to every backup the operation disposes off once dropped
+    ///
+    /// Unlike [backup_path](#method.backup_path) this covers operations that keep several backups, and leaves out files that
+    /// outlive the operation, like rotated siblings. Used to store backups in a [backup archive](struct.Transaction.html#method.with_backup_archive).
+    /// Defaults to the backup path
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backup_path().into_iter().collect()
+    }
+
+    /// Returns the number of bytes written by the operation
+    ///
+    /// Only meaningful after the operation was executed. Defaults to 0
+    fn bytes_written(&self) -> u64 {
+        0
+    }
+
+    /// Returns an estimate of the bytes the operation will write, along with where they will be written
+    ///
+    /// Backups count as writes to the temp dir. Used by [Transaction::check_free_space](struct.Transaction.html#method.check_free_space). Defaults to no writes
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        vec![]
+    }
+
+    /// Returns mutable references to the paths the operation acts on
+    ///
+    /// Used to rewrite paths of queued operations, e.g. by [Transaction::canonicalize_paths](struct.Transaction.html#method.canonicalize_paths). Defaults to no paths
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![]
+    }
+
+    /// Returns a copy of the operation with its execution state reset
+    ///
+    /// Returns `None` if the operation cannot be cloned, which is the default
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        None
+    }
+
+    /// Marks the operation as committed
+    ///
+    /// Called by [Transaction::commit](struct.Transaction.html#method.commit) before the operation is dropped. Does nothing by default
+    fn mark_committed(&mut self) {}
+
+    /// Returns whether the effects of the operation can be undone by [rollback](#tymethod.rollback)
+    ///
+    /// Operations with external effects, like sending data over a socket, should return `false`. Defaults to `true`
+    fn is_reversible(&self) -> bool {
+        true
+    }
+
+    /// Returns the steps needed to undo the executed operation without the operation itself
+    ///
+    /// Returns `None` if the operation cannot be undone this way, which is the default
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        None
+    }
+
+    /// Returns an operation that reverses the effect of this one without relying on its backups
+    ///
+    /// Only operations whose inverse follows from their structure, like a move or a create, provide one.
+    /// An inverse that needs backups of its own creates them in `temp_dir`.
+    /// Used by [Transaction::inverse](struct.Transaction.html#method.inverse). Defaults to `None`
+    fn inverse(&self, _temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        None
+    }
+
+    /// Returns the parameters the operation was constructed with, by name
+    ///
+    /// Used by [Transaction::plan_json](struct.Transaction.html#method.plan_json). Defaults to no parameters
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![]
+    }
+
+    /// Returns a human readable description of the operation
+    ///
+    /// Used by [Transaction::describe](struct.Transaction.html#method.describe) and in errors. Defaults to the name of the type,
+    /// use [Described](struct.Described.html) to supply a custom one
+    fn description(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        let path = name.split('<').next().unwrap_or(name);
+        path.rsplit("::").next().unwrap_or(path).to_string()
+    }
 }
 
 /// Trait that represents a Directory operation
+#[allow(drop_bounds)]
 pub trait DirectoryOperation: RollbackableOperation + Drop {
     /// Returns path to source directory
     fn get_path(&self) -> &Path;
@@ -102,41 +351,35 @@ pub trait DirectoryOperation: RollbackableOperation + Drop {
     /// Returns path to temp dir
     fn get_temp_dir(&self) -> &Path;
 
+    /// Returns path to the dir the backup is created in, if it overrides the [temp dir](#method.get_temp_dir)
+    ///
+    /// Defaults to `None`
+    fn get_backup_dir(&self) -> Option<&Path> {
+        None
+    }
+
     /// Dispose off resources used by the operation
     ///
-    /// It should be called inside [Drop](std::ops::Drop)
+    /// It should be called inside [Drop](std::ops::Drop). A backup that does not exist is not an `Error`
     fn dispose(&self) -> io::Result<()> {
-        fs::remove_dir(self.get_backup_path())
+        Backup::existing_dir(self.get_backup_path()).dispose()
     }
 
     /// Creates a backup of the source directory
     ///
     /// If backup file is successfully created, method should call [set_backup_path](#method.set_backup_path)
     fn create_backup_folder(&mut self) -> io::Result<()> {
-        fs::create_dir_all(&self.get_temp_dir())?;
-
-        let uuid = Uuid::new_v4();
-        let mut buffer = [b' '; 36];
-
-        uuid.to_hyphenated().encode_lower(&mut buffer);
+        let backup_dir = self.get_backup_dir().unwrap_or(self.get_temp_dir());
+        let backup = Backup::of_dir(self.get_path(), backup_dir)?;
 
-        let uuid_str = String::from_utf8(buffer.to_vec())
-            .expect(format!("Could not convert buffer to String").as_str());
-        let backup_path = Path::new(&self.get_temp_dir())
-            .join(uuid_str)
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        copy_dir(self.get_path(), &backup_path)?;
-
-        self.set_backup_path(&backup_path);
+        self.set_backup_path(backup.path());
 
         Ok(())
     }
 }
 
 /// Trait that represents a single file operation
+#[allow(drop_bounds)]
 pub trait SingleFileOperation: RollbackableOperation + Drop {
     /// Returns path to source file
     fn get_path(&self) -> &Path;
@@ -152,47 +395,70 @@ pub trait SingleFileOperation: RollbackableOperation + Drop {
     /// Returns path to temp dir
     fn get_temp_dir(&self) -> &Path;
 
+    /// Returns path to the content-addressed store shared with other operations, if any
+    ///
+    /// Defaults to `None`
+    fn get_backup_store(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns path to the dir the backup is created in, if it overrides the [temp dir](#method.get_temp_dir)
+    ///
+    /// Defaults to `None`
+    fn get_backup_dir(&self) -> Option<&Path> {
+        None
+    }
+
     /// Dispose off resources used by the operation
     ///
-    /// It should be called inside [Drop](std::ops::Drop)
+    /// It should be called inside [Drop](std::ops::Drop). A backup that does not exist is not an `Error`
     fn dispose(&self) -> io::Result<()> {
-        fs::remove_file(self.get_backup_path())
+        Backup::existing_file(self.get_backup_path()).dispose()
+    }
+
+    /// Returns the size of the buffer used to stream the source file into its backup
+    ///
+    /// Defaults to [DEFAULT_BACKUP_BUFFER_SIZE](constant.DEFAULT_BACKUP_BUFFER_SIZE.html)
+    fn backup_buffer_size(&self) -> usize {
+        DEFAULT_BACKUP_BUFFER_SIZE
     }
 
     /// Creates a backup of the source file
     ///
+    /// The file is streamed through a buffer of [backup_buffer_size](#method.backup_buffer_size) bytes.
+    /// With the `hash` feature and a [backup store](#method.get_backup_store), identical files share a single copy.
     /// If backup file is successfully created, method should call [set_backup_path](#method.set_backup_path)
     fn create_backup_file(&mut self) -> io::Result<()> {
-        fs::create_dir_all(&self.get_temp_dir())?;
+        let backup = back_up_file(
+            self.get_path(),
+            self.get_backup_dir().unwrap_or(self.get_temp_dir()),
+            self.get_backup_store(),
+            self.backup_buffer_size(),
+        )?;
 
-        let uuid = Uuid::new_v4();
-        let mut buffer = [b' '; 36];
-
-        uuid.to_hyphenated().encode_lower(&mut buffer);
-
-        let uuid_str = String::from_utf8(buffer.to_vec())
-            .expect(format!("Could not convert buffer to String").as_str());
-        let backup_path = Path::new(&self.get_temp_dir())
-            .join(uuid_str)
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        let mut buffer = Vec::new();
-        let mut dest_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&backup_path)?;
-        let mut source_file = OpenOptions::new().read(true).open(&self.get_path())?;
-
-        source_file.read_to_end(&mut buffer)?;
-        dest_file.write_all(&buffer)?;
-        self.set_backup_path(&backup_path);
+        self.set_backup_path(backup.path());
 
         Ok(())
     }
 }
 
+/// Backs up `path` into `backup_dir`, or into `store` with the `hash` feature
+fn back_up_file(
+    path: &Path,
+    backup_dir: &Path,
+    store: Option<&Path>,
+    buffer_size: usize,
+) -> io::Result<Backup> {
+    #[cfg(feature = "hash")]
+    if let Some(store) = store {
+        return Backup::of_file_in_store(path, backup_dir, store);
+    }
+    #[cfg(not(feature = "hash"))]
+    let _ = store;
+
+    Backup::of_file_with_buffer_size(path, backup_dir, buffer_size)
+}
+
 fn copy_dir<U: AsRef<Path>, V: AsRef<Path>>(from: U, to: V) -> io::Result<()> {
     let mut stack = Vec::new();
     stack.push(PathBuf::from(from.as_ref()));
@@ -224,24 +490,209 @@ fn copy_dir<U: AsRef<Path>, V: AsRef<Path>>(from: U, to: V) -> io::Result<()> {
                         let dest_path = dest.join(filename);
                         fs::copy(&path, &dest_path)?;
                     }
-                    None => {
+                    None => return Err(Error::other("Could not extract filename from path")),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn file_size<S: AsRef<Path>>(path: S) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+fn copy_file_contents<S: AsRef<Path>, T: AsRef<Path>>(
+    from: S,
+    to: T,
+    buffer_size: usize,
+) -> io::Result<()> {
+    let mut reader =
+        BufReader::with_capacity(buffer_size, OpenOptions::new().read(true).open(from)?);
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+
+    io::copy(&mut reader, &mut writer)?;
+
+    Ok(())
+}
+
+/// Gives the file at `path` the permissions and, where allowed, the owner described by `original`
+///
+/// Used before a new file is renamed over an existing one, so the replace does not change its mode. Changing the owner
+/// usually requires privileges and is skipped when denied
+fn preserve_metadata(original: &fs::Metadata, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{chown, MetadataExt};
+
+        let current = fs::metadata(path)?;
+        if current.uid() != original.uid() || current.gid() != original.gid() {
+            match chown(path, Some(original.uid()), Some(original.gid())) {
+                Err(e) if e.kind() == ErrorKind::PermissionDenied => {}
+                result => result?,
+            }
+        }
+    }
+
+    fs::set_permissions(path, original.permissions())
+}
+
+fn normalize_path(path: &Path) -> io::Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Rewrites the paths of `op` to live under `root`
+///
+/// Leaves the paths untouched and fails with `ErrorKind::PermissionDenied` if any of them climbs above `root`
+fn sandbox_paths(root: &Path, op: &mut dyn RollbackableOperation) -> io::Result<()> {
+    let mut paths = op.paths_mut();
+    let mut sandboxed = Vec::with_capacity(paths.len());
+
+    for path in paths.iter() {
+        if path.as_os_str().is_empty() {
+            sandboxed.push(PathBuf::new());
+            continue;
+        }
+
+        let mut relative = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+                Component::ParentDir => {
+                    if !relative.pop() {
                         return Err(Error::new(
-                            ErrorKind::Other,
-                            "Could not extract filename from path",
-                        ))
+                            ErrorKind::PermissionDenied,
+                            format!("{} escapes the sandbox", path.display()),
+                        ));
                     }
                 }
+                Component::Normal(name) => relative.push(name),
             }
         }
+        sandboxed.push(root.join(relative));
+    }
+
+    for (path, new) in paths.iter_mut().zip(sandboxed) {
+        **path = new;
     }
 
     Ok(())
 }
 
+/// Joins the non-empty relative paths of `op` onto `cwd`
+fn resolve_relative_paths(cwd: &Path, op: &mut dyn RollbackableOperation) {
+    for path in op.paths_mut() {
+        if !path.as_os_str().is_empty() && path.is_relative() {
+            *path = cwd.join(&*path);
+        }
+    }
+}
+
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .unwrap_or_else(|| Path::new("."))
+        .into()
+}
+
+#[cfg(unix)]
+fn filesystem_id(path: &Path) -> io::Result<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(fs::metadata(path)?.dev().to_string())
+}
+
+#[cfg(not(unix))]
+fn filesystem_id(path: &Path) -> io::Result<String> {
+    let root: PathBuf = fs::canonicalize(path)?.components().take(1).collect();
+
+    Ok(root.display().to_string())
+}
+
+fn unique_path<S: AsRef<Path>>(dir: S) -> PathBuf {
+    dir.as_ref()
+        .join(Uuid::new_v4().to_hyphenated().to_string())
+}
+
+fn non_empty_path(path: &Path) -> Option<&Path> {
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn remove_temp_dir<S: AsRef<Path>>(temp_dir: S) -> io::Result<()> {
+    ignore_not_found(fs::remove_dir_all(temp_dir))
+}
+
+#[cfg(feature = "hash")]
+fn hash_file<S: AsRef<Path>>(path: S) -> io::Result<String> {
+    hash_reader(OpenOptions::new().read(true).open(path)?)
+}
+
+#[cfg(feature = "hash")]
+fn hash_reader<R: io::Read>(mut reader: R) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut reader, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
 /// A rollbackable Transaction
 pub struct Transaction {
     ops: Vec<Box<dyn RollbackableOperation>>,
+    tags: Vec<Option<String>>,
+    durations: Vec<Duration>,
     execution_count: usize,
+    commit_hooks: Vec<Box<dyn FnOnce() -> io::Result<()>>>,
+    temp_dir: Option<PathBuf>,
+    event_sender: Option<Sender<TransactionEvent>>,
+    sandbox: Option<PathBuf>,
+    sandbox_escapes: Vec<usize>,
+    pinned_cwd: Option<PathBuf>,
+    rollback_strategy: RollbackStrategy,
+    max_ops: Option<usize>,
+    total_timeout: Option<Duration>,
+    group_starts: Vec<usize>,
+    committed_count: usize,
+    rollback_log: RefCell<Vec<String>>,
+    backup_buffer_size: Option<usize>,
+    #[cfg(feature = "archive")]
+    backup_archive: Option<PathBuf>,
+    #[cfg(feature = "hash")]
+    backup_store: Option<PathBuf>,
 }
 
 impl Transaction {
@@ -249,122 +700,1513 @@ impl Transaction {
     pub fn new() -> Self {
         Self {
             ops: vec![],
+            tags: vec![],
+            durations: vec![],
             execution_count: 0,
+            commit_hooks: vec![],
+            temp_dir: None,
+            event_sender: None,
+            sandbox: None,
+            sandbox_escapes: vec![],
+            pinned_cwd: None,
+            rollback_strategy: RollbackStrategy::default(),
+            max_ops: None,
+            total_timeout: None,
+            group_starts: vec![],
+            committed_count: 0,
+            rollback_log: RefCell::new(vec![]),
+            backup_buffer_size: None,
+            #[cfg(feature = "archive")]
+            backup_archive: None,
+            #[cfg(feature = "hash")]
+            backup_store: None,
         }
     }
 
-    /// Adds a [CreateFile](struct.CreateFile.html) operation to the transaction
-    pub fn create_file<S: AsRef<Path>>(mut self, path: S) -> Transaction {
-        self.ops.push(Box::new(CreateFile::new(path)));
+    /// Constructs a new, empty Transaction that manages its own temp dir
+    ///
+    /// A unique temp dir is created under [std::env::temp_dir](std::env::temp_dir) and every operation added to the transaction creates its backups inside it, regardless of the temp dir it was constructed with. The whole temp dir is removed on [commit](#method.commit) or a successful [rollback](#method.rollback)
+    pub fn scoped() -> Self {
+        let mut tr = Self::new();
+        tr.temp_dir =
+            Some(std::env::temp_dir().join(format!("tfio-{}", Uuid::new_v4().to_hyphenated())));
+        tr
+    }
+
+    /// Returns a report of the changes made by the executed operations
+    pub fn change_report(&self) -> ChangeReport {
+        ChangeReport {
+            changes: self.changes(),
+        }
+    }
+
+    /// Stores the backups of all operations in a single tar archive
+    ///
+    /// After each operation is executed successfully its [owned backups](trait.RollbackableOperation.html#method.owned_backups) are moved into the
+    /// archive as entries keyed by the operation index. The entries are extracted again on [rollback](#method.rollback), and the archive
+    /// is removed on [commit](#method.commit) or a successful rollback. Backups of a failed operation stay in its temp dir
+    #[cfg(feature = "archive")]
+    pub fn with_backup_archive<S: AsRef<Path>>(mut self, path: S) -> Transaction {
+        self.backup_archive = Some(path.as_ref().into());
         self
     }
 
-    /// Adds a [CreateDirectory](struct.CreateDirectory.html) operation to the transaction
-    pub fn create_dir<S: AsRef<Path>>(mut self, path: S) -> Transaction {
-        self.ops.push(Box::new(CreateDirectory::new(path)));
+    /// Exports the data needed to undo the executed operations to a file
+    ///
+    /// The backups of the operations are copied next to the file, so [replay_undo](fn.replay_undo.html) can reverse the transaction even after it was dropped. Fails with `ErrorKind::Unsupported` if an executed operation cannot be exported
+    pub fn export_undo<S: AsRef<Path>>(&self, path: S) -> io::Result<()> {
+        let steps = self.undo_steps().ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unsupported,
+                "Transaction contains an operation that cannot be exported",
+            )
+        })?;
+
+        undo::export_undo(path.as_ref(), steps)
+    }
+
+    /// Returns path to the temp dir managed by a [scoped](#method.scoped) transaction
+    pub fn temp_dir(&self) -> Option<&Path> {
+        self.temp_dir.as_deref()
+    }
+
+    fn push(mut self, op: Box<dyn RollbackableOperation>) -> Transaction {
+        if let Err(e) = self.try_push(op) {
+            panic!("{}", e);
+        }
         self
     }
 
-    /// Adds a [AppendFile](struct.AppendFile.html) operation to the transaction
-    pub fn append_file<S: AsRef<Path>>(
-        mut self,
-        source: S,
-        temp_dir: S,
-        data: Vec<u8>,
+    /// Adds an operation to the transaction, failing if it would exceed the [maximum operation count](#method.with_max_ops)
+    ///
+    /// The builder methods panic instead, so this should be preferred when the operations come from untrusted input
+    pub fn try_push(&mut self, mut op: Box<dyn RollbackableOperation>) -> io::Result<()> {
+        if let Some(max_ops) = self.max_ops {
+            if self.ops.len() >= max_ops {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Transaction is limited to {} operations, cannot add {}",
+                        max_ops,
+                        op.description()
+                    ),
+                ));
+            }
+        }
+
+        if let Some(root) = &self.sandbox {
+            if sandbox_paths(root, op.as_mut()).is_err() {
+                self.sandbox_escapes.push(self.ops.len());
+            }
+        }
+
+        if let Some(cwd) = &self.pinned_cwd {
+            resolve_relative_paths(cwd, op.as_mut());
+        }
+
+        if let Some(temp_dir) = &self.temp_dir {
+            op.set_temp_dir(temp_dir);
+        }
+
+        #[cfg(feature = "hash")]
+        if let Some(store) = &self.backup_store {
+            op.set_backup_store(store);
+        }
+
+        if let Some(size) = self.backup_buffer_size {
+            op.set_backup_buffer_size(size);
+        }
+
+        self.ops.push(op);
+        self.tags.push(None);
+        Ok(())
+    }
+
+    /// Adds an operation to the transaction
+    pub fn add_operation(self, op: Box<dyn RollbackableOperation>) -> Transaction {
+        self.push(op)
+    }
+
+    /// Adds an operation to the transaction under a tag used to aggregate [metrics](#method.metrics_by_tag)
+    pub fn add_tagged(self, tag: &str, op: Box<dyn RollbackableOperation>) -> Transaction {
+        let mut tr = self.push(op);
+        if let Some(last) = tr.tags.last_mut() {
+            *last = Some(tag.to_string());
+        }
+        tr
+    }
+
+    /// Adds an operation to the transaction with a custom [description](trait.RollbackableOperation.html#method.description)
+    pub fn add_described<S: Into<String>>(
+        self,
+        description: S,
+        op: Box<dyn RollbackableOperation>,
     ) -> Transaction {
-        self.ops
-            .push(Box::new(AppendFile::new(source, temp_dir, data)));
-        self
+        self.push(Box::new(Described::new(description, op)))
     }
 
-    /// Adds a [CopyFile](struct.CopyFile.html) operation to the transaction
-    pub fn copy_file<S: AsRef<Path>>(mut self, source: S, dest: S) -> Transaction {
-        self.ops.push(Box::new(CopyFile::new(source, dest)));
-        self
+    /// Adds an operation to the transaction that is only executed if `condition` returns `true`, see [Precondition](struct.Precondition.html)
+    pub fn add_with_precondition(
+        self,
+        condition: Box<dyn Fn() -> io::Result<bool>>,
+        op: Box<dyn RollbackableOperation>,
+    ) -> Transaction {
+        self.push(Box::new(Precondition::new(condition, op)))
     }
 
-    /// Adds a [CopyDirectory](struct.CopyDirectory.html) operation to the transaction
-    pub fn copy_dir<S: AsRef<Path>>(mut self, source: S, dest: S, temp_dir: S) -> Transaction {
+    /// Returns the [description](trait.RollbackableOperation.html#method.description) of every operation, one per line
+    /// and prefixed by its index
+    pub fn describe(&self) -> String {
         self.ops
-            .push(Box::new(CopyDirectory::new(source, dest, temp_dir)));
-        self
+            .iter()
+            .enumerate()
+            .map(|(i, op)| format!("{}: {}\n", i, op.description()))
+            .collect()
     }
 
-    /// Adds a [DeleteFile](struct.DeleteFile.html) operation to the transaction
-    pub fn delete_file<S: AsRef<Path>>(mut self, source: S, temp_dir: S) -> Transaction {
-        self.ops.push(Box::new(DeleteFile::new(source, temp_dir)));
-        self
+    /// Returns the plan of the transaction as a JSON array, without executing it
+    ///
+    /// Every operation becomes an object with its [description](trait.RollbackableOperation.html#method.description) as `type`
+    /// and its [spec](trait.RollbackableOperation.html#method.spec) as `params`. Data is represented by its length as `{"bytes": n}`.
+    /// Fails with `ErrorKind::InvalidData` if a path is not valid UTF-8
+    pub fn plan_json(&self) -> io::Result<String> {
+        plan::plan_json(self.ops.iter().map(|op| op.as_ref()))
     }
 
-    /// Adds a [DeleteDirectory](struct.DeleteDirectory.html) operation to the transaction
-    pub fn delete_dir<S: AsRef<Path>>(mut self, source: S, temp_dir: S) -> Transaction {
-        self.ops
-            .push(Box::new(DeleteDirectory::new(source, temp_dir)));
-        self
+    /// Builds a transaction that reverses this one, to be executed independently of it
+    ///
+    /// Contains the [inverse](trait.RollbackableOperation.html#method.inverse) of every operation, in reverse order.
+    /// Inverse operations back up into `temp_dir`. A [scoped](#method.scoped) transaction yields a scoped inverse.
+    /// Fails with `ErrorKind::Unsupported` if an operation can only be undone from its backups through [rollback](#method.rollback)
+    pub fn inverse<S: AsRef<Path>>(&self, temp_dir: S) -> io::Result<Transaction> {
+        let mut tr = match self.temp_dir {
+            Some(_) => Transaction::scoped(),
+            None => Transaction::new(),
+        };
+
+        for (i, op) in self.ops.iter().enumerate().rev() {
+            let op = op.inverse(temp_dir.as_ref()).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Unsupported,
+                    format!(
+                        "Operation {} cannot be inverted without a backup: {}",
+                        i,
+                        op.description()
+                    ),
+                )
+            })?;
+
+            tr.try_push(op)?;
+        }
+
+        Ok(tr)
     }
 
-    /// Adds a [MoveFile](type.MoveFile.html) operation to the transaction
-    pub fn move_file<S: AsRef<Path>>(mut self, source: S, dest: S) -> Transaction {
-        self.ops.push(Box::new(MoveFile::new(source, dest)));
-        self
+    /// Adds an operation to the transaction that fails once executed, see [FailAt](struct.FailAt.html)
+    #[cfg(feature = "test-util")]
+    pub fn add_failing(self, op: Box<dyn RollbackableOperation>) -> Transaction {
+        self.push(Box::new(FailAt::new(op)))
     }
 
-    /// Adds a [MoveDirectory](type.MoveDirectory.html) operation to the transaction
-    pub fn move_dir<S: AsRef<Path>>(mut self, source: S, dest: S) -> Transaction {
-        self.ops.push(Box::new(MoveDirectory::new(source, dest)));
+    /// Stores identical file backups only once
+    ///
+    /// Operations added afterwards hash the files they back up and hard link their backup to a shared copy in a
+    /// content-addressed store, falling back to a regular copy if linking fails. The store is under the temp dir of
+    /// a [scoped](#method.scoped) transaction, or a unique dir under [std::env::temp_dir](std::env::temp_dir) otherwise,
+    /// and is removed on [commit](#method.commit) or a successful [rollback](#method.rollback)
+    #[cfg(feature = "hash")]
+    pub fn with_dedup_backups(mut self, enabled: bool) -> Transaction {
+        self.backup_store = match (enabled, &self.temp_dir) {
+            (false, _) => None,
+            (true, Some(temp_dir)) => Some(temp_dir.join("blobs")),
+            (true, None) => Some(
+                std::env::temp_dir().join(format!("tfio-blobs-{}", Uuid::new_v4().to_hyphenated())),
+            ),
+        };
         self
     }
 
-    /// Adds a [WriteFile](struct.WriteFile.html) operation to the transaction
-    pub fn write_file<S: AsRef<Path>>(
-        mut self,
-        source: S,
-        temp_dir: S,
-        data: Vec<u8>,
-    ) -> Transaction {
-        self.ops
-            .push(Box::new(WriteFile::new(source, temp_dir, data)));
+    /// Streams the backups of operations added afterwards through a buffer of `size` bytes
+    ///
+    /// Defaults to [DEFAULT_BACKUP_BUFFER_SIZE](constant.DEFAULT_BACKUP_BUFFER_SIZE.html)
+    pub fn with_backup_buffer_size(mut self, size: usize) -> Transaction {
+        self.backup_buffer_size = Some(size);
         self
     }
-}
 
-impl RollbackableOperation for Transaction {
-    /// Executes the transaction
-    fn execute(&mut self) -> io::Result<()> {
-        for i in 0..self.ops.len() {
-            self.execution_count += 1;
-            if let Err(e) = self.ops[i].execute() {
-                return Err(e);
+    /// Returns a copy of the transaction that has not been executed
+    ///
+    /// Every operation is copied with [clone_box](trait.RollbackableOperation.html#method.clone_box) and keeps its tag.
+    /// A scoped transaction gets its own temp dir. Fails with `ErrorKind::Unsupported` if an operation cannot be cloned
+    /// or commit hooks were added, as hooks can only run once
+    pub fn try_clone(&self) -> io::Result<Transaction> {
+        if !self.commit_hooks.is_empty() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Commit hooks cannot be cloned",
+            ));
+        }
+
+        let mut tr = match self.temp_dir {
+            Some(_) => Transaction::scoped(),
+            None => Transaction::new(),
+        };
+
+        #[cfg(feature = "archive")]
+        {
+            tr.backup_archive = self.backup_archive.clone();
+        }
+
+        tr.event_sender = self.event_sender.clone();
+        tr.sandbox_escapes = self.sandbox_escapes.clone();
+        tr.rollback_strategy = self.rollback_strategy;
+        tr.max_ops = self.max_ops;
+        tr.total_timeout = self.total_timeout;
+        tr.group_starts = self.group_starts.clone();
+        tr.backup_buffer_size = self.backup_buffer_size;
+
+        #[cfg(feature = "hash")]
+        {
+            tr = tr.with_dedup_backups(self.backup_store.is_some());
+        }
+
+        for (i, op) in self.ops.iter().enumerate() {
+            let op = op.clone_box().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Operation {} cannot be cloned", i),
+                )
+            })?;
+
+            tr.try_push(op)?;
+            tr.tags[i] = self.tags[i].clone();
+        }
+
+        // Set last, the copied operations already live in the sandbox and have their paths resolved
+        tr.sandbox = self.sandbox.clone();
+        tr.pinned_cwd = self.pinned_cwd.clone();
+
+        Ok(tr)
+    }
+
+    /// Confines every operation to a sandbox dir
+    ///
+    /// The paths of every operation, queued or added afterwards, are reinterpreted relative to `root`: absolute paths
+    /// lose their root and `.` and `..` components are resolved logically. Executing an operation with a path that climbs above `root`
+    /// fails with `ErrorKind::PermissionDenied` before it touches anything. Symlinks inside the sandbox are not resolved.
+    /// The temp dir of a [scoped](#method.scoped) transaction stays outside the sandbox
+    pub fn with_sandbox<S: AsRef<Path>>(mut self, root: S) -> Transaction {
+        let root = root.as_ref().to_path_buf();
+
+        for (i, op) in self.ops.iter_mut().enumerate() {
+            if sandbox_paths(&root, op.as_mut()).is_err() {
+                self.sandbox_escapes.push(i);
+            }
+
+            if let Some(temp_dir) = &self.temp_dir {
+                op.set_temp_dir(temp_dir);
             }
         }
 
+        self.sandbox = Some(root);
+        self
+    }
+
+    /// Resolves relative paths against the current dir at the time of the call
+    ///
+    /// Relative paths of queued operations, and of operations added afterwards, are joined onto the current dir, so changing it
+    /// before the transaction is executed does not change which files are touched
+    pub fn pin_cwd(&mut self) -> io::Result<()> {
+        let cwd = std::env::current_dir()?;
+
+        for op in self.ops.iter_mut() {
+            resolve_relative_paths(&cwd, op.as_mut());
+        }
+
+        self.pinned_cwd = Some(cwd);
         Ok(())
     }
 
-    /// Performs rollback on the transaction
+    /// Rewrites the paths of every queued operation to absolute, normalized paths
     ///
-    /// Only the operations that were executed will be rollbacked
-    fn rollback(&self) -> io::Result<()> {
-        for i in (0..self.execution_count).rev() {
-            if let Err(e) = self.ops[i].rollback() {
-                return Err(e);
-            }
+    /// Relative paths are resolved against the current dir, and `.` and `..` components are removed logically,
+    /// so the paths do not need to exist. Symlinks are not resolved
+    pub fn canonicalize_paths(&mut self) -> io::Result<()> {
+        for path in self.ops.iter_mut().flat_map(|op| op.paths_mut()) {
+            *path = normalize_path(path)?;
         }
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Checks that every filesystem written to by the transaction has enough free space
+    ///
+    /// Sums the [space_required](trait.RollbackableOperation.html#method.space_required) estimates of every operation per filesystem
+    /// and returns an `Error` with the shortfall if any of them has less space available
+    pub fn check_free_space(&self) -> io::Result<()> {
+        let mut required = HashMap::new();
 
-    #[test]
-    #[allow(unused)]
-    fn transaction_works() {
-        let temp_dir = "./tmp";
-        let mut tr = Transaction::new()
-            .create_file("./created_file_transaction.txt")
+        for (path, bytes) in self.ops.iter().flat_map(|op| op.space_required()) {
+            let existing = existing_ancestor(&path);
+            let entry = required
+                .entry(filesystem_id(&existing)?)
+                .or_insert((existing, 0u64));
+            entry.1 = entry.1.saturating_add(bytes);
+        }
+
+        for (path, bytes) in required.values() {
+            let available = fs2::available_space(path)?;
+
+            if *bytes > available {
+                return Err(Error::other(format!(
+                    "insufficient space on {}: {} bytes short",
+                    path.display(),
+                    bytes - available
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the next pending operation and returns its index
+    ///
+    /// Returns `None` once every operation was executed. Allows inspecting the filesystem between operations,
+    /// and [rollback](#method.rollback) undoes the operations executed so far
+    pub fn step(&mut self) -> io::Result<Option<usize>> {
+        let i = self.execution_count;
+
+        if i == self.ops.len() {
+            return Ok(None);
+        }
+
+        if i == 0 {
+            self.emit(TransactionEvent::Started);
+        }
+
+        if self.sandbox_escapes.contains(&i) {
+            let e = Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Operation {} ({}) escapes the sandbox",
+                    i,
+                    self.ops[i].description()
+                ),
+            );
+            self.emit(TransactionEvent::OpFailed(
+                i,
+                Error::new(e.kind(), e.to_string()),
+            ));
+            return Err(e);
+        }
+
+        self.execution_count += 1;
+        let start = Instant::now();
+        let result = self.ops[i].execute();
+
+        self.durations.truncate(i);
+        self.durations.push(start.elapsed());
+
+        match &result {
+            Ok(()) => self.emit(TransactionEvent::OpSucceeded(i)),
+            Err(e) => self.emit(TransactionEvent::OpFailed(
+                i,
+                Error::new(e.kind(), e.to_string()),
+            )),
+        }
+
+        result?;
+
+        #[cfg(feature = "archive")]
+        self.archive_backups(i)?;
+
+        Ok(Some(i))
+    }
+
+    /// Sends a [TransactionEvent](enum.TransactionEvent.html) to `sender` as operations are executed, rollbacked and committed
+    ///
+    /// Events are dropped once the receiver is gone
+    pub fn with_event_sender(mut self, sender: Sender<TransactionEvent>) -> Transaction {
+        self.event_sender = Some(sender);
+        self
+    }
+
+    /// Sets how [rollback](#method.rollback) handles operations that fail to rollback
+    ///
+    /// Defaults to [RollbackStrategy::StopOnError](enum.RollbackStrategy.html#variant.StopOnError)
+    pub fn with_rollback_strategy(mut self, strategy: RollbackStrategy) -> Transaction {
+        self.rollback_strategy = strategy;
+        self
+    }
+
+    /// Limits the number of operations the transaction may contain
+    ///
+    /// Adding an operation beyond the limit fails in [try_push](#method.try_push) and panics in the builder methods.
+    /// Operations already added are kept
+    pub fn with_max_ops(mut self, max_ops: usize) -> Transaction {
+        self.max_ops = Some(max_ops);
+        self
+    }
+
+    /// Limits the wall-clock time [execute](#method.execute) may take
+    ///
+    /// The elapsed time is checked before each operation is started, so a running operation is never interrupted. Once the
+    /// budget is exceeded the executed operations are rollbacked and execution fails with `ErrorKind::TimedOut`, even if
+    /// the rollback failed too
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Transaction {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    fn time_out(&mut self, timeout: Duration) -> io::Error {
+        let pending = self.ops.len() - self.execution_count;
+        let message = format!(
+            "Transaction exceeded its total timeout of {:?} with {} operations pending",
+            timeout, pending
+        );
+
+        let result = self.rollback();
+        self.execution_count = self.committed_count;
+
+        match result {
+            Ok(()) => Error::new(ErrorKind::TimedOut, message),
+            Err(e) => Error::new(
+                ErrorKind::TimedOut,
+                format!("{}, rollback failed: {}", message, e),
+            ),
+        }
+    }
+
+    /// Starts a new group, the operations added afterwards belong to it
+    ///
+    /// Groups only matter to [execute_groups](#method.execute_groups). Starting a group before any operation, or twice in a row,
+    /// has no effect
+    pub fn group(&mut self) {
+        let start = self.ops.len();
+
+        if start > 0 && self.group_starts.last() != Some(&start) {
+            self.group_starts.push(start);
+        }
+    }
+
+    /// Executes the transaction one [group](#method.group) at a time, committing each group before the next is started
+    ///
+    /// Once every operation of a group succeeded, its backups are disposed off and it can no longer be rollbacked. If an operation
+    /// fails, only the operations of its group are rollbacked and its error is returned, along with the error of the rollback
+    /// if that failed too. Calling this again resumes at the
+    /// failed group. This is weaker than [execute](#method.execute): the transaction is all-or-nothing per group only, and
+    /// a failure leaves the earlier groups applied. Commit hooks still only run on [commit](#method.commit)
+    pub fn execute_groups(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+        let ends: Vec<usize> = self
+            .group_starts
+            .iter()
+            .copied()
+            .chain(std::iter::once(self.ops.len()))
+            .filter(|&end| end > self.committed_count)
+            .collect();
+
+        for end in ends {
+            let group_start = self.committed_count;
+
+            while self.execution_count < end {
+                if let Some(timeout) = self.total_timeout {
+                    if start.elapsed() >= timeout {
+                        return Err(self.time_out(timeout));
+                    }
+                }
+
+                if let Err(e) = self.step() {
+                    let result = self.rollback();
+                    self.execution_count = group_start;
+
+                    return Err(match result {
+                        Ok(()) => e,
+                        Err(re) => Error::new(e.kind(), format!("{}, rollback failed: {}", e, re)),
+                    });
+                }
+            }
+
+            self.commit_group(end);
+        }
+
+        Ok(())
+    }
+
+    fn commit_group(&mut self, end: usize) {
+        for i in self.committed_count..end {
+            let committed = Box::new(Committed::of(self.ops[i].as_ref()));
+            let mut op = std::mem::replace(&mut self.ops[i], committed);
+
+            op.mark_committed();
+        }
+
+        self.committed_count = end;
+    }
+
+    /// Rollbacks the executed operations, recording the outcome of every operation it reaches in `outcomes`
+    fn rollback_recording(&self, outcomes: &mut [Option<String>]) -> io::Result<()> {
+        #[cfg(feature = "archive")]
+        self.extract_backups()?;
+
+        let mut failures = vec![];
+
+        for i in (self.committed_count..self.execution_count).rev() {
+            if !self.ops[i].is_reversible() {
+                outcomes[i] = Some("skipped, irreversible".to_string());
+                continue;
+            }
+
+            match self.ops[i].rollback() {
+                Ok(()) => {
+                    outcomes[i] = Some("rolled back".to_string());
+                    self.emit(TransactionEvent::RolledBack(i));
+                }
+                Err(e) => {
+                    outcomes[i] = Some(format!("failed: {}", e));
+
+                    if self.rollback_strategy == RollbackStrategy::StopOnError {
+                        return Err(e);
+                    }
+                    failures.push((i, e));
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            let indices: Vec<usize> = failures.iter().map(|(i, _)| *i).collect();
+            let errors: Vec<String> = failures
+                .iter()
+                .map(|(i, e)| format!("{} ({}): {}", i, self.ops[*i].description(), e))
+                .collect();
+
+            return Err(Error::other(format!(
+                "Operations {:?} failed to rollback: {}",
+                indices,
+                errors.join("; ")
+            )));
+        }
+
+        if let Some(temp_dir) = &self.temp_dir {
+            remove_temp_dir(temp_dir)?;
+        }
+
+        #[cfg(feature = "archive")]
+        if let Some(archive) = &self.backup_archive {
+            ignore_not_found(fs::remove_file(archive))?;
+        }
+
+        #[cfg(feature = "hash")]
+        if let Some(store) = &self.backup_store {
+            remove_temp_dir(store)?;
+        }
+
+        let irreversible: Vec<usize> = self
+            .irreversible_ops()
+            .into_iter()
+            .filter(|&i| i >= self.committed_count && i < self.execution_count)
+            .collect();
+
+        if irreversible.is_empty() {
+            Ok(())
+        } else {
+            let descriptions: Vec<String> = irreversible
+                .iter()
+                .map(|&i| self.ops[i].description())
+                .collect();
+
+            Err(Error::other(format!(
+                "Operations {:?} could not be reversed: {}",
+                irreversible,
+                descriptions.join(", ")
+            )))
+        }
+    }
+
+    /// Drops the operations in reverse order, so a [drop policy](enum.DropPolicy.html) that rollbacks them undoes the last one first
+    fn drop_ops(&mut self) {
+        while let Some(op) = self.ops.pop() {
+            drop(op);
+        }
+    }
+
+    fn emit(&self, event: TransactionEvent) {
+        if let Some(sender) = &self.event_sender {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Returns the [backup paths](trait.RollbackableOperation.html#method.backup_path) of the executed operations that created one
+    ///
+    /// Every [owned backup](trait.RollbackableOperation.html#method.owned_backups) is listed, except those moved into the
+    /// [backup archive](#method.with_backup_archive), which is listed itself instead
+    pub fn backup_paths(&self) -> Vec<PathBuf> {
+        let archived = self.archived_backups();
+        let mut paths = vec![];
+
+        for (i, op) in self.ops[..self.execution_count].iter().enumerate() {
+            let owned = op.owned_backups();
+
+            if let Some(path) = op.backup_path().filter(|path| !owned.contains(path)) {
+                paths.push(path.into());
+            }
+
+            for (j, path) in owned.into_iter().enumerate() {
+                if !archived.contains(&(i, j)) {
+                    paths.push(path.into());
+                }
+            }
+        }
+
+        #[cfg(feature = "archive")]
+        if let (false, Some(archive)) = (archived.is_empty(), &self.backup_archive) {
+            paths.push(archive.clone());
+        }
+
+        paths
+    }
+
+    /// Returns the indices of the operations that cannot be reversed
+    pub fn irreversible_ops(&self) -> Vec<usize> {
+        self.ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| !op.is_reversible())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Returns the outcome of the last [rollback](#method.rollback) for every operation, one line each
+    ///
+    /// Lines are ordered by operation index and read `<index>: <description>: <outcome>`, where the outcome is `rolled back`,
+    /// `failed: <error>` or `skipped, <reason>`. Empty until a rollback was attempted
+    pub fn rollback_log(&self) -> Vec<String> {
+        self.rollback_log.borrow().clone()
+    }
+
+    /// Returns the time spent executing each executed operation
+    pub fn durations(&self) -> &[Duration] {
+        &self.durations
+    }
+
+    /// Returns the bytes written and time spent by the executed operations, aggregated per tag
+    ///
+    /// Untagged operations are not included
+    pub fn metrics_by_tag(&self) -> HashMap<String, TagMetrics> {
+        let mut metrics: HashMap<String, TagMetrics> = HashMap::new();
+
+        for (i, duration) in self.durations.iter().enumerate() {
+            if let Some(tag) = &self.tags[i] {
+                let entry = metrics.entry(tag.clone()).or_default();
+                entry.ops += 1;
+                entry.bytes_written += self.ops[i].bytes_written();
+                entry.duration += *duration;
+            }
+        }
+
+        metrics
+    }
+
+    /// Registers a hook that is run after the transaction is committed
+    ///
+    /// Hooks are run in registration order by [commit](#method.commit) and never run if the transaction is rollbacked
+    pub fn on_commit(mut self, hook: Box<dyn FnOnce() -> io::Result<()>>) -> Transaction {
+        self.commit_hooks.push(hook);
+        self
+    }
+
+    /// Commits the transaction
+    ///
+    /// All operations are disposed off, cleaning up their backups, after which the commit hooks are run. Every hook is run even if an earlier one fails and the first `Error` is returned. A failing hook does not undo the commit
+    pub fn commit(mut self) -> io::Result<()> {
+        #[cfg(feature = "archive")]
+        let archive_result = match &self.backup_archive {
+            Some(archive) => ignore_not_found(fs::remove_file(archive)),
+            None => Ok(()),
+        };
+        #[cfg(not(feature = "archive"))]
+        let archive_result = Ok(());
+
+        for op in self.ops.iter_mut() {
+            op.mark_committed();
+        }
+
+        self.drop_ops();
+
+        if let Some(sender) = self.event_sender.take() {
+            let _ = sender.send(TransactionEvent::Committed);
+        }
+
+        #[cfg(feature = "hash")]
+        let archive_result = match self.backup_store.take() {
+            Some(store) => remove_temp_dir(store).and(archive_result),
+            None => archive_result,
+        };
+
+        let mut result = match self.temp_dir.take() {
+            Some(temp_dir) => remove_temp_dir(temp_dir),
+            None => Ok(()),
+        }
+        .and(archive_result);
+
+        for hook in self.commit_hooks.drain(..) {
+            if let Err(e) = hook() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Adds an [AssertEmptyDir](struct.AssertEmptyDir.html) operation to the transaction
+    pub fn assert_empty_dir<S: AsRef<Path>>(self, path: S) -> Transaction {
+        self.push(Box::new(AssertEmptyDir::new(path)))
+    }
+
+    /// Adds a [AssertFile](struct.AssertFile.html) operation to the transaction
+    pub fn assert_file<S: AsRef<Path>>(
+        self,
+        path: S,
+        expected_size: Option<u64>,
+        expected_hash: Option<String>,
+    ) -> Transaction {
+        self.push(Box::new(AssertFile::new(
+            path,
+            expected_size,
+            expected_hash,
+        )))
+    }
+
+    /// Adds an [AssertPathType](struct.AssertPathType.html) operation to the transaction
+    pub fn assert_path_type<S: AsRef<Path>>(self, path: S, expected: PathType) -> Transaction {
+        self.push(Box::new(AssertPathType::new(path, expected)))
+    }
+
+    /// Adds a [CapFileSize](struct.CapFileSize.html) operation to the transaction
+    pub fn cap_file_size<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        max_len: u64,
+    ) -> Transaction {
+        self.push(Box::new(CapFileSize::new(source, temp_dir, max_len)))
+    }
+
+    /// Adds a [CreateFile](struct.CreateFile.html) operation to the transaction
+    pub fn create_file<S: AsRef<Path>>(self, path: S) -> Transaction {
+        self.push(Box::new(CreateFile::new(path)))
+    }
+
+    /// Adds a [CreateFile](struct.CreateFile.html) operation that streams a reader into the file to the transaction
+    pub fn create_file_from_reader<S: AsRef<Path>, R: io::Read + 'static>(
+        self,
+        path: S,
+        reader: R,
+    ) -> Transaction {
+        self.push(Box::new(CreateFile::from_reader(path, reader)))
+    }
+
+    /// Adds a [CreateDirectory](struct.CreateDirectory.html) operation to the transaction
+    pub fn create_dir<S: AsRef<Path>>(self, path: S) -> Transaction {
+        self.push(Box::new(CreateDirectory::new(path)))
+    }
+
+    /// Adds a [CreateManyFiles](struct.CreateManyFiles.html) operation to the transaction
+    pub fn create_many_files<S: AsRef<Path>>(
+        self,
+        files: Vec<(PathBuf, Vec<u8>)>,
+        temp_dir: S,
+    ) -> Transaction {
+        self.push(Box::new(CreateManyFiles::new(files, temp_dir)))
+    }
+
+    /// Adds a [CreateMarkedDir](struct.CreateMarkedDir.html) operation to the transaction
+    pub fn create_marked_dir<S: AsRef<Path>, T: AsRef<Path>>(
+        self,
+        path: S,
+        sentinel: T,
+    ) -> Transaction {
+        self.push(Box::new(CreateMarkedDir::new(path, sentinel)))
+    }
+
+    /// Adds a [CreateTempFile](struct.CreateTempFile.html) operation to the transaction
+    ///
+    /// The path of the created file is reported as a created path by [change_report](#method.change_report)
+    pub fn create_temp_file<S: AsRef<Path>, T: AsRef<str>>(self, dir: S, prefix: T) -> Transaction {
+        self.push(Box::new(CreateTempFile::new(dir, prefix)))
+    }
+
+    /// Adds a [AppendFile](struct.AppendFile.html) operation to the transaction
+    pub fn append_file<S: AsRef<Path>>(self, source: S, temp_dir: S, data: Vec<u8>) -> Transaction {
+        self.push(Box::new(AppendFile::new(source, temp_dir, data)))
+    }
+
+    /// Adds a [AppendFile](struct.AppendFile.html) operation that [streams](struct.AppendFile.html#method.from_file)
+    /// the contents of another file to the transaction
+    pub fn append_from_file<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        append_from: S,
+    ) -> Transaction {
+        self.push(Box::new(AppendFile::from_file(
+            source,
+            temp_dir,
+            append_from.as_ref().into(),
+        )))
+    }
+
+    /// Adds a [AppendWithRotation](struct.AppendWithRotation.html) operation to the transaction
+    pub fn append_with_rotation<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        data: Vec<u8>,
+        max_size: u64,
+    ) -> Transaction {
+        self.push(Box::new(AppendWithRotation::new(
+            source, temp_dir, data, max_size,
+        )))
+    }
+
+    /// Adds a [CompareAndWrite](struct.CompareAndWrite.html) operation to the transaction
+    pub fn compare_and_write<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        expected: Vec<u8>,
+        new: Vec<u8>,
+    ) -> Transaction {
+        self.push(Box::new(CompareAndWrite::new(
+            source, temp_dir, expected, new,
+        )))
+    }
+
+    /// Adds a [CopyFile](struct.CopyFile.html) operation to the transaction
+    pub fn copy_file<S: AsRef<Path>>(self, source: S, dest: S) -> Transaction {
+        self.push(Box::new(CopyFile::new(source, dest)))
+    }
+
+    /// Adds a [CopyOwner](struct.CopyOwner.html) operation to the transaction
+    #[cfg(unix)]
+    pub fn copy_owner<S: AsRef<Path>>(self, target: S, reference: S) -> Transaction {
+        self.push(Box::new(CopyOwner::new(target, reference)))
+    }
+
+    /// Adds a [CopyPermissions](struct.CopyPermissions.html) operation to the transaction
+    #[cfg(unix)]
+    pub fn copy_permissions<S: AsRef<Path>>(self, target: S, reference: S) -> Transaction {
+        self.push(Box::new(CopyPermissions::new(target, reference)))
+    }
+
+    /// Adds a [CopyTimes](struct.CopyTimes.html) operation to the transaction
+    pub fn copy_times<S: AsRef<Path>>(self, target: S, reference: S) -> Transaction {
+        self.push(Box::new(CopyTimes::new(target, reference)))
+    }
+
+    /// Adds a [CopyDirectory](struct.CopyDirectory.html) operation to the transaction
+    pub fn copy_dir<S: AsRef<Path>>(self, source: S, dest: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(CopyDirectory::new(source, dest, temp_dir)))
+    }
+
+    /// Adds a [DeduplicateFile](struct.DeduplicateFile.html) operation to the transaction
+    #[cfg(feature = "hash")]
+    pub fn deduplicate_file<S: AsRef<Path>>(
+        self,
+        source: S,
+        store_dir: S,
+        temp_dir: S,
+    ) -> Transaction {
+        self.push(Box::new(DeduplicateFile::new(source, store_dir, temp_dir)))
+    }
+
+    /// Adds a [DeduplicateDirectory](struct.DeduplicateDirectory.html) operation to the transaction
+    #[cfg(feature = "hash")]
+    pub fn deduplicate_dir<S: AsRef<Path>>(self, root: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(DeduplicateDirectory::new(root, temp_dir)))
+    }
+
+    /// Adds a [DeleteFile](struct.DeleteFile.html) operation to the transaction
+    pub fn delete_file<S: AsRef<Path>>(self, source: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(DeleteFile::new(source, temp_dir)))
+    }
+
+    /// Adds a [DeleteDirectory](struct.DeleteDirectory.html) operation to the transaction
+    pub fn delete_dir<S: AsRef<Path>>(self, source: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(DeleteDirectory::new(source, temp_dir)))
+    }
+
+    /// Adds a [DeleteMany](struct.DeleteMany.html) operation to the transaction
+    pub fn delete_many<S: AsRef<Path>>(self, paths: Vec<PathBuf>, temp_dir: S) -> Transaction {
+        self.push(Box::new(DeleteMany::new(paths, temp_dir)))
+    }
+
+    /// Adds a [DownloadFile](struct.DownloadFile.html) operation to the transaction
+    #[cfg(feature = "download")]
+    pub fn download_file<S: AsRef<Path>>(self, url: &str, dest: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(DownloadFile::new(url, dest, temp_dir)))
+    }
+
+    /// Adds a [DurableWrite](struct.DurableWrite.html) operation to the transaction
+    pub fn durable_write<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        data: Vec<u8>,
+    ) -> Transaction {
+        self.push(Box::new(DurableWrite::new(source, temp_dir, data)))
+    }
+
+    /// Adds an [EditFile](struct.EditFile.html) operation to the transaction
+    pub fn edit_file<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        editor: Box<dyn FnMut(Vec<u8>) -> io::Result<Vec<u8>>>,
+    ) -> Transaction {
+        self.push(Box::new(EditFile::new(source, temp_dir, editor)))
+    }
+
+    /// Adds an [EnsureFinalNewline](struct.EnsureFinalNewline.html) operation to the transaction
+    pub fn ensure_final_newline<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        single: bool,
+    ) -> Transaction {
+        self.push(Box::new(EnsureFinalNewline::new(source, temp_dir, single)))
+    }
+
+    /// Adds a [FlattenDir](struct.FlattenDir.html) operation to the transaction
+    pub fn flatten_dir<S: AsRef<Path>>(self, subdir: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(FlattenDir::new(subdir, temp_dir)))
+    }
+
+    /// Adds a [GuardUnchanged](struct.GuardUnchanged.html) operation to the transaction
+    pub fn guard_unchanged<S: AsRef<Path>>(
+        self,
+        path: S,
+        mtime: SystemTime,
+        size: u64,
+    ) -> Transaction {
+        self.push(Box::new(GuardUnchanged::new(path, mtime, size)))
+    }
+
+    /// Adds a [HardenPermissions](struct.HardenPermissions.html) operation to the transaction
+    #[cfg(unix)]
+    pub fn harden_permissions<S: AsRef<Path>>(self, root: S) -> Transaction {
+        self.push(Box::new(HardenPermissions::new(root)))
+    }
+
+    /// Adds a [IncrementCounter](struct.IncrementCounter.html) operation to the transaction
+    pub fn increment_counter<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        delta: i64,
+    ) -> Transaction {
+        self.push(Box::new(IncrementCounter::new(source, temp_dir, delta)))
+    }
+
+    /// Adds a [MapLines](struct.MapLines.html) operation to the transaction
+    pub fn map_lines<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        f: Box<dyn FnMut(&str) -> String>,
+    ) -> Transaction {
+        self.push(Box::new(MapLines::new(source, temp_dir, f)))
+    }
+
+    /// Adds a [MergeJson](struct.MergeJson.html) operation to the transaction
+    #[cfg(feature = "serde_json")]
+    pub fn merge_json<S: AsRef<Path>>(self, target: S, patch: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(MergeJson::new(target, patch, temp_dir)))
+    }
+
+    /// Adds a [MoveAndRewrite](struct.MoveAndRewrite.html) operation to the transaction
+    pub fn move_and_rewrite<S: AsRef<Path>>(
+        self,
+        source: S,
+        dest: S,
+        files: Vec<PathBuf>,
+        temp_dir: S,
+    ) -> Transaction {
+        self.push(Box::new(MoveAndRewrite::new(source, dest, files, temp_dir)))
+    }
+
+    /// Adds a [MoveFile](type.MoveFile.html) operation to the transaction
+    pub fn move_file<S: AsRef<Path>>(self, source: S, dest: S) -> Transaction {
+        self.push(Box::new(MoveFile::new(source, dest)))
+    }
+
+    /// Adds a [MoveDirectory](type.MoveDirectory.html) operation to the transaction
+    pub fn move_dir<S: AsRef<Path>>(self, source: S, dest: S) -> Transaction {
+        self.push(Box::new(MoveDirectory::new(source, dest)))
+    }
+
+    /// Adds a [MoveIntoDir](struct.MoveIntoDir.html) operation to the transaction
+    pub fn move_into_dir<S: AsRef<Path>>(self, source: S, dest_dir: S) -> Transaction {
+        self.push(Box::new(MoveIntoDir::new(source, dest_dir)))
+    }
+
+    /// Adds a [MoveMany](struct.MoveMany.html) operation to the transaction
+    pub fn move_many<S: AsRef<Path>>(
+        self,
+        pairs: Vec<(PathBuf, PathBuf)>,
+        temp_dir: S,
+    ) -> Transaction {
+        self.push(Box::new(MoveMany::new(pairs, temp_dir)))
+    }
+
+    /// Adds a [MultiPatch](struct.MultiPatch.html) operation to the transaction
+    pub fn multi_patch<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        edits: Vec<(u64, Vec<u8>)>,
+    ) -> Transaction {
+        self.push(Box::new(MultiPatch::new(source, temp_dir, edits)))
+    }
+
+    /// Adds a [NormalizeTextFile](struct.NormalizeTextFile.html) operation to the transaction
+    pub fn normalize_text_file<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        strip_bom: bool,
+        crlf_to_lf: bool,
+        final_newline: bool,
+    ) -> Transaction {
+        self.push(Box::new(NormalizeTextFile::new(
+            source,
+            temp_dir,
+            strip_bom,
+            crlf_to_lf,
+            final_newline,
+        )))
+    }
+
+    /// Adds a [PrependHeaderIfMissing](struct.PrependHeaderIfMissing.html) operation to the transaction
+    pub fn prepend_header_if_missing<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        header: Vec<u8>,
+    ) -> Transaction {
+        self.push(Box::new(PrependHeaderIfMissing::new(
+            source, temp_dir, header,
+        )))
+    }
+
+    /// Adds a [PruneEmptyDirs](struct.PruneEmptyDirs.html) operation to the transaction
+    pub fn prune_empty_dirs<S: AsRef<Path>>(self, root: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(PruneEmptyDirs::new(root, temp_dir)))
+    }
+
+    /// Adds a [RenamePattern](struct.RenamePattern.html) operation to the transaction
+    pub fn rename_pattern<S: AsRef<Path>>(
+        self,
+        dir: S,
+        transform: Box<dyn Fn(&str) -> String>,
+    ) -> Transaction {
+        self.push(Box::new(RenamePattern::new(dir, transform)))
+    }
+
+    /// Adds a [RenderTemplate](struct.RenderTemplate.html) operation to the transaction
+    #[cfg(feature = "template")]
+    pub fn render_template<S: AsRef<Path>>(
+        self,
+        template: &str,
+        context: serde_json::Value,
+        output: S,
+        temp_dir: S,
+    ) -> Transaction {
+        self.push(Box::new(RenderTemplate::new(
+            template, context, output, temp_dir,
+        )))
+    }
+
+    /// Adds a [RenumberFiles](struct.RenumberFiles.html) operation to the transaction
+    pub fn renumber_files<S: AsRef<Path>, T: AsRef<str>>(
+        self,
+        dir: S,
+        prefix: T,
+        width: usize,
+    ) -> Transaction {
+        self.push(Box::new(RenumberFiles::new(dir, prefix, width)))
+    }
+
+    /// Adds a [RegexReplace](struct.RegexReplace.html) operation to the transaction
+    #[cfg(feature = "regex")]
+    pub fn regex_replace<S: AsRef<Path>, P: AsRef<str>>(
+        self,
+        source: S,
+        temp_dir: S,
+        pattern: P,
+        replacement: P,
+    ) -> Transaction {
+        self.push(Box::new(RegexReplace::new(
+            source,
+            temp_dir,
+            pattern,
+            replacement,
+        )))
+    }
+
+    /// Adds a [ReplaceDirectory](struct.ReplaceDirectory.html) operation to the transaction
+    pub fn replace_dir<S: AsRef<Path>>(self, target: S, new_source: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(ReplaceDirectory::new(
+            target, new_source, temp_dir,
+        )))
+    }
+
+    /// Adds a [RequireFreeSpace](struct.RequireFreeSpace.html) operation to the transaction
+    pub fn require_free_space<S: AsRef<Path>>(self, path: S, bytes: u64) -> Transaction {
+        self.push(Box::new(RequireFreeSpace::new(path, bytes)))
+    }
+
+    /// Adds a [Retab](struct.Retab.html) operation to the transaction
+    pub fn retab<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        tab_width: usize,
+        direction: RetabDirection,
+    ) -> Transaction {
+        self.push(Box::new(Retab::new(source, temp_dir, tab_width, direction)))
+    }
+
+    /// Adds a [RetargetSymlink](struct.RetargetSymlink.html) operation to the transaction
+    #[cfg(unix)]
+    pub fn retarget_symlink<S: AsRef<Path>>(self, link: S, target: S) -> Transaction {
+        self.push(Box::new(RetargetSymlink::new(link, target)))
+    }
+
+    /// Adds a [SetDefaultAcl](struct.SetDefaultAcl.html) operation to the transaction
+    #[cfg(all(target_os = "linux", feature = "posix-acl"))]
+    pub fn set_default_acl<S: AsRef<Path>, T: AsRef<str>>(self, dir: S, spec: T) -> Transaction {
+        self.push(Box::new(SetDefaultAcl::new(dir, spec)))
+    }
+
+    /// Adds a [SetFileLength](struct.SetFileLength.html) operation to the transaction
+    pub fn set_file_length<S: AsRef<Path>>(self, source: S, temp_dir: S, len: u64) -> Transaction {
+        self.push(Box::new(SetFileLength::new(source, temp_dir, len)))
+    }
+
+    /// Adds a [SetImmutable](struct.SetImmutable.html) operation to the transaction
+    #[cfg(target_os = "linux")]
+    pub fn set_immutable<S: AsRef<Path>>(self, path: S, immutable: bool) -> Transaction {
+        self.push(Box::new(SetImmutable::new(path, immutable)))
+    }
+
+    /// Adds a [SnapshotDirectory](struct.SnapshotDirectory.html) operation to the transaction
+    pub fn snapshot_dir<S: AsRef<Path>>(self, source: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(SnapshotDirectory::new(source, temp_dir)))
+    }
+
+    /// Adds a [SnapshotToTar](struct.SnapshotToTar.html) operation to the transaction
+    #[cfg(feature = "archive-gz")]
+    pub fn snapshot_to_tar<S: AsRef<Path>>(self, source: S, output: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(SnapshotToTar::new(source, output, temp_dir)))
+    }
+
+    /// Adds a [SortLines](struct.SortLines.html) operation to the transaction
+    pub fn sort_lines<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        descending: bool,
+    ) -> Transaction {
+        self.push(Box::new(SortLines::new(source, temp_dir, descending)))
+    }
+
+    /// Adds a [Transcode](struct.Transcode.html) operation to the transaction
+    #[cfg(feature = "encoding_rs")]
+    pub fn transcode<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        from: &'static encoding_rs::Encoding,
+        to: &'static encoding_rs::Encoding,
+    ) -> Transaction {
+        self.push(Box::new(Transcode::new(source, temp_dir, from, to)))
+    }
+
+    /// Adds a [TrimWhitespace](struct.TrimWhitespace.html) operation to the transaction
+    pub fn trim_whitespace<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        trim_trailing: bool,
+        collapse_blank_lines: bool,
+    ) -> Transaction {
+        self.push(Box::new(TrimWhitespace::new(
+            source,
+            temp_dir,
+            trim_trailing,
+            collapse_blank_lines,
+        )))
+    }
+
+    /// Adds a [VerifyChecksum](struct.VerifyChecksum.html) operation to the transaction
+    #[cfg(feature = "hash")]
+    pub fn verify_checksum<S: AsRef<Path>>(self, source: S) -> Transaction {
+        self.push(Box::new(VerifyChecksum::new(source)))
+    }
+
+    /// Adds a [VerifyHash](struct.VerifyHash.html) operation to the transaction
+    #[cfg(feature = "hash")]
+    pub fn verify_hash<S: AsRef<Path>, T: AsRef<str>>(self, source: S, expected: T) -> Transaction {
+        self.push(Box::new(VerifyHash::new(source, expected)))
+    }
+
+    /// Adds a [WriteChecksum](struct.WriteChecksum.html) operation to the transaction
+    #[cfg(feature = "hash")]
+    pub fn write_checksum<S: AsRef<Path>>(self, source: S, temp_dir: S) -> Transaction {
+        self.push(Box::new(WriteChecksum::new(source, temp_dir)))
+    }
+
+    /// Adds a [WriteFile](struct.WriteFile.html) operation to the transaction
+    pub fn write_file<S: AsRef<Path>>(self, source: S, temp_dir: S, data: Vec<u8>) -> Transaction {
+        self.push(Box::new(WriteFile::new(source, temp_dir, data)))
+    }
+
+    /// Adds a [WriteFile](struct.WriteFile.html) operation that [rotates](struct.WriteFile.html#method.with_rotation)
+    /// up to `keep` previous versions of the file to the transaction
+    pub fn write_file_rotated<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        data: Vec<u8>,
+        keep: usize,
+    ) -> Transaction {
+        self.push(Box::new(
+            WriteFile::new(source, temp_dir, data).with_rotation(keep),
+        ))
+    }
+
+    /// Adds a [WriteLockfile](struct.WriteLockfile.html) operation to the transaction
+    pub fn write_lockfile<S: AsRef<Path>>(self, path: S, content: Vec<u8>) -> Transaction {
+        self.push(Box::new(WriteLockfile::new(path, content)))
+    }
+
+    /// Adds a [WriteMany](struct.WriteMany.html) operation to the transaction
+    pub fn write_many<S: AsRef<Path>>(
+        self,
+        paths: Vec<PathBuf>,
+        temp_dir: S,
+        data: Vec<u8>,
+    ) -> Transaction {
+        self.push(Box::new(WriteMany::new(paths, temp_dir, data)))
+    }
+
+    /// Adds a [WriteSecure](struct.WriteSecure.html) operation to the transaction
+    #[cfg(unix)]
+    pub fn write_secure<S: AsRef<Path>>(
+        self,
+        source: S,
+        temp_dir: S,
+        data: Vec<u8>,
+        mode: u32,
+    ) -> Transaction {
+        self.push(Box::new(WriteSecure::new(source, temp_dir, data, mode)))
+    }
+}
+
+#[cfg(feature = "archive")]
+impl Transaction {
+    fn archive_backups(&self, index: usize) -> io::Result<()> {
+        let archive = match &self.backup_archive {
+            Some(archive) => archive,
+            None => return Ok(()),
+        };
+
+        for (slot, backup_path) in self.ops[index].owned_backups().into_iter().enumerate() {
+            if !backup_path.exists() {
+                continue;
+            }
+
+            archive::append_backup(archive, index, slot, backup_path)?;
+
+            if backup_path.is_dir() {
+                fs::remove_dir_all(backup_path)?;
+            } else {
+                fs::remove_file(backup_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn extract_backups(&self) -> io::Result<()> {
+        match &self.backup_archive {
+            Some(archive) if archive.exists() => {
+                archive::extract_backups(archive, |index, slot| {
+                    self.ops[..self.execution_count]
+                        .get(index)
+                        .and_then(|op| op.owned_backups().get(slot).map(PathBuf::from))
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the operation index and slot of every backup currently stored in the archive
+    fn archived_backups(&self) -> HashSet<(usize, usize)> {
+        match &self.backup_archive {
+            Some(archive) if archive.exists() => archive::list_backups(archive).unwrap_or_default(),
+            _ => HashSet::new(),
+        }
+    }
+}
+
+#[cfg(not(feature = "archive"))]
+impl Transaction {
+    fn archived_backups(&self) -> HashSet<(usize, usize)> {
+        HashSet::new()
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        self.drop_ops();
+    }
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RollbackableOperation for Transaction {
+    /// Executes the transaction
+    ///
+    /// Operations already executed with [step](struct.Transaction.html#method.step) are not executed again
+    fn execute(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+
+        while self.execution_count < self.ops.len() {
+            if let Some(timeout) = self.total_timeout {
+                if start.elapsed() >= timeout {
+                    return Err(self.time_out(timeout));
+                }
+            }
+
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Performs rollback on the transaction
+    ///
+    /// Only the operations that were executed will be rollbacked. Irreversible operations are skipped and,
+    /// once everything else was rollbacked, an `Error` listing them is returned. Failing operations are handled
+    /// according to the [RollbackStrategy](enum.RollbackStrategy.html). Backups are kept if any operation failed.
+    /// Groups already committed by [execute_groups](struct.Transaction.html#method.execute_groups) are left applied.
+    /// The outcome for every operation is available from [rollback_log](struct.Transaction.html#method.rollback_log) afterwards
+    fn rollback(&self) -> io::Result<()> {
+        let mut outcomes = vec![None; self.ops.len()];
+        let result = self.rollback_recording(&mut outcomes);
+
+        *self.rollback_log.borrow_mut() = outcomes
+            .into_iter()
+            .enumerate()
+            .map(|(i, outcome)| {
+                let outcome = outcome.unwrap_or_else(|| match i {
+                    i if i >= self.execution_count => "skipped, not executed".to_string(),
+                    i if i < self.committed_count => "skipped, committed".to_string(),
+                    _ => "skipped, rollback stopped before reaching it".to_string(),
+                });
+                format!("{}: {}: {}", i, self.ops[i].description(), outcome)
+            })
+            .collect();
+
+        result
+    }
+
+    fn is_reversible(&self) -> bool {
+        self.ops.iter().all(|op| op.is_reversible())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.ops.iter_mut().flat_map(|op| op.paths_mut()).collect()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        self.try_clone()
+            .ok()
+            .map(|tr| Box::new(tr) as Box<dyn RollbackableOperation>)
+    }
+
+    /// Returns whether every executed operation can currently be rollbacked
+    ///
+    /// Backups moved into the [backup archive](struct.Transaction.html#method.with_backup_archive) count as present
+    fn can_rollback(&self) -> bool {
+        let archived = self.archived_backups();
+
+        self.ops[..self.execution_count]
+            .iter()
+            .enumerate()
+            .all(|(i, op)| {
+                let owned = op.owned_backups();
+
+                op.can_rollback()
+                    || (!owned.is_empty()
+                        && owned
+                            .iter()
+                            .enumerate()
+                            .all(|(j, path)| path.exists() || archived.contains(&(i, j))))
+            })
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.ops[..self.execution_count]
+            .iter()
+            .flat_map(|op| op.changes())
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        for op in self.ops[..self.execution_count].iter().rev() {
+            steps.extend(op.undo_steps()?);
+        }
+
+        Some(steps)
+    }
+
+    fn mark_committed(&mut self) {
+        for op in self.ops.iter_mut() {
+            op.mark_committed();
+        }
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.ops[..self.execution_count]
+            .iter()
+            .map(|op| op.bytes_written())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    #[allow(unused)]
+    fn transaction_works() {
+        let temp_dir = "./tmp";
+        let mut tr = Transaction::new()
+            .create_file("./created_file_transaction.txt")
             .create_file("./for_delete.txt")
             .create_dir("./inner/create_dir_transaction")
             .create_dir("./for_delete_dir")
@@ -390,10 +2232,1035 @@ mod tests {
                 "./inner/created_file_transaction.txt",
                 "./inner/magic_dir/created_file_transaction.txt",
             )
-            .create_dir("./for_moving")
-            .move_dir("./for_moving", "./inner/magic_dir/for_moving");
+            .create_dir("./for_moving")
+            .move_dir("./for_moving", "./inner/magic_dir/for_moving");
+
+        assert_eq!((), tr.execute().expect("Cannot execute"));
+        assert_eq!((), tr.rollback().expect("Cannot Rollback"));
+    }
+
+    const COMMIT_HOOK_FILE: &str = "./commit_hook_file.txt";
+    const ROLLBACK_HOOK_FILE: &str = "./rollback_hook_file.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn commit_hook_works() {
+        let calls = Rc::new(Cell::new(0));
+
+        let first = calls.clone();
+        let second = calls.clone();
+        let mut tr = Transaction::new()
+            .create_file(COMMIT_HOOK_FILE)
+            .on_commit(Box::new(move || {
+                assert_eq!(0, first.get());
+                first.set(1);
+                Ok(())
+            }))
+            .on_commit(Box::new(move || {
+                assert_eq!(1, second.get());
+                second.set(2);
+                Err(Error::other("hook failed"))
+            }));
+
+        assert_eq!((), tr.execute().unwrap());
+        assert!(tr.commit().is_err());
+        assert_eq!(2, calls.get());
+        assert!(Path::new(COMMIT_HOOK_FILE).exists());
+
+        let hook = calls.clone();
+        let mut tr = Transaction::new()
+            .create_file(ROLLBACK_HOOK_FILE)
+            .on_commit(Box::new(move || {
+                hook.set(3);
+                Ok(())
+            }));
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!((), tr.rollback().unwrap());
+        drop(tr);
+        assert_eq!(2, calls.get());
+
+        fs::remove_file(COMMIT_HOOK_FILE);
+    }
+
+    const CAN_ROLLBACK_FILE: &str = "./can_rollback_file.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn can_rollback_works() {
+        let temp_dir = "./tmp/can_rollback";
+        fs::write(CAN_ROLLBACK_FILE, b"Hello World").unwrap();
+
+        let mut tr = Transaction::new().delete_file(CAN_ROLLBACK_FILE, temp_dir);
+        assert!(tr.can_rollback());
+
+        assert_eq!((), tr.execute().unwrap());
+        assert!(tr.can_rollback());
+
+        for entry in fs::read_dir(temp_dir).unwrap() {
+            fs::remove_file(entry.unwrap().path()).unwrap();
+        }
+        assert!(!tr.can_rollback());
+
+        fs::remove_dir_all(temp_dir);
+    }
+
+    const REPORT_CREATED: &str = "./change_report_created.txt";
+    const REPORT_DELETED: &str = "./change_report_deleted.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn change_report_works() {
+        let temp_dir = "./tmp";
+        fs::write(REPORT_DELETED, b"Hello World").unwrap();
+
+        let mut tr = Transaction::new()
+            .create_file(REPORT_CREATED)
+            .write_file(REPORT_CREATED, temp_dir, b"Hello".to_vec())
+            .delete_file(REPORT_DELETED, temp_dir);
+
+        assert_eq!(0, tr.change_report().changes.len());
+        assert_eq!((), tr.execute().unwrap());
+
+        let report = tr.change_report();
+        assert_eq!(3, report.changes.len());
+
+        let created = report.created();
+        assert_eq!(1, created.len());
+        assert_eq!(Path::new(REPORT_CREATED), created[0].path);
+
+        let modified = report.modified();
+        assert_eq!(1, modified.len());
+        assert_eq!(Some(0), modified[0].size_before);
+        assert_eq!(Some(5), modified[0].size_after);
+        assert_eq!(Some(5), modified[0].size_delta());
+
+        let deleted = report.deleted();
+        assert_eq!(1, deleted.len());
+        assert_eq!(Path::new(REPORT_DELETED), deleted[0].path);
+        assert_eq!(Some(11), deleted[0].size_before);
+
+        assert!(report.moved().is_empty());
+
+        assert_eq!((), tr.rollback().unwrap());
+        fs::remove_file(REPORT_DELETED);
+    }
+
+    #[cfg(feature = "archive")]
+    const ARCHIVE_WRITE_FILE: &str = "./backup_archive_write.txt";
+    #[cfg(feature = "archive")]
+    const ARCHIVE_DELETE_FILE: &str = "./backup_archive_delete.txt";
+    #[cfg(feature = "archive")]
+    const ARCHIVE_DELETE_DIR: &str = "./backup_archive_delete_dir";
+    #[cfg(feature = "archive")]
+    const ARCHIVE_MOVE_SOURCE: &str = "./backup_archive_move_source.txt";
+    #[cfg(feature = "archive")]
+    const ARCHIVE_MOVE_DEST: &str = "./backup_archive_move_dest.txt";
+
+    #[test]
+    #[cfg(feature = "archive")]
+    #[allow(unused_must_use)]
+    fn backup_archive_works() {
+        let temp_dir = "./tmp/backup_archive";
+        let archive = "./tmp/backup_archive/backups.tar";
+        let dir_file = Path::new(ARCHIVE_DELETE_DIR).join("inner.txt");
+
+        fs::write(ARCHIVE_WRITE_FILE, b"Yellow World").unwrap();
+        fs::write(ARCHIVE_DELETE_FILE, b"Hello World").unwrap();
+        fs::create_dir_all(ARCHIVE_DELETE_DIR).unwrap();
+        fs::write(&dir_file, b"Inner World").unwrap();
+        fs::write(ARCHIVE_MOVE_SOURCE, b"Moved World").unwrap();
+        fs::write(ARCHIVE_MOVE_DEST, b"Replaced World").unwrap();
+
+        let mut tr = Transaction::new()
+            .with_backup_archive(archive)
+            .write_file(ARCHIVE_WRITE_FILE, temp_dir, b"Hello".to_vec())
+            .append_file(ARCHIVE_WRITE_FILE, temp_dir, b"!".to_vec())
+            .delete_file(ARCHIVE_DELETE_FILE, temp_dir)
+            .delete_dir(ARCHIVE_DELETE_DIR, temp_dir)
+            .move_many(
+                vec![(ARCHIVE_MOVE_SOURCE.into(), ARCHIVE_MOVE_DEST.into())],
+                temp_dir,
+            );
+
+        assert_eq!((), tr.execute().unwrap());
+        let entries: Vec<_> = fs::read_dir(temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(vec![Path::new(archive).to_path_buf()], entries);
+        assert_eq!(vec![Path::new(archive).to_path_buf()], tr.backup_paths());
+        assert!(tr.can_rollback());
+
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new(archive).exists());
+        assert_eq!(
+            b"Replaced World".to_vec(),
+            fs::read(ARCHIVE_MOVE_DEST).unwrap()
+        );
+        assert_eq!(
+            b"Moved World".to_vec(),
+            fs::read(ARCHIVE_MOVE_SOURCE).unwrap()
+        );
+        assert_eq!(
+            b"Yellow World".to_vec(),
+            fs::read(ARCHIVE_WRITE_FILE).unwrap()
+        );
+        assert_eq!(
+            b"Hello World".to_vec(),
+            fs::read(ARCHIVE_DELETE_FILE).unwrap()
+        );
+        assert_eq!(b"Inner World".to_vec(), fs::read(&dir_file).unwrap());
+
+        assert_eq!((), tr.commit().unwrap());
+        assert!(!Path::new(archive).exists());
+
+        fs::remove_file(ARCHIVE_WRITE_FILE);
+        fs::remove_file(ARCHIVE_DELETE_FILE);
+        fs::remove_dir_all(ARCHIVE_DELETE_DIR);
+        fs::remove_file(ARCHIVE_MOVE_SOURCE);
+        fs::remove_file(ARCHIVE_MOVE_DEST);
+        fs::remove_dir_all(temp_dir);
+    }
+
+    const TAGGED_CONFIG: &str = "./tagged_config.txt";
+    const TAGGED_DATA: &str = "./tagged_data.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn metrics_by_tag_works() {
+        let temp_dir = "./tmp";
+
+        let mut tr = Transaction::new()
+            .create_file(TAGGED_DATA)
+            .add_tagged("config", Box::new(CreateFile::new(TAGGED_CONFIG)))
+            .add_tagged(
+                "config",
+                Box::new(WriteFile::new(TAGGED_CONFIG, temp_dir, b"Hello".to_vec())),
+            )
+            .add_tagged(
+                "data",
+                Box::new(WriteFile::new(TAGGED_DATA, temp_dir, b"Hello".to_vec())),
+            )
+            .add_tagged(
+                "data",
+                Box::new(AppendFile::new(TAGGED_DATA, temp_dir, b" World".to_vec())),
+            );
 
-        assert_eq!((), tr.execute().expect("Cannot execute"));
-        assert_eq!((), tr.rollback().expect("Cannot Rollback"));
+        assert!(tr.metrics_by_tag().is_empty());
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!(5, tr.durations().len());
+
+        let metrics = tr.metrics_by_tag();
+        assert_eq!(2, metrics.len());
+        assert_eq!(2, metrics["config"].ops);
+        assert_eq!(5, metrics["config"].bytes_written);
+        assert_eq!(2, metrics["data"].ops);
+        assert_eq!(11, metrics["data"].bytes_written);
+        assert_eq!(
+            tr.durations()[1..].iter().sum::<Duration>(),
+            metrics["config"].duration + metrics["data"].duration
+        );
+
+        assert_eq!((), tr.rollback().unwrap());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn max_ops_works() {
+        let mut tr = Transaction::new()
+            .with_max_ops(2)
+            .create_dir("./max_ops_a")
+            .create_dir("./max_ops_b");
+
+        let err = tr
+            .try_push(Box::new(CreateDirectory::new("./max_ops_c")))
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+        assert!(err.to_string().contains("limited to 2 operations"));
+        assert_eq!(2, tr.describe().lines().count());
+
+        let mut tr = Transaction::new().with_max_ops(1);
+        assert_eq!(
+            (),
+            tr.try_push(Box::new(CreateDirectory::new("./max_ops_a")))
+                .unwrap()
+        );
+        assert!(tr
+            .try_push(Box::new(CreateDirectory::new("./max_ops_b")))
+            .is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "limited to 1 operations")]
+    fn max_ops_builder_panics() {
+        Transaction::new()
+            .with_max_ops(1)
+            .create_dir("./max_ops_a")
+            .create_dir("./max_ops_b");
+    }
+
+    const SCOPED_FILE: &str = "./scoped_transaction_file.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn scoped_transaction_works() {
+        fs::write(SCOPED_FILE, b"Yellow World").unwrap();
+
+        let mut tr = Transaction::scoped().write_file(SCOPED_FILE, "./tmp", b"Hello".to_vec());
+        let temp_dir = tr.temp_dir().unwrap().to_path_buf();
+
+        assert_eq!((), tr.execute().unwrap());
+        assert!(temp_dir.exists());
+        assert_eq!(1, fs::read_dir(&temp_dir).unwrap().count());
+
+        assert_eq!((), tr.commit().unwrap());
+        assert!(!temp_dir.exists());
+
+        fs::remove_file(SCOPED_FILE);
+    }
+
+    const LARGE_FILE: &str = "./large_backup_file.bin";
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    fn chunks_equal<S: AsRef<Path>, T: AsRef<Path>>(a: S, b: T) -> bool {
+        use std::io::Read;
+
+        let mut a = BufReader::new(fs::File::open(a).unwrap());
+        let mut b = BufReader::new(fs::File::open(b).unwrap());
+        let (mut chunk_a, mut chunk_b) = (vec![0; CHUNK_SIZE], vec![0; CHUNK_SIZE]);
+
+        loop {
+            let read = a.read(&mut chunk_a).unwrap();
+            b.read_exact(&mut chunk_b[..read]).unwrap();
+
+            if chunk_a[..read] != chunk_b[..read] {
+                return false;
+            }
+            if read == 0 {
+                return b.read(&mut chunk_b).unwrap() == 0;
+            }
+        }
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn large_backup_file_works() {
+        {
+            use std::io::Write;
+
+            let mut file = io::BufWriter::new(fs::File::create(LARGE_FILE).unwrap());
+            for i in 0..32 {
+                file.write_all(&vec![i as u8; CHUNK_SIZE]).unwrap();
+            }
+        }
+
+        let mut op = DeleteFile::new(LARGE_FILE, "./tmp/");
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!Path::new(LARGE_FILE).exists());
+        assert_eq!(
+            Some(32 * CHUNK_SIZE as u64),
+            file_size(op.get_backup_path())
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(chunks_equal(LARGE_FILE, op.get_backup_path()));
+
+        copy_file_contents(LARGE_FILE, "./large_backup_file_copy.bin", 4096).unwrap();
+        assert!(chunks_equal(LARGE_FILE, "./large_backup_file_copy.bin"));
+
+        fs::remove_file("./large_backup_file_copy.bin");
+        fs::remove_file(LARGE_FILE);
+    }
+
+    const BUFFER_SIZE_FILE: &str = "./backup_buffer_size.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn backup_buffer_size_works() {
+        fs::write(BUFFER_SIZE_FILE, "Hello World").unwrap();
+
+        let mut op = DeleteFile::new(BUFFER_SIZE_FILE, "./tmp/");
+        op.set_backup_buffer_size(3);
+        assert_eq!(3, op.backup_buffer_size());
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "Hello World",
+            fs::read_to_string(op.get_backup_path()).unwrap()
+        );
+        assert_eq!((), op.rollback().unwrap());
+
+        let mut tr = Transaction::new()
+            .with_backup_buffer_size(3)
+            .write_file(BUFFER_SIZE_FILE, "./tmp/", b"Goodbye".to_vec())
+            .sort_lines(BUFFER_SIZE_FILE, "./tmp/", false);
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!((), tr.rollback().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(BUFFER_SIZE_FILE).unwrap());
+
+        fs::remove_file(BUFFER_SIZE_FILE);
+    }
+
+    struct SendNotification {
+        sent: Rc<Cell<bool>>,
+    }
+
+    impl RollbackableOperation for SendNotification {
+        fn execute(&mut self) -> io::Result<()> {
+            self.sent.set(true);
+            Ok(())
+        }
+
+        fn rollback(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn is_reversible(&self) -> bool {
+            false
+        }
+    }
+
+    const IRREVERSIBLE_FILE: &str = "./irreversible_transaction.txt";
+
+    #[test]
+    fn irreversible_ops_works() {
+        let sent = Rc::new(Cell::new(false));
+        let mut tr = Transaction::new()
+            .create_file(IRREVERSIBLE_FILE)
+            .add_operation(Box::new(SendNotification { sent: sent.clone() }));
+
+        assert_eq!(vec![1], tr.irreversible_ops());
+        assert!(!tr.is_reversible());
+
+        assert_eq!((), tr.execute().unwrap());
+        assert!(sent.get());
+
+        let err = tr.rollback().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("[1]"));
+        assert!(err.to_string().contains("SendNotification"));
+        assert!(!Path::new(IRREVERSIBLE_FILE).exists());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn rollback_strategy_works() {
+        const FIRST: &str = "./rollback_strategy_first.txt";
+        const SECOND: &str = "./rollback_strategy_second.txt";
+        const THIRD: &str = "./rollback_strategy_third.txt";
+
+        let build = |strategy| {
+            Transaction::new()
+                .with_rollback_strategy(strategy)
+                .create_file(FIRST)
+                .create_file(SECOND)
+                .create_file(THIRD)
+        };
+
+        // Removing the created files out from under the transaction makes their rollback fail
+        let mut tr = build(RollbackStrategy::ContinueAndCollect);
+        assert_eq!((), tr.execute().unwrap());
+        fs::remove_file(FIRST).unwrap();
+        fs::remove_file(THIRD).unwrap();
+
+        let err = tr.rollback().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("[2, 0]"));
+        assert!(err.to_string().contains("2 (CreateFile)"));
+        assert!(err.to_string().contains("0 (CreateFile)"));
+        assert!(!Path::new(SECOND).exists());
+
+        let mut tr = build(RollbackStrategy::StopOnError);
+        assert_eq!((), tr.execute().unwrap());
+        fs::remove_file(FIRST).unwrap();
+        fs::remove_file(THIRD).unwrap();
+
+        assert_eq!(ErrorKind::NotFound, tr.rollback().unwrap_err().kind());
+        assert!(Path::new(SECOND).exists());
+
+        fs::remove_file(SECOND);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn rollback_log_works() {
+        const FIRST: &str = "./rollback_log_first.txt";
+        const SECOND: &str = "./rollback_log_second.txt";
+        const THIRD: &str = "./rollback_log_third.txt";
+        const MISSING: &str = "./rollback_log_missing";
+        const UNREACHED: &str = "./rollback_log_unreached.txt";
+
+        let mut tr = Transaction::new()
+            .create_file(FIRST)
+            .create_file(SECOND)
+            .create_file(THIRD)
+            .assert_empty_dir(MISSING)
+            .create_file(UNREACHED);
+        assert!(tr.rollback_log().is_empty());
+
+        assert!(tr.execute().is_err());
+        fs::remove_file(SECOND).unwrap();
+
+        // The rollback of the second file fails, so the first is never reached
+        assert_eq!(ErrorKind::NotFound, tr.rollback().unwrap_err().kind());
+
+        let log = tr.rollback_log();
+        assert_eq!(5, log.len());
+        assert_eq!(
+            "0: CreateFile: skipped, rollback stopped before reaching it",
+            log[0]
+        );
+        assert!(log[1].starts_with("1: CreateFile: failed: "));
+        assert_eq!("2: CreateFile: rolled back", log[2]);
+        assert_eq!("3: AssertEmptyDir: rolled back", log[3]);
+        assert_eq!("4: CreateFile: skipped, not executed", log[4]);
+
+        assert!(Path::new(FIRST).exists());
+        assert!(!Path::new(THIRD).exists());
+
+        fs::remove_file(FIRST);
+    }
+
+    struct Sleep {
+        duration: Duration,
+        rolled_back: Rc<Cell<bool>>,
+        fail_rollback: bool,
+    }
+
+    impl RollbackableOperation for Sleep {
+        fn execute(&mut self) -> io::Result<()> {
+            std::thread::sleep(self.duration);
+            Ok(())
+        }
+
+        fn rollback(&self) -> io::Result<()> {
+            self.rolled_back.set(true);
+
+            match self.fail_rollback {
+                true => Err(Error::other("Sleep cannot be rollbacked")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn total_timeout_works() {
+        const FIRST: &str = "./total_timeout_first.txt";
+        const SECOND: &str = "./total_timeout_second.txt";
+
+        let rolled_back = Rc::new(Cell::new(false));
+        let sleep = |rolled_back: &Rc<Cell<bool>>| {
+            Box::new(Sleep {
+                duration: Duration::from_millis(50),
+                rolled_back: rolled_back.clone(),
+                fail_rollback: false,
+            })
+        };
+
+        let mut tr = Transaction::new()
+            .with_total_timeout(Duration::from_millis(30))
+            .create_file(FIRST)
+            .add_operation(sleep(&rolled_back))
+            .create_file(SECOND)
+            .add_operation(sleep(&rolled_back));
+
+        let err = tr.execute().unwrap_err();
+        assert_eq!(ErrorKind::TimedOut, err.kind());
+        assert!(err.to_string().contains("2 operations pending"));
+
+        // The completed operations were rollbacked and the rest never started
+        assert!(rolled_back.get());
+        assert!(!Path::new(FIRST).exists());
+        assert!(!Path::new(SECOND).exists());
+
+        // Nothing is left to rollback
+        rolled_back.set(false);
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!rolled_back.get());
+    }
+
+    #[test]
+    fn total_timeout_rollback_failure_works() {
+        let mut tr = Transaction::new()
+            .with_total_timeout(Duration::from_millis(30))
+            .add_operation(Box::new(Sleep {
+                duration: Duration::from_millis(50),
+                rolled_back: Rc::new(Cell::new(false)),
+                fail_rollback: true,
+            }))
+            .create_dir("./total_timeout_rollback_failure");
+
+        let err = tr.execute().unwrap_err();
+        assert_eq!(ErrorKind::TimedOut, err.kind());
+        assert!(err.to_string().contains("1 operations pending"));
+        assert!(err.to_string().contains("Sleep cannot be rollbacked"));
+    }
+
+    const GROUP_CONFIG: &str = "./execute_groups_config.txt";
+    const GROUP_DATA: &str = "./execute_groups_data.txt";
+    const GROUP_MISSING: &str = "./execute_groups_missing";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn execute_groups_works() {
+        let temp_dir = "./tmp/execute_groups";
+        fs::write(GROUP_CONFIG, b"Yellow World").unwrap();
+
+        let mut tr = Transaction::new().write_file(GROUP_CONFIG, temp_dir, b"Hello".to_vec());
+        tr.group();
+        let mut tr = tr.create_file(GROUP_DATA).assert_empty_dir(GROUP_MISSING);
+
+        assert!(tr.execute_groups().is_err());
+
+        // The first group stays applied with its backup disposed off, the second was rollbacked
+        assert!(fs::read(GROUP_CONFIG).unwrap().starts_with(b"Hello"));
+        assert_eq!(0, fs::read_dir(temp_dir).unwrap().count());
+        assert!(!Path::new(GROUP_DATA).exists());
+        assert_eq!(1, tr.change_report().changes.len());
+
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(fs::read(GROUP_CONFIG).unwrap().starts_with(b"Hello"));
+
+        // Resuming only runs the failed group again
+        fs::create_dir(GROUP_MISSING).unwrap();
+        assert_eq!((), tr.execute_groups().unwrap());
+        assert!(Path::new(GROUP_DATA).exists());
+        assert_eq!((), tr.commit().unwrap());
+
+        fs::remove_file(GROUP_CONFIG);
+        fs::remove_file(GROUP_DATA);
+        fs::remove_dir(GROUP_MISSING);
+        fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn execute_groups_rollback_failure_works() {
+        const MISSING: &str = "./execute_groups_rollback_failure";
+
+        let rolled_back = Rc::new(Cell::new(false));
+        let mut tr = Transaction::new()
+            .add_operation(Box::new(Sleep {
+                duration: Duration::from_millis(0),
+                rolled_back: rolled_back.clone(),
+                fail_rollback: true,
+            }))
+            .assert_empty_dir(MISSING);
+
+        let err = tr.execute_groups().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(rolled_back.get());
+        assert!(err.to_string().contains("Sleep cannot be rollbacked"));
+
+        // The failed group was reset, so there is nothing left to rollback
+        rolled_back.set(false);
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!rolled_back.get());
+    }
+
+    struct ReserveSpace(u64);
+
+    impl RollbackableOperation for ReserveSpace {
+        fn execute(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn rollback(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn space_required(&self) -> Vec<(PathBuf, u64)> {
+            vec![("./tmp/check_free_space".into(), self.0)]
+        }
+    }
+
+    #[test]
+    fn check_free_space_works() {
+        let tr = Transaction::new()
+            .write_file("./check_free_space.txt", "./tmp", b"Hello".to_vec())
+            .add_operation(Box::new(ReserveSpace(1024)));
+        assert_eq!((), tr.check_free_space().unwrap());
+
+        let tr = tr
+            .add_operation(Box::new(ReserveSpace(u64::MAX / 2)))
+            .add_operation(Box::new(ReserveSpace(u64::MAX / 2)));
+        let err = tr.check_free_space().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("insufficient space"));
+    }
+
+    /// Runs against a small tmpfs mounted at the path in `TFIO_TINY_TMPFS`, e.g. `mount -t tmpfs -o size=1m tmpfs /mnt/tiny`
+    #[test]
+    fn check_free_space_tiny_tmpfs() {
+        let mount = match std::env::var("TFIO_TINY_TMPFS") {
+            Ok(mount) => PathBuf::from(mount),
+            Err(_) => return,
+        };
+        let available = fs2::available_space(&mount).unwrap() as usize;
+
+        let tr = Transaction::new().write_file(
+            mount.join("fits.bin"),
+            mount.join("tmp"),
+            vec![0; available / 2],
+        );
+        assert_eq!((), tr.check_free_space().unwrap());
+
+        let tr = Transaction::new().write_file(
+            mount.join("too_large.bin"),
+            mount.join("tmp"),
+            vec![0; available + 1],
+        );
+        assert!(tr
+            .check_free_space()
+            .unwrap_err()
+            .to_string()
+            .contains("1 bytes short"));
+    }
+
+    #[test]
+    fn canonicalize_paths_works() {
+        let mut tr = Transaction::new()
+            .create_file("./a/../b/c.txt")
+            .delete_file("/var/./log/../app.log", "./tmp/");
+
+        assert_eq!((), tr.canonicalize_paths().unwrap());
+
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(vec![&mut cwd.join("b/c.txt")], tr.ops[0].paths_mut());
+        assert_eq!(
+            vec![&mut PathBuf::from("/var/app.log"), &mut cwd.join("tmp")],
+            tr.ops[1].paths_mut()
+        );
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn try_clone_works() {
+        fs::create_dir_all("./try_clone_first").unwrap();
+        fs::create_dir_all("./try_clone_second").unwrap();
+
+        let mut first = Transaction::new()
+            .create_file("./try_clone_first/created.txt")
+            .add_tagged(
+                "data",
+                Box::new(WriteFile::new(
+                    "./try_clone_first/created.txt",
+                    "./tmp",
+                    b"Hello".to_vec(),
+                )),
+            );
+        let mut second = first.try_clone().unwrap();
+
+        for path in second.paths_mut() {
+            if let Ok(rest) = path.strip_prefix("./try_clone_first") {
+                *path = Path::new("./try_clone_second").join(rest);
+            }
+        }
+
+        assert_eq!((), first.execute().unwrap());
+        assert_eq!((), second.execute().unwrap());
+        assert_eq!(
+            b"Hello".to_vec(),
+            fs::read("./try_clone_first/created.txt").unwrap()
+        );
+        assert_eq!(
+            b"Hello".to_vec(),
+            fs::read("./try_clone_second/created.txt").unwrap()
+        );
+        assert_eq!(5, second.metrics_by_tag()["data"].bytes_written);
+
+        assert_eq!((), first.rollback().unwrap());
+        assert!(!Path::new("./try_clone_first/created.txt").exists());
+        assert!(Path::new("./try_clone_second/created.txt").exists());
+
+        let unclonable = Transaction::new().add_operation(Box::new(ReserveSpace(0)));
+        assert_eq!(
+            Some(ErrorKind::Unsupported),
+            unclonable.try_clone().err().map(|e| e.kind())
+        );
+
+        fs::remove_dir_all("./try_clone_first");
+        fs::remove_dir_all("./try_clone_second");
+    }
+
+    const STEP_FILE: &str = "./step_transaction/file.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn step_works() {
+        let mut tr = Transaction::new()
+            .create_dir("./step_transaction")
+            .create_file(STEP_FILE)
+            .write_file(STEP_FILE, "./tmp", b"Hello".to_vec());
+
+        assert_eq!(Some(0), tr.step().unwrap());
+        assert!(Path::new("./step_transaction").exists());
+        assert!(!Path::new(STEP_FILE).exists());
+
+        assert_eq!(Some(1), tr.step().unwrap());
+        assert_eq!(Some(0), file_size(STEP_FILE));
+
+        assert_eq!(Some(2), tr.step().unwrap());
+        assert_eq!(b"Hello".to_vec(), fs::read(STEP_FILE).unwrap());
+
+        assert_eq!(None, tr.step().unwrap());
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!(3, tr.durations().len());
+
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new("./step_transaction").exists());
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "hash"))]
+    #[allow(unused_must_use)]
+    fn dedup_backups_works() {
+        use std::os::unix::fs::MetadataExt;
+
+        let files = [
+            "./dedup_backups_a.toml",
+            "./dedup_backups_b.toml",
+            "./dedup_backups_c.toml",
+        ];
+        for file in &files {
+            fs::write(file, "template = true").unwrap();
+        }
+
+        let mut tr = Transaction::scoped().with_dedup_backups(true);
+        for file in &files {
+            tr = tr.write_file(*file, "./tmp", b"changed".to_vec());
+        }
+        let store = tr.temp_dir().unwrap().join("blobs");
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!(1, fs::read_dir(&store).unwrap().count());
+
+        let inodes: Vec<u64> = tr
+            .ops
+            .iter()
+            .map(|op| fs::metadata(op.backup_path().unwrap()).unwrap().ino())
+            .collect();
+        assert!(inodes.iter().all(|ino| *ino == inodes[0]));
+
+        assert_eq!((), tr.rollback().unwrap());
+        for file in &files {
+            assert_eq!("template = true", fs::read_to_string(file).unwrap());
+            fs::remove_file(file);
+        }
+        assert!(!store.exists());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn require_free_space_aborts_transaction() {
+        const SOURCE: &str = "./require_free_space_transaction.txt";
+
+        let mut tr = Transaction::new()
+            .create_file(SOURCE)
+            .require_free_space(SOURCE, u64::MAX)
+            .write_file(SOURCE, "./tmp", b"Hello".to_vec());
+
+        assert_eq!(ErrorKind::Other, tr.execute().unwrap_err().kind());
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new(SOURCE).exists());
+    }
+
+    #[test]
+    fn describe_works() {
+        let tr = Transaction::new()
+            .create_file("./describe.txt")
+            .add_described("notify the ops channel", Box::new(ReserveSpace(0)));
+
+        assert_eq!("0: CreateFile\n1: notify the ops channel\n", tr.describe());
+    }
+
+    #[test]
+    fn plan_json_works() {
+        let tr = Transaction::new()
+            .create_file("./plan.txt")
+            .write_file("./plan.txt", "./tmp", b"Hello \"World\"".to_vec())
+            .move_file("./plan.txt", "./plan_moved.txt")
+            .add_operation(Box::new(ReserveSpace(0)));
+
+        let json = tr.plan_json().unwrap();
+
+        assert_eq!(4, json.matches("\"type\":").count());
+        assert!(
+            json.starts_with("[{\"type\":\"CreateFile\",\"params\":{\"path\":\"./plan.txt\"}},")
+        );
+        assert!(json.contains(
+            "{\"type\":\"WriteFile\",\"params\":{\"source\":\"./plan.txt\",\"temp_dir\":\"./tmp\",\"data\":{\"bytes\":13}}}"
+        ));
+        assert!(json.contains("{\"type\":\"MoveOperation\","));
+        assert!(json.ends_with("{\"type\":\"ReserveSpace\",\"params\":{}}]"));
+        assert!(!json.contains("Hello"));
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn inverse_works() {
+        const SOURCE: &str = "./inverse_source.txt";
+        const DEST: &str = "./inverse_dest.txt";
+        const CREATED: &str = "./inverse_created";
+
+        fs::write(SOURCE, "Hello World").unwrap();
+
+        let mut tr = Transaction::new()
+            .move_file(SOURCE, DEST)
+            .create_dir(CREATED);
+        let mut inverse = tr.inverse("./tmp/").unwrap();
+
+        assert_eq!("0: DeleteDirectory\n1: MoveOperation\n", inverse.describe());
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!((), tr.commit().unwrap());
+        assert!(Path::new(DEST).exists());
+        assert!(Path::new(CREATED).exists());
+
+        assert_eq!((), inverse.execute().unwrap());
+        assert!(inverse.ops[0].backup_path().unwrap().starts_with("./tmp/"));
+        assert_eq!((), inverse.commit().unwrap());
+        assert_eq!("Hello World", fs::read_to_string(SOURCE).unwrap());
+        assert!(!Path::new(DEST).exists());
+        assert!(!Path::new(CREATED).exists());
+
+        let tr = Transaction::new().delete_file(SOURCE, "./tmp/");
+        assert_eq!(
+            ErrorKind::Unsupported,
+            tr.inverse("./tmp/").err().unwrap().kind()
+        );
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn event_sender_works() {
+        use std::sync::mpsc;
+
+        const SOURCE: &str = "./event_sender.txt";
+        let (sender, receiver) = mpsc::channel();
+
+        let mut tr = Transaction::new()
+            .with_event_sender(sender)
+            .create_file(SOURCE)
+            .write_file(SOURCE, "./tmp", b"Hello".to_vec())
+            .require_free_space(SOURCE, u64::MAX);
+
+        assert_eq!(ErrorKind::Other, tr.execute().unwrap_err().kind());
+        assert_eq!((), tr.rollback().unwrap());
+
+        let events: Vec<TransactionEvent> = receiver.try_iter().collect();
+        assert!(matches!(events[0], TransactionEvent::Started));
+        assert!(matches!(events[1], TransactionEvent::OpSucceeded(0)));
+        assert!(matches!(events[2], TransactionEvent::OpSucceeded(1)));
+        assert!(
+            matches!(&events[3], TransactionEvent::OpFailed(2, e) if e.kind() == ErrorKind::Other)
+        );
+        assert!(matches!(events[4], TransactionEvent::RolledBack(2)));
+        assert!(matches!(events[5], TransactionEvent::RolledBack(1)));
+        assert!(matches!(events[6], TransactionEvent::RolledBack(0)));
+        assert_eq!(7, events.len());
+
+        let (sender, receiver) = mpsc::channel();
+        drop(receiver);
+
+        let mut tr = Transaction::new()
+            .with_event_sender(sender)
+            .create_file(SOURCE);
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!((), tr.commit().unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn sandbox_works() {
+        const ROOT: &str = "./sandbox_root";
+        fs::create_dir_all(ROOT).unwrap();
+
+        let mut tr = Transaction::new()
+            .create_dir("/data")
+            .with_sandbox(ROOT)
+            .create_file("/data/./created.txt")
+            .write_file("data/../data/created.txt", "/tmp", b"Hello".to_vec());
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!(
+            "Hello",
+            fs::read_to_string(Path::new(ROOT).join("data/created.txt")).unwrap()
+        );
+        assert!(Path::new(ROOT).join("tmp").is_dir());
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new(ROOT).join("data").exists());
+
+        let mut tr = Transaction::new()
+            .with_sandbox(ROOT)
+            .create_dir("/escape")
+            .create_file("/escape/../../sandbox_escaped.txt");
+
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            tr.execute().unwrap_err().kind()
+        );
+        assert!(!Path::new("./sandbox_escaped.txt").exists());
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new(ROOT).join("escape").exists());
+
+        fs::remove_dir_all(ROOT);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn backup_paths_works() {
+        const WRITTEN: &str = "./backup_paths_written.txt";
+        const DELETED: &str = "./backup_paths_deleted.txt";
+
+        fs::write(WRITTEN, "Hello").unwrap();
+        fs::write(DELETED, "World").unwrap();
+
+        let mut tr = Transaction::new()
+            .write_file(WRITTEN, "./tmp", b"Hi".to_vec())
+            .create_file("./backup_paths_created.txt")
+            .delete_file(DELETED, "./tmp");
+        assert!(tr.backup_paths().is_empty());
+
+        assert_eq!((), tr.execute().unwrap());
+        let backups = tr.backup_paths();
+        assert_eq!(2, backups.len());
+        assert_eq!("Hello", fs::read_to_string(&backups[0]).unwrap());
+        assert_eq!("World", fs::read_to_string(&backups[1]).unwrap());
+
+        assert_eq!((), tr.rollback().unwrap());
+        fs::remove_file(WRITTEN);
+        fs::remove_file(DELETED);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn drop_policy_rollbacks_in_reverse() {
+        const SOURCE: &str = "./drop_policy_reverse.txt";
+
+        fs::write(SOURCE, "Hello").unwrap();
+
+        let mut first = WriteFile::new(SOURCE, "./tmp", b"Hello World".to_vec());
+        first.set_drop_policy(DropPolicy::RollbackIfNotCommitted);
+        let mut second = WriteFile::new(SOURCE, "./tmp", b"Goodbye World".to_vec());
+        second.set_drop_policy(DropPolicy::RollbackIfNotCommitted);
+
+        let mut tr = Transaction::new()
+            .add_operation(Box::new(first))
+            .add_operation(Box::new(second));
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!("Goodbye World", fs::read_to_string(SOURCE).unwrap());
+
+        drop(tr);
+        assert_eq!("Hello", fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
     }
 }
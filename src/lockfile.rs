@@ -0,0 +1,109 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation, UndoStep};
+
+/// Writes a lockfile, failing if it already exists
+///
+/// The file is created exclusively, so only one transaction can hold the lock. It is removed on rollback and
+/// left in place once the transaction commits, releasing the lock is up to the caller
+pub struct WriteLockfile {
+    path: PathBuf,
+    content: Vec<u8>,
+    created: bool,
+}
+
+impl WriteLockfile {
+    /// Constructs a new WriteLockfile operation
+    pub fn new<S: AsRef<Path>>(path: S, content: Vec<u8>) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            content,
+            created: false,
+        }
+    }
+}
+
+impl RollbackableOperation for WriteLockfile {
+    fn execute(&mut self) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.path)?;
+        self.created = true;
+
+        file.write_all(&self.content)?;
+        file.sync_all()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        // Never remove a lock held by someone else
+        if !self.created {
+            return Ok(());
+        }
+
+        fs::remove_file(&self.path)
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Created, &self.path)
+            .with_sizes(None, Some(self.content.len() as u64))]
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.content.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        match self.created {
+            true => Some(vec![UndoStep::RemoveFile(self.path.clone())]),
+            false => Some(vec![]),
+        }
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(WriteLockfile::new(
+            &self.path,
+            self.content.clone(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    const LOCKFILE: &str = "./write_lockfile.lock";
+    const HELD_LOCKFILE: &str = "./write_lockfile_held.lock";
+
+    #[test]
+    fn write_lockfile_works() {
+        let mut op = WriteLockfile::new(LOCKFILE, b"1234".to_vec());
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("1234", fs::read_to_string(LOCKFILE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(LOCKFILE).exists());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_lockfile_already_locked() {
+        fs::write(HELD_LOCKFILE, "42").unwrap();
+
+        let mut op = WriteLockfile::new(HELD_LOCKFILE, b"1234".to_vec());
+        assert_eq!(ErrorKind::AlreadyExists, op.execute().unwrap_err().kind());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("42", fs::read_to_string(HELD_LOCKFILE).unwrap());
+
+        fs::remove_file(HELD_LOCKFILE);
+    }
+}
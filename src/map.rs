@@ -0,0 +1,111 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+
+/// Replaces every line of a text file with the result of a function
+///
+/// The function is given each line without its line ending. Line endings, including `\r\n`, and whether the file ends
+/// with a newline are preserved. Fails with `ErrorKind::InvalidData` if the file is not valid UTF-8
+pub struct MapLines {
+    file: FileBackup,
+    f: Box<dyn FnMut(&str) -> String>,
+}
+
+impl MapLines {
+    /// Constructs a new MapLines operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        f: Box<dyn FnMut(&str) -> String>,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            f,
+        }
+    }
+
+    fn map(&mut self, contents: &str) -> String {
+        if contents.is_empty() {
+            return String::new();
+        }
+
+        let body = contents.strip_suffix('\n').unwrap_or(contents);
+        let mut mapped = String::with_capacity(contents.len());
+
+        for (i, line) in body.split('\n').enumerate() {
+            if i > 0 {
+                mapped.push('\n');
+            }
+
+            match line.strip_suffix('\r') {
+                Some(text) => {
+                    mapped.push_str(&(self.f)(text));
+                    mapped.push('\r');
+                }
+                None => mapped.push_str(&(self.f)(line)),
+            }
+        }
+
+        if body.len() < contents.len() {
+            mapped.push('\n');
+        }
+
+        mapped
+    }
+}
+
+impl Rewrite for MapLines {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let contents =
+            String::from_utf8(contents).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(Some(self.map(&contents).into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RollbackableOperation;
+    use std::fs;
+
+    const SOURCE: &str = "./map_lines.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &str = "alpha\r\nbeta\r\ngamma\r\n";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn map_lines_works() {
+        fs::write(SOURCE, DATA).unwrap();
+
+        let mut n = 0;
+        let mut op = MapLines::new(
+            SOURCE,
+            TEMP_DIR,
+            Box::new(move |line| {
+                n += 1;
+                format!("{}: {}", n, line)
+            }),
+        );
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "1: alpha\r\n2: beta\r\n3: gamma\r\n",
+            fs::read_to_string(SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+}
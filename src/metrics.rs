@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+/// Aggregated metrics of the executed operations sharing a tag
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagMetrics {
+    /// Number of executed operations
+    pub ops: usize,
+    /// Total number of bytes written
+    pub bytes_written: u64,
+    /// Total time spent executing
+    pub duration: Duration,
+}
@@ -1,10 +1,14 @@
 use std::fs;
 use std::{
-    io,
+    io::{self, Error, ErrorKind},
     path::{Path, PathBuf},
 };
 
-use crate::RollbackableOperation;
+use crate::backup::Backup;
+use crate::{
+    file_size, non_empty_path, Change, ChangeKind, RollbackableOperation, SingleFileOperation,
+    SpecValue, UndoStep, DEFAULT_BACKUP_BUFFER_SIZE,
+};
 
 /// Moves a file from source to destination. A type alias for [MoveOperation](MoveOperation) for consistency in the API
 pub type MoveFile = MoveOperation;
@@ -40,6 +44,812 @@ impl RollbackableOperation for MoveOperation {
     fn rollback(&self) -> io::Result<()> {
         fs::rename(&self.dest, &self.source)
     }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Moved, &self.source).with_dest(&self.dest)]
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(vec![UndoStep::Rename {
+            from: self.dest.clone(),
+            to: self.source.clone(),
+        }])
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.dest]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("dest", SpecValue::Path(self.dest.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MoveOperation::new(&self.source, &self.dest)))
+    }
+
+    fn inverse(&self, _temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MoveOperation::new(&self.dest, &self.source)))
+    }
+}
+
+/// Moves a file into a directory, keeping its filename
+///
+/// Fails if the destination already exists, unless a backup dir is set with [with_backup](#method.with_backup) in which case the existing destination is backed up and overwritten
+pub struct MoveIntoDir {
+    source: PathBuf,
+    dest: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
+    backup: bool,
+    backed_up: bool,
+    moved: bool,
+}
+
+impl MoveIntoDir {
+    /// Constructs a new MoveIntoDir operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, dest_dir: T) -> Self {
+        let source: PathBuf = source.as_ref().into();
+        let dest = match source.file_name() {
+            Some(filename) => dest_dir.as_ref().join(filename),
+            None => PathBuf::new(),
+        };
+
+        Self {
+            source,
+            dest,
+            temp_dir: PathBuf::new(),
+            backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            backup: false,
+            backed_up: false,
+            moved: false,
+        }
+    }
+
+    /// Backs up and overwrites an existing destination instead of failing
+    pub fn with_backup<S: AsRef<Path>>(mut self, temp_dir: S) -> Self {
+        self.temp_dir = temp_dir.as_ref().into();
+        self.backup = true;
+        self
+    }
+
+    /// Returns the computed destination path
+    pub fn get_dest(&self) -> &Path {
+        &self.dest
+    }
+}
+
+impl RollbackableOperation for MoveIntoDir {
+    fn execute(&mut self) -> io::Result<()> {
+        if self.source.file_name().is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Could not extract filename from path",
+            ));
+        }
+
+        if self.dest.exists() {
+            if !self.backup {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "Destination already exists",
+                ));
+            }
+
+            self.create_backup_file()?;
+            self.backed_up = true;
+        }
+
+        fs::rename(&self.source, &self.dest)?;
+        self.moved = true;
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        // An existing destination must not be moved over the source if execution failed
+        if !self.moved {
+            return Ok(());
+        }
+
+        fs::rename(&self.dest, &self.source)?;
+
+        if self.backed_up {
+            fs::copy(self.get_backup_path(), &self.dest)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        if self.backup {
+            self.temp_dir = temp_dir.into();
+        }
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        !self.backed_up || self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Moved, &self.source).with_dest(&self.dest)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if !self.moved {
+            return Some(vec![]);
+        }
+
+        let mut steps = vec![UndoStep::Rename {
+            from: self.dest.clone(),
+            to: self.source.clone(),
+        }];
+
+        if self.backed_up {
+            steps.push(UndoStep::RestoreFile {
+                backup: self.backup_path.clone(),
+                path: self.dest.clone(),
+            });
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.dest, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MoveIntoDir {
+            source: self.source.clone(),
+            dest: self.dest.clone(),
+            temp_dir: self.temp_dir.clone(),
+            backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: self.backup_buffer_size,
+            backup: self.backup,
+            backed_up: false,
+            moved: false,
+        }))
+    }
+
+    fn inverse(&self, _temp_dir: &Path) -> Option<Box<dyn RollbackableOperation>> {
+        // An overwritten destination can only be brought back from its backup
+        match self.backup {
+            true => None,
+            false => Some(Box::new(MoveOperation::new(&self.dest, &self.source))),
+        }
+    }
+}
+
+impl SingleFileOperation for MoveIntoDir {
+    fn get_path(&self) -> &Path {
+        &self.dest
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
+}
+
+impl Drop for MoveIntoDir {
+    fn drop(&mut self) {
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Moves many files, each from its source to its destination
+///
+/// Existing destinations are backed up into the temp dir and overwritten. If a move fails, rollback only reverses the moves that succeeded
+pub struct MoveMany {
+    pairs: Vec<(PathBuf, PathBuf)>,
+    temp_dir: PathBuf,
+    backups: Vec<Option<Backup>>,
+}
+
+impl MoveMany {
+    /// Constructs a new MoveMany operation
+    pub fn new<S: AsRef<Path>>(pairs: Vec<(PathBuf, PathBuf)>, temp_dir: S) -> Self {
+        Self {
+            pairs,
+            temp_dir: temp_dir.as_ref().into(),
+            backups: vec![],
+        }
+    }
+
+    /// Returns the number of files moved so far
+    pub fn moved(&self) -> usize {
+        self.backups.len()
+    }
+
+    fn move_one(&self, source: &Path, dest: &Path) -> io::Result<Option<Backup>> {
+        let backup = match dest.exists() {
+            true => Some(Backup::of_file(dest, &self.temp_dir)?),
+            false => None,
+        };
+
+        if let Err(e) = fs::rename(source, dest) {
+            if let Some(backup) = backup {
+                backup.dispose()?;
+            }
+            return Err(e);
+        }
+
+        Ok(backup)
+    }
+}
+
+impl RollbackableOperation for MoveMany {
+    fn execute(&mut self) -> io::Result<()> {
+        for i in self.moved()..self.pairs.len() {
+            let (source, dest) = &self.pairs[i];
+            let backup = self.move_one(source, dest)?;
+            self.backups.push(backup);
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for ((source, dest), backup) in self.pairs.iter().zip(&self.backups).rev() {
+            fs::rename(dest, source)?;
+
+            if let Some(backup) = backup {
+                backup.restore(dest)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backups.iter().flatten().all(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backups.iter().flatten().map(Backup::path).collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.pairs[..self.moved()]
+            .iter()
+            .map(|(source, dest)| Change::new(ChangeKind::Moved, source).with_dest(dest))
+            .collect()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.backups
+            .iter()
+            .flatten()
+            .filter_map(|backup| file_size(backup.path()))
+            .sum()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        for ((source, dest), backup) in self.pairs.iter().zip(&self.backups).rev() {
+            steps.push(UndoStep::Rename {
+                from: dest.clone(),
+                to: source.clone(),
+            });
+
+            if let Some(backup) = backup {
+                steps.push(UndoStep::RestoreFile {
+                    backup: backup.path().into(),
+                    path: dest.clone(),
+                });
+            }
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.temp_dir];
+        for (source, dest) in self.pairs.iter_mut() {
+            paths.push(source);
+            paths.push(dest);
+        }
+        paths
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MoveMany::new(self.pairs.clone(), &self.temp_dir)))
+    }
+}
+
+impl Drop for MoveMany {
+    fn drop(&mut self) {
+        for backup in self.backups.iter().flatten() {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
+        }
+    }
+}
+
+/// Moves a file and rewrites references to it in other files
+///
+/// Every occurrence of the source path, exactly as given, is replaced with the destination path in each of the files.
+/// Files are backed up into the temp dir before they are rewritten, files without an occurrence are left untouched.
+/// Fails with `ErrorKind::AlreadyExists` if the destination exists and with `ErrorKind::InvalidInput` if a path is not valid UTF-8
+pub struct MoveAndRewrite {
+    source: PathBuf,
+    dest: PathBuf,
+    files: Vec<PathBuf>,
+    temp_dir: PathBuf,
+    old_text: Option<String>,
+    new_text: Option<String>,
+    moved: bool,
+    backups: Vec<(PathBuf, Backup)>,
+}
+
+impl MoveAndRewrite {
+    /// Constructs a new MoveAndRewrite operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        source: S,
+        dest: T,
+        files: Vec<PathBuf>,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            dest: dest.as_ref().into(),
+            files,
+            temp_dir: temp_dir.as_ref().into(),
+            old_text: source.as_ref().to_str().map(String::from),
+            new_text: dest.as_ref().to_str().map(String::from),
+            moved: false,
+            backups: vec![],
+        }
+    }
+
+    /// Returns the files that were rewritten
+    pub fn rewritten(&self) -> Vec<&Path> {
+        self.backups
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+}
+
+fn replace_bytes(haystack: &[u8], from: &[u8], to: &[u8]) -> Option<Vec<u8>> {
+    let mut replaced = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    let mut found = false;
+
+    while let Some(i) = rest.windows(from.len()).position(|window| window == from) {
+        replaced.extend_from_slice(&rest[..i]);
+        replaced.extend_from_slice(to);
+        rest = &rest[i + from.len()..];
+        found = true;
+    }
+
+    replaced.extend_from_slice(rest);
+
+    match found {
+        true => Some(replaced),
+        false => None,
+    }
+}
+
+impl RollbackableOperation for MoveAndRewrite {
+    fn execute(&mut self) -> io::Result<()> {
+        let (old_text, new_text) = match (&self.old_text, &self.new_text) {
+            (Some(old_text), Some(new_text)) if !old_text.is_empty() => {
+                (old_text.clone(), new_text.clone())
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Source and destination must be non-empty UTF-8 paths",
+                ))
+            }
+        };
+
+        if self.dest.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", self.dest.display()),
+            ));
+        }
+
+        fs::rename(&self.source, &self.dest)?;
+        self.moved = true;
+
+        for path in self.files.clone() {
+            let contents = fs::read(&path)?;
+
+            if let Some(rewritten) =
+                replace_bytes(&contents, old_text.as_bytes(), new_text.as_bytes())
+            {
+                let backup = Backup::of_file(&path, &self.temp_dir)?;
+                self.backups.push((path.clone(), backup));
+                fs::write(&path, rewritten)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (path, backup) in self.backups.iter().rev() {
+            backup.restore(path)?;
+        }
+
+        if self.moved {
+            fs::rename(&self.dest, &self.source)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.backups.iter().all(|(_, backup)| backup.exists())
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.backups
+            .iter()
+            .map(|(_, backup)| backup.path())
+            .collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let mut changes = vec![Change::new(ChangeKind::Moved, &self.source).with_dest(&self.dest)];
+
+        for (path, backup) in &self.backups {
+            changes.push(
+                Change::new(ChangeKind::Modified, path)
+                    .with_sizes(file_size(backup.path()), file_size(path)),
+            );
+        }
+
+        changes
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.backups
+            .iter()
+            .map(|(path, backup)| {
+                file_size(backup.path()).unwrap_or(0) + file_size(path).unwrap_or(0)
+            })
+            .sum()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps: Vec<UndoStep> = self
+            .backups
+            .iter()
+            .rev()
+            .map(|(path, backup)| UndoStep::RestoreFile {
+                backup: backup.path().into(),
+                path: path.clone(),
+            })
+            .collect();
+
+        if self.moved {
+            steps.push(UndoStep::Rename {
+                from: self.dest.clone(),
+                to: self.source.clone(),
+            });
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.dest, &mut self.temp_dir];
+        paths.extend(self.files.iter_mut());
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("dest", SpecValue::Path(self.dest.clone())),
+            ("files", SpecValue::Number(self.files.len() as u64)),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op =
+            MoveAndRewrite::new(&self.source, &self.dest, self.files.clone(), &self.temp_dir);
+        op.old_text = self.old_text.clone();
+        op.new_text = self.new_text.clone();
+
+        Some(Box::new(op))
+    }
+}
+
+impl Drop for MoveAndRewrite {
+    fn drop(&mut self) {
+        for (_, backup) in &self.backups {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
+        }
+    }
+}
+
+/// Moves every entry of a directory up into its parent and removes the then empty directory
+///
+/// The directory is first renamed to a hidden name, so an entry named like the directory itself can be moved too. Existing
+/// entries of the parent with the same name as a moved entry are backed up into the temp dir and replaced. Rollback moves the
+/// entries back into a recreated directory with the original permissions and restores the replaced entries
+pub struct FlattenDir {
+    subdir: PathBuf,
+    temp_dir: PathBuf,
+    staging: PathBuf,
+    permissions: Option<fs::Permissions>,
+    moved: Vec<(PathBuf, Option<Backup>)>,
+    removed: bool,
+}
+
+impl FlattenDir {
+    /// Constructs a new FlattenDir operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(subdir: S, temp_dir: T) -> Self {
+        Self {
+            subdir: subdir.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            staging: PathBuf::new(),
+            permissions: None,
+            moved: vec![],
+            removed: false,
+        }
+    }
+
+    /// Returns the paths the entries were moved to
+    pub fn moved(&self) -> Vec<&Path> {
+        self.moved.iter().map(|(dest, _)| dest.as_path()).collect()
+    }
+
+    fn parent(&self) -> &Path {
+        match self.subdir.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    }
+
+    fn move_one(&self, source: &Path, dest: &Path) -> io::Result<Option<Backup>> {
+        let backup = match fs::symlink_metadata(dest) {
+            Ok(metadata) if metadata.is_dir() => Some(Backup::of_dir(dest, &self.temp_dir)?),
+            Ok(_) => Some(Backup::of_file(dest, &self.temp_dir)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let result = match &backup {
+            Some(backup) if backup.is_dir() => fs::remove_dir_all(dest),
+            Some(_) => fs::remove_file(dest),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::rename(source, dest));
+
+        if let Err(e) = result {
+            if let Some(backup) = backup {
+                backup.restore(dest)?;
+                backup.dispose()?;
+            }
+            return Err(e);
+        }
+
+        Ok(backup)
+    }
+}
+
+impl RollbackableOperation for FlattenDir {
+    fn execute(&mut self) -> io::Result<()> {
+        if !self.subdir.is_dir() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a directory", self.subdir.display()),
+            ));
+        }
+
+        let parent = self.parent().to_path_buf();
+        self.permissions = Some(fs::metadata(&self.subdir)?.permissions());
+
+        let staging = parent.join(format!(".{}.flatten", uuid::Uuid::new_v4().to_simple()));
+        fs::rename(&self.subdir, &staging)?;
+        self.staging = staging;
+
+        let mut names = fs::read_dir(&self.staging)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        names.sort();
+
+        for name in names {
+            let dest = parent.join(&name);
+            let backup = self.move_one(&self.staging.join(&name), &dest)?;
+            self.moved.push((dest, backup));
+        }
+
+        fs::remove_dir(&self.staging)?;
+        self.removed = true;
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.staging.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        if self.removed {
+            fs::create_dir(&self.staging)?;
+
+            if let Some(permissions) = &self.permissions {
+                fs::set_permissions(&self.staging, permissions.clone())?;
+            }
+        }
+
+        for (dest, backup) in self.moved.iter().rev() {
+            if let Some(name) = dest.file_name() {
+                fs::rename(dest, self.staging.join(name))?;
+            }
+
+            if let Some(backup) = backup {
+                backup.restore(dest)?;
+            }
+        }
+
+        fs::rename(&self.staging, &self.subdir)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.moved
+            .iter()
+            .filter_map(|(_, backup)| backup.as_ref())
+            .all(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.moved
+            .iter()
+            .filter_map(|(_, backup)| backup.as_ref())
+            .map(Backup::path)
+            .collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let mut changes: Vec<Change> = self
+            .moved
+            .iter()
+            .filter_map(|(dest, _)| {
+                let source = self.subdir.join(dest.file_name()?);
+                Some(Change::new(ChangeKind::Moved, source).with_dest(dest))
+            })
+            .collect();
+
+        if self.removed {
+            changes.push(Change::new(ChangeKind::Deleted, &self.subdir));
+        }
+
+        changes
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.moved
+            .iter()
+            .filter_map(|(_, backup)| backup.as_ref())
+            .filter_map(|backup| file_size(backup.path()))
+            .sum()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if self.staging.as_os_str().is_empty() {
+            return Some(vec![]);
+        }
+
+        let mut steps = vec![];
+
+        if self.removed {
+            steps.push(UndoStep::CreateDir(self.staging.clone()));
+        }
+
+        for (dest, backup) in self.moved.iter().rev() {
+            steps.push(UndoStep::Rename {
+                from: dest.clone(),
+                to: self.staging.join(dest.file_name()?),
+            });
+
+            match backup {
+                Some(backup) if backup.is_dir() => steps.push(UndoStep::RestoreDir {
+                    backup: backup.path().into(),
+                    path: dest.clone(),
+                }),
+                Some(backup) => steps.push(UndoStep::RestoreFile {
+                    backup: backup.path().into(),
+                    path: dest.clone(),
+                }),
+                None => {}
+            }
+        }
+
+        steps.push(UndoStep::Rename {
+            from: self.staging.clone(),
+            to: self.subdir.clone(),
+        });
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.subdir, &mut self.temp_dir]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("subdir", SpecValue::Path(self.subdir.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(FlattenDir::new(&self.subdir, &self.temp_dir)))
+    }
+}
+
+impl Drop for FlattenDir {
+    fn drop(&mut self) {
+        for (_, backup) in &self.moved {
+            if let Some(Err(e)) = backup.as_ref().map(Backup::dispose) {
+                eprintln!("{}", e)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -65,16 +875,16 @@ mod tests {
 
         let mut op = MoveFile::new(FILE_SOURCE, FILE_DEST);
 
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
-        assert_eq!(false, Path::new(FILE_DEST).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
+        assert!(!Path::new(FILE_DEST).exists());
 
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(false, Path::new(FILE_SOURCE).exists());
-        assert_eq!(true, Path::new(FILE_DEST).exists());
+        assert!(!Path::new(FILE_SOURCE).exists());
+        assert!(Path::new(FILE_DEST).exists());
 
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(FILE_SOURCE).exists());
-        assert_eq!(false, Path::new(FILE_DEST).exists());
+        assert!(Path::new(FILE_SOURCE).exists());
+        assert!(!Path::new(FILE_DEST).exists());
 
         fs::remove_file(FILE_SOURCE);
         fs::remove_dir_all(FILE_DEST_DIR);
@@ -96,18 +906,224 @@ mod tests {
 
         let mut op = MoveDirectory::new(DIR_SOURCE, DIR_DEST);
 
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
-        assert_eq!(false, Path::new(DIR_DEST).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_DEST).exists());
 
         assert_eq!((), op.execute().unwrap());
-        assert_eq!(false, Path::new(DIR_SOURCE).exists());
-        assert_eq!(true, Path::new(DIR_DEST).exists());
+        assert!(!Path::new(DIR_SOURCE).exists());
+        assert!(Path::new(DIR_DEST).exists());
 
         assert_eq!((), op.rollback().unwrap());
-        assert_eq!(true, Path::new(DIR_SOURCE).exists());
-        assert_eq!(false, Path::new(DIR_DEST).exists());
+        assert!(Path::new(DIR_SOURCE).exists());
+        assert!(!Path::new(DIR_DEST).exists());
 
         fs::remove_dir_all(DIR_SOURCE);
         fs::remove_dir_all(DIR_DIR);
     }
+
+    const INTO_FILE_SOURCE: &str = "./move_into_dir_source.txt";
+    const INTO_DIR: &str = "./move_into_dir_dest";
+    const INTO_FILE_DEST: &str = "./move_into_dir_dest/move_into_dir_source.txt";
+    const INTO_TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn move_into_dir_works() {
+        fs::write(INTO_FILE_SOURCE, b"Hello World").unwrap();
+        fs::create_dir_all(INTO_DIR).unwrap();
+
+        let mut op = MoveIntoDir::new(INTO_FILE_SOURCE, INTO_DIR);
+        assert_eq!(Path::new(INTO_FILE_DEST), op.get_dest());
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!Path::new(INTO_FILE_SOURCE).exists());
+        assert!(Path::new(INTO_FILE_DEST).exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(Path::new(INTO_FILE_SOURCE).exists());
+        assert!(!Path::new(INTO_FILE_DEST).exists());
+
+        fs::write(INTO_FILE_DEST, b"Yellow World").unwrap();
+
+        let mut op = MoveIntoDir::new(INTO_FILE_SOURCE, INTO_DIR);
+        assert_eq!(ErrorKind::AlreadyExists, op.execute().unwrap_err().kind());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(Path::new(INTO_FILE_SOURCE).exists());
+
+        let mut op = MoveIntoDir::new(INTO_FILE_SOURCE, INTO_DIR).with_backup(INTO_TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(b"Hello World".to_vec(), fs::read(INTO_FILE_DEST).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(b"Hello World".to_vec(), fs::read(INTO_FILE_SOURCE).unwrap());
+        assert_eq!(b"Yellow World".to_vec(), fs::read(INTO_FILE_DEST).unwrap());
+
+        fs::remove_file(INTO_FILE_SOURCE);
+        fs::remove_dir_all(INTO_DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn move_many_works() {
+        const DIR: &str = "./move_many_dir";
+        let pairs: Vec<(PathBuf, PathBuf)> = (0..3)
+            .map(|i| {
+                (
+                    PathBuf::from(format!("./move_many_{}.txt", i)),
+                    Path::new(DIR).join(format!("{}.txt", i)),
+                )
+            })
+            .collect();
+
+        fs::create_dir_all(DIR).unwrap();
+        for (i, (source, _)) in pairs.iter().enumerate() {
+            fs::write(source, i.to_string()).unwrap();
+        }
+        fs::write(&pairs[1].1, "existing").unwrap();
+
+        let mut op = MoveMany::new(pairs.clone(), "./tmp/");
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(3, op.moved());
+        for (i, (source, dest)) in pairs.iter().enumerate() {
+            assert!(!source.exists());
+            assert_eq!(i.to_string(), fs::read_to_string(dest).unwrap());
+        }
+
+        assert_eq!((), op.rollback().unwrap());
+        for (i, (source, _)) in pairs.iter().enumerate() {
+            assert_eq!(i.to_string(), fs::read_to_string(source).unwrap());
+            fs::remove_file(source);
+        }
+        assert_eq!("existing", fs::read_to_string(&pairs[1].1).unwrap());
+        assert!(!pairs[0].1.exists());
+
+        fs::remove_dir_all(DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn move_many_partial_failure_works() {
+        const SOURCE: &str = "./move_many_partial.txt";
+        const DEST: &str = "./move_many_partial_dest.txt";
+
+        fs::write(SOURCE, "Hello").unwrap();
+
+        let mut op = MoveMany::new(
+            vec![
+                (SOURCE.into(), DEST.into()),
+                (
+                    "./move_many_partial_missing.txt".into(),
+                    "./move_many_partial_never.txt".into(),
+                ),
+            ],
+            "./tmp/",
+        );
+        assert_eq!(ErrorKind::NotFound, op.execute().unwrap_err().kind());
+        assert_eq!(1, op.moved());
+        assert_eq!(1, op.changes().len());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("Hello", fs::read_to_string(SOURCE).unwrap());
+        assert!(!Path::new(DEST).exists());
+
+        fs::remove_file(SOURCE);
+    }
+
+    const REWRITE_DIR: &str = "./move_and_rewrite";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn move_and_rewrite_works() {
+        let dir = Path::new(REWRITE_DIR);
+        let source = dir.join("old.css");
+        let dest = dir.join("new.css");
+        let index = dir.join("index.html");
+        let about = dir.join("about.html");
+        let unrelated = dir.join("readme.txt");
+
+        let link = |path: &Path| format!("<link href=\"{}\">", path.display());
+
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&source, "body {}").unwrap();
+        fs::write(&index, link(&source)).unwrap();
+        fs::write(&about, format!("{}\n{}", link(&source), link(&source))).unwrap();
+        fs::write(&unrelated, "Hello").unwrap();
+
+        let files = vec![index.clone(), about.clone(), unrelated.clone()];
+        let mut op = MoveAndRewrite::new(&source, &dest, files, "./tmp/");
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!source.exists());
+        assert_eq!("body {}", fs::read_to_string(&dest).unwrap());
+        assert_eq!(link(&dest), fs::read_to_string(&index).unwrap());
+        assert_eq!(
+            format!("{}\n{}", link(&dest), link(&dest)),
+            fs::read_to_string(&about).unwrap()
+        );
+        assert_eq!(vec![index.as_path(), about.as_path()], op.rewritten());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!dest.exists());
+        assert_eq!("body {}", fs::read_to_string(&source).unwrap());
+        assert_eq!(link(&source), fs::read_to_string(&index).unwrap());
+        assert_eq!(
+            format!("{}\n{}", link(&source), link(&source)),
+            fs::read_to_string(&about).unwrap()
+        );
+
+        fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn flatten_dir_works() {
+        const ROOT: &str = "./flatten_dir";
+        const TEMP_DIR: &str = "./tmp/";
+        let root = Path::new(ROOT);
+        let subdir = root.join("nested");
+
+        fs::create_dir_all(subdir.join("inner")).unwrap();
+        fs::write(subdir.join("a.txt"), "nested a").unwrap();
+        fs::write(subdir.join("inner/b.txt"), "nested b").unwrap();
+        fs::write(subdir.join("nested"), "same name").unwrap();
+        fs::write(root.join("a.txt"), "parent a").unwrap();
+        fs::create_dir_all(root.join("inner")).unwrap();
+        fs::write(root.join("inner/c.txt"), "parent c").unwrap();
+
+        let mut op = FlattenDir::new(&subdir, TEMP_DIR);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(3, op.moved().len());
+        assert_eq!("nested a", fs::read_to_string(root.join("a.txt")).unwrap());
+        assert_eq!(
+            "nested b",
+            fs::read_to_string(root.join("inner/b.txt")).unwrap()
+        );
+        assert!(!root.join("inner/c.txt").exists());
+        assert_eq!("same name", fs::read_to_string(&subdir).unwrap());
+        assert_eq!(3, fs::read_dir(ROOT).unwrap().count());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("parent a", fs::read_to_string(root.join("a.txt")).unwrap());
+        assert_eq!(
+            "parent c",
+            fs::read_to_string(root.join("inner/c.txt")).unwrap()
+        );
+        assert!(!root.join("inner/b.txt").exists());
+        assert_eq!(
+            "nested a",
+            fs::read_to_string(subdir.join("a.txt")).unwrap()
+        );
+        assert_eq!(
+            "nested b",
+            fs::read_to_string(subdir.join("inner/b.txt")).unwrap()
+        );
+        assert_eq!(
+            "same name",
+            fs::read_to_string(subdir.join("nested")).unwrap()
+        );
+        assert_eq!(3, fs::read_dir(ROOT).unwrap().count());
+
+        fs::remove_dir_all(ROOT);
+    }
 }
@@ -0,0 +1,264 @@
+use std::io;
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Returns `contents` ending with a newline, or `None` if they need no fix
+///
+/// The newline is `\r\n` if `contents` already contain one. With `single`, extra trailing newlines are removed as well
+fn ensure_final_newline(contents: &[u8], single: bool) -> Option<Vec<u8>> {
+    if contents.is_empty() {
+        return None;
+    }
+
+    if !contents.ends_with(b"\n") {
+        let newline: &[u8] = if contents.windows(2).any(|pair| pair == b"\r\n") {
+            b"\r\n"
+        } else {
+            b"\n"
+        };
+        return Some([contents, newline].concat());
+    }
+
+    if !single {
+        return None;
+    }
+
+    let end = contents
+        .iter()
+        .rposition(|&byte| byte != b'\n' && byte != b'\r')
+        .map_or(0, |i| i + 1);
+    let newline_len = if contents[end..].starts_with(b"\r\n") {
+        2
+    } else {
+        1
+    };
+
+    if end + newline_len == contents.len() {
+        None
+    } else {
+        Some(contents[..end + newline_len].to_vec())
+    }
+}
+
+/// Makes sure a text file ends with a newline
+///
+/// A newline is appended if missing, using `\r\n` if the file already contains one. With `single`, extra trailing
+/// newlines are removed as well. Empty files are left empty, and files that need no fix are neither backed up nor written
+pub struct EnsureFinalNewline {
+    file: FileBackup,
+    single: bool,
+}
+
+impl EnsureFinalNewline {
+    /// Constructs a new EnsureFinalNewline operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, single: bool) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            single,
+        }
+    }
+
+    /// Returns the fixed contents, or `None` if `contents` need no fix
+    fn fix(&self, contents: &[u8]) -> Option<Vec<u8>> {
+        ensure_final_newline(contents, self.single)
+    }
+}
+
+impl Rewrite for EnsureFinalNewline {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.fix(&contents))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(EnsureFinalNewline::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.single,
+        )))
+    }
+}
+
+/// Cleans up common encoding and line ending issues of a text file in a single rewrite
+///
+/// Can strip a leading UTF-8 byte order mark, convert `\r\n` line endings to `\n` and append a final newline if missing.
+/// All enabled fixes share one backup. Files that need no fix are neither backed up nor written
+pub struct NormalizeTextFile {
+    file: FileBackup,
+    strip_bom: bool,
+    crlf_to_lf: bool,
+    final_newline: bool,
+}
+
+impl NormalizeTextFile {
+    /// Constructs a new NormalizeTextFile operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        strip_bom: bool,
+        crlf_to_lf: bool,
+        final_newline: bool,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            strip_bom,
+            crlf_to_lf,
+            final_newline,
+        }
+    }
+
+    /// Returns the fixed contents, or `None` if `contents` need no fix
+    fn fix(&self, contents: &[u8]) -> Option<Vec<u8>> {
+        let mut fixed = match contents.strip_prefix(UTF8_BOM) {
+            Some(rest) if self.strip_bom => rest,
+            _ => contents,
+        }
+        .to_vec();
+
+        if self.crlf_to_lf {
+            let mut converted = Vec::with_capacity(fixed.len());
+            let mut bytes = fixed.iter().peekable();
+
+            while let Some(&byte) = bytes.next() {
+                if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+                    converted.push(byte);
+                }
+            }
+            fixed = converted;
+        }
+
+        if self.final_newline {
+            if let Some(terminated) = ensure_final_newline(&fixed, false) {
+                fixed = terminated;
+            }
+        }
+
+        match fixed == contents {
+            true => None,
+            false => Some(fixed),
+        }
+    }
+}
+
+impl Rewrite for NormalizeTextFile {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.fix(&contents))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(NormalizeTextFile::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.strip_bom,
+            self.crlf_to_lf,
+            self.final_newline,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn ensure_final_newline_works() {
+        let cases: [(&str, &str, &str); 4] = [
+            ("./ensure_final_newline_zero.txt", "a\nb", "a\nb\n"),
+            ("./ensure_final_newline_one.txt", "a\nb\n", "a\nb\n"),
+            ("./ensure_final_newline_several.txt", "a\nb\n\n\n", "a\nb\n"),
+            ("./ensure_final_newline_empty.txt", "", ""),
+        ];
+
+        for (source, data, expected) in cases.iter() {
+            fs::write(source, data).unwrap();
+
+            let mut op = EnsureFinalNewline::new(source, TEMP_DIR, true);
+            assert_eq!((), op.execute().unwrap());
+            assert_eq!(*expected, fs::read_to_string(source).unwrap());
+            assert_eq!(data != expected, op.backup_path().is_some());
+
+            assert_eq!((), op.rollback().unwrap());
+            assert_eq!(*data, fs::read_to_string(source).unwrap());
+
+            fs::remove_file(source);
+        }
+    }
+
+    #[test]
+    fn ensure_final_newline_flags() {
+        let single = EnsureFinalNewline::new("", TEMP_DIR, true);
+        let keep = EnsureFinalNewline::new("", TEMP_DIR, false);
+
+        assert_eq!(None, keep.fix(b"a\n\n\n"));
+        assert_eq!(Some(b"a\r\nb\r\n".to_vec()), keep.fix(b"a\r\nb"));
+        assert_eq!(Some(b"a\r\n".to_vec()), single.fix(b"a\r\n\r\n"));
+        assert_eq!(Some(b"\n".to_vec()), single.fix(b"\n\n"));
+    }
+
+    const NORMALIZE_SOURCE: &str = "./normalize_text_file.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn normalize_text_file_works() {
+        let data = b"\xEF\xBB\xBFname,value\r\nfirst,1\r\nsecond,2";
+        fs::write(NORMALIZE_SOURCE, data).unwrap();
+
+        let mut op = NormalizeTextFile::new(NORMALIZE_SOURCE, TEMP_DIR, true, true, true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "name,value\nfirst,1\nsecond,2\n",
+            fs::read_to_string(NORMALIZE_SOURCE).unwrap()
+        );
+        assert_eq!(1, op.changes().len());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(data.to_vec(), fs::read(NORMALIZE_SOURCE).unwrap());
+
+        // Already normalized, nothing is backed up
+        fs::write(NORMALIZE_SOURCE, "a\nb\n").unwrap();
+        let mut op = NormalizeTextFile::new(NORMALIZE_SOURCE, TEMP_DIR, true, true, true);
+        assert_eq!((), op.execute().unwrap());
+        assert!(op.backup_path().is_none());
+
+        fs::remove_file(NORMALIZE_SOURCE);
+    }
+
+    #[test]
+    fn normalize_text_file_flags() {
+        let bom_only = NormalizeTextFile::new("", TEMP_DIR, true, false, false);
+        let newline_only = NormalizeTextFile::new("", TEMP_DIR, false, false, true);
+
+        assert_eq!(
+            Some(b"a\r\nb".to_vec()),
+            bom_only.fix(b"\xEF\xBB\xBFa\r\nb")
+        );
+        assert_eq!(None, bom_only.fix(b"a\r\nb"));
+        assert_eq!(
+            Some(b"\xEF\xBB\xBFa\r\nb\r\n".to_vec()),
+            newline_only.fix(b"\xEF\xBB\xBFa\r\nb")
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{Change, ChangeKind, RollbackableOperation, UndoStep};
+
+/// Writes data at multiple offsets of a file
+///
+/// Edits are applied in the order given, so later edits win where they overlap. Writing past the end of the file extends it
+pub struct MultiPatch {
+    file: FileBackup,
+    edits: Vec<(u64, Vec<u8>)>,
+}
+
+impl MultiPatch {
+    /// Constructs a new MultiPatch operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        edits: Vec<(u64, Vec<u8>)>,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            edits,
+        }
+    }
+}
+
+impl RollbackableOperation for MultiPatch {
+    fn execute(&mut self) -> io::Result<()> {
+        self.file.create()?;
+
+        let mut file = OpenOptions::new().write(true).open(self.file.path())?;
+
+        for (offset, data) in &self.edits {
+            file.seek(SeekFrom::Start(*offset))?;
+            file.write_all(data)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.restore()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let before = self.file.backup_size();
+        let end = self
+            .edits
+            .iter()
+            .map(|(offset, data)| offset + data.len() as u64)
+            .max()
+            .unwrap_or(0);
+
+        vec![Change::new(ChangeKind::Modified, self.file.path())
+            .with_sizes(before, before.map(|size| size.max(end)))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.edits.iter().map(|(_, data)| data.len() as u64).sum()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.restore_step().into_iter().collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(MultiPatch::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.edits.clone(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./multi_patch.bin";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &[u8] = &[0; 16];
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn multi_patch_works() {
+        fs::write(SOURCE, DATA).unwrap();
+
+        let mut op = MultiPatch::new(
+            SOURCE,
+            TEMP_DIR,
+            vec![
+                (1, vec![1, 1]),
+                (8, vec![2, 2, 2]),
+                (9, vec![3]),
+                (15, vec![4, 4]),
+            ],
+        );
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            vec![0, 1, 1, 0, 0, 0, 0, 0, 2, 3, 2, 0, 0, 0, 0, 4, 4],
+            fs::read(SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SOURCE);
+    }
+}
@@ -0,0 +1,356 @@
+use std::fs::{self, Permissions};
+use std::os::unix::fs::{self as unix_fs, MetadataExt, PermissionsExt};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Change, ChangeKind, RollbackableOperation, UndoStep};
+
+/// Copies the permissions of a reference file onto a target file
+pub struct CopyPermissions {
+    target: PathBuf,
+    reference: PathBuf,
+    original_mode: Option<u32>,
+}
+
+impl CopyPermissions {
+    /// Constructs a new CopyPermissions operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(target: S, reference: T) -> Self {
+        Self {
+            target: target.as_ref().into(),
+            reference: reference.as_ref().into(),
+            original_mode: None,
+        }
+    }
+}
+
+impl RollbackableOperation for CopyPermissions {
+    fn execute(&mut self) -> io::Result<()> {
+        let mode = fs::metadata(&self.reference)?.permissions().mode();
+
+        self.original_mode = Some(fs::metadata(&self.target)?.permissions().mode());
+        fs::set_permissions(&self.target, Permissions::from_mode(mode))
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match self.original_mode {
+            Some(mode) => fs::set_permissions(&self.target, Permissions::from_mode(mode)),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.target)]
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.original_mode
+                .map(|mode| UndoStep::SetMode {
+                    path: self.target.clone(),
+                    mode,
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.target, &mut self.reference]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CopyPermissions::new(
+            &self.target,
+            &self.reference,
+        )))
+    }
+}
+
+/// Copies the owner and group of a reference file onto a target file
+///
+/// Changing the owner usually requires elevated privileges
+pub struct CopyOwner {
+    target: PathBuf,
+    reference: PathBuf,
+    original_owner: Option<(u32, u32)>,
+}
+
+impl CopyOwner {
+    /// Constructs a new CopyOwner operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(target: S, reference: T) -> Self {
+        Self {
+            target: target.as_ref().into(),
+            reference: reference.as_ref().into(),
+            original_owner: None,
+        }
+    }
+}
+
+impl RollbackableOperation for CopyOwner {
+    fn execute(&mut self) -> io::Result<()> {
+        let reference = fs::metadata(&self.reference)?;
+        let target = fs::metadata(&self.target)?;
+
+        unix_fs::chown(&self.target, Some(reference.uid()), Some(reference.gid()))?;
+        self.original_owner = Some((target.uid(), target.gid()));
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match self.original_owner {
+            Some((uid, gid)) => unix_fs::chown(&self.target, Some(uid), Some(gid)),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.target)]
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.original_owner
+                .map(|(uid, gid)| UndoStep::SetOwner {
+                    path: self.target.clone(),
+                    uid,
+                    gid,
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.target, &mut self.reference]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CopyOwner::new(&self.target, &self.reference)))
+    }
+}
+
+/// Clears permission bits from every entry of a directory tree
+///
+/// By default the group and other write bits (`0o022`) are cleared. The root itself is included and symlinks are skipped.
+/// The original mode of every changed entry is recorded and restored on rollback
+pub struct HardenPermissions {
+    root: PathBuf,
+    mask: u32,
+    original_modes: Vec<(PathBuf, u32)>,
+}
+
+impl HardenPermissions {
+    /// Constructs a new HardenPermissions operation
+    pub fn new<S: AsRef<Path>>(root: S) -> Self {
+        Self {
+            root: root.as_ref().into(),
+            mask: 0o022,
+            original_modes: vec![],
+        }
+    }
+
+    /// Sets the bits to clear, defaults to `0o022`
+    pub fn with_mask(mut self, mask: u32) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    fn harden(&mut self, path: &Path) -> io::Result<()> {
+        let mode = fs::symlink_metadata(path)?.permissions().mode();
+
+        if mode & self.mask != 0 {
+            fs::set_permissions(path, Permissions::from_mode(mode & !self.mask))?;
+            self.original_modes.push((path.into(), mode));
+        }
+
+        Ok(())
+    }
+}
+
+impl RollbackableOperation for HardenPermissions {
+    fn execute(&mut self) -> io::Result<()> {
+        let mut stack = vec![self.root.clone()];
+
+        while let Some(path) = stack.pop() {
+            let file_type = fs::symlink_metadata(&path)?.file_type();
+
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                for entry in fs::read_dir(&path)? {
+                    stack.push(entry?.path());
+                }
+            }
+
+            self.harden(&path)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (path, mode) in self.original_modes.iter().rev() {
+            fs::set_permissions(path, Permissions::from_mode(*mode))?;
+        }
+
+        Ok(())
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.original_modes
+            .iter()
+            .map(|(path, _)| Change::new(ChangeKind::Modified, path))
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.original_modes
+                .iter()
+                .rev()
+                .map(|(path, mode)| UndoStep::SetMode {
+                    path: path.clone(),
+                    mode: *mode,
+                })
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.root]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(
+            HardenPermissions::new(&self.root).with_mask(self.mask),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const TARGET: &str = "./copy_permissions_target.txt";
+    const REFERENCE: &str = "./copy_permissions_reference.txt";
+
+    fn mode(path: &str) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    fn paths(changes: &[Change]) -> Vec<&Path> {
+        changes.iter().map(|change| change.path.as_path()).collect()
+    }
+
+    fn setup() -> std::io::Result<()> {
+        fs::write(TARGET, b"Hello World")?;
+        fs::write(REFERENCE, b"Hello World")?;
+        fs::set_permissions(TARGET, Permissions::from_mode(0o644))?;
+        fs::set_permissions(REFERENCE, Permissions::from_mode(0o600))
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn copy_permissions_works() {
+        assert_eq!((), setup().unwrap());
+
+        let mut op = CopyPermissions::new(TARGET, REFERENCE);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(mode(REFERENCE), mode(TARGET));
+        assert_eq!(vec![Path::new(TARGET)], paths(&op.changes()));
+        assert_eq!(
+            Some(vec![UndoStep::SetMode {
+                path: TARGET.into(),
+                mode: 0o100644,
+            }]),
+            op.undo_steps()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(0o644, mode(TARGET));
+
+        fs::remove_file(TARGET);
+        fs::remove_file(REFERENCE);
+    }
+
+    const HARDEN_ROOT: &str = "./harden_permissions";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn harden_permissions_works() {
+        let root = Path::new(HARDEN_ROOT);
+        let nested = root.join("nested");
+        let shared = nested.join("shared.txt");
+        let private = root.join("private.txt");
+
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(&shared, b"Hello").unwrap();
+        fs::write(&private, b"World").unwrap();
+        fs::set_permissions(&nested, Permissions::from_mode(0o777)).unwrap();
+        fs::set_permissions(&shared, Permissions::from_mode(0o666)).unwrap();
+        fs::set_permissions(&private, Permissions::from_mode(0o600)).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let mut op = HardenPermissions::new(root);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(0o755, mode(&nested));
+        assert_eq!(0o644, mode(&shared));
+        assert_eq!(0o600, mode(&private));
+        assert!(op.changes().iter().all(|change| change.path != private));
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(0o777, mode(&nested));
+        assert_eq!(0o666, mode(&shared));
+        assert_eq!(0o600, mode(&private));
+
+        fs::remove_dir_all(root);
+    }
+
+    const OWNER_TARGET: &str = "./copy_owner_target.txt";
+    const OWNER_REFERENCE: &str = "./copy_owner_reference.txt";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn copy_owner_works() {
+        fs::write(OWNER_TARGET, b"Hello World").unwrap();
+        fs::write(OWNER_REFERENCE, b"Hello World").unwrap();
+
+        // Only root can hand files to another user
+        if fs::metadata(OWNER_TARGET).unwrap().uid() != 0 {
+            fs::remove_file(OWNER_TARGET);
+            fs::remove_file(OWNER_REFERENCE);
+            return;
+        }
+        unix_fs::chown(OWNER_REFERENCE, Some(1234), Some(5678)).unwrap();
+
+        let original = fs::metadata(OWNER_TARGET).unwrap();
+        let mut op = CopyOwner::new(OWNER_TARGET, OWNER_REFERENCE);
+
+        assert_eq!((), op.execute().unwrap());
+        let metadata = fs::metadata(OWNER_TARGET).unwrap();
+        assert_eq!((1234, 5678), (metadata.uid(), metadata.gid()));
+        assert_eq!(vec![Path::new(OWNER_TARGET)], paths(&op.changes()));
+        assert_eq!(
+            Some(vec![UndoStep::SetOwner {
+                path: OWNER_TARGET.into(),
+                uid: original.uid(),
+                gid: original.gid(),
+            }]),
+            op.undo_steps()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(0, fs::metadata(OWNER_TARGET).unwrap().uid());
+
+        fs::remove_file(OWNER_TARGET);
+        fs::remove_file(OWNER_REFERENCE);
+    }
+}
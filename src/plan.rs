@@ -0,0 +1,118 @@
+use std::fmt::Write;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::RollbackableOperation;
+
+/// Value of a parameter in the [spec](trait.RollbackableOperation.html#method.spec) of an operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecValue {
+    /// A path the operation acts on
+    Path(PathBuf),
+    /// Data the operation writes, represented by its length only
+    Bytes(usize),
+    /// A number
+    Number(u64),
+    /// A flag
+    Flag(bool),
+    /// Free-form text
+    Text(String),
+}
+
+fn push_string(json: &mut String, s: &str) {
+    json.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+}
+
+fn push_path(json: &mut String, path: &Path) -> io::Result<()> {
+    match path.to_str() {
+        Some(s) => {
+            push_string(json, s);
+            Ok(())
+        }
+        None => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Path is not valid UTF-8: {}", path.display()),
+        )),
+    }
+}
+
+fn push_value(json: &mut String, value: &SpecValue) -> io::Result<()> {
+    match value {
+        SpecValue::Path(path) => push_path(json, path)?,
+        SpecValue::Bytes(len) => {
+            let _ = write!(json, "{{\"bytes\":{}}}", len);
+        }
+        SpecValue::Number(n) => {
+            let _ = write!(json, "{}", n);
+        }
+        SpecValue::Flag(flag) => {
+            let _ = write!(json, "{}", flag);
+        }
+        SpecValue::Text(text) => push_string(json, text),
+    }
+
+    Ok(())
+}
+
+/// Renders the operations as a JSON array of `{"type": ..., "params": {...}}` objects
+pub(crate) fn plan_json<'a, I>(ops: I) -> io::Result<String>
+where
+    I: IntoIterator<Item = &'a dyn RollbackableOperation>,
+{
+    let mut json = String::from("[");
+
+    for (i, op) in ops.into_iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+
+        json.push_str("{\"type\":");
+        push_string(&mut json, &op.description());
+        json.push_str(",\"params\":{");
+
+        for (j, (name, value)) in op.spec().iter().enumerate() {
+            if j > 0 {
+                json.push(',');
+            }
+
+            push_string(&mut json, name);
+            json.push(':');
+            push_value(&mut json, value)?;
+        }
+
+        json.push_str("}}");
+    }
+
+    json.push(']');
+
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_string_escapes() {
+        let mut json = String::new();
+        push_string(&mut json, "a\"b\\c\nd\u{1}");
+
+        assert_eq!("\"a\\\"b\\\\c\\nd\\u0001\"", json);
+    }
+}
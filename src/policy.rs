@@ -0,0 +1,68 @@
+use std::cell::Cell;
+
+/// Policy applied when an operation is dropped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Only dispose off the backup
+    #[default]
+    CleanupOnly,
+    /// Rollback the operation if it was executed but neither rollbacked nor committed, then dispose off the backup
+    ///
+    /// Operations owned by a [Transaction](crate::Transaction) are dropped in reverse order, so they are rollbacked like
+    /// [rollback](crate::RollbackableOperation::rollback) would
+    RollbackIfNotCommitted,
+}
+
+/// How a [Transaction](crate::Transaction) handles operations that fail to rollback
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RollbackStrategy {
+    /// Stop at the first operation that fails to rollback and return its error
+    #[default]
+    StopOnError,
+    /// Attempt to rollback every operation, then return an error listing every one that failed
+    ContinueAndCollect,
+}
+
+/// Tracks the state an operation needs to apply its [DropPolicy](DropPolicy)
+#[derive(Debug, Default)]
+pub(crate) struct DropState {
+    policy: DropPolicy,
+    executed: bool,
+    committed: bool,
+    rolled_back: Cell<bool>,
+}
+
+impl DropState {
+    pub(crate) fn set_policy(&mut self, policy: DropPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns a fresh state with the same policy
+    pub(crate) fn reset(&self) -> DropState {
+        DropState {
+            policy: self.policy,
+            ..DropState::default()
+        }
+    }
+
+    pub(crate) fn executed(&mut self) {
+        self.executed = true;
+        self.rolled_back.set(false);
+    }
+
+    pub(crate) fn rolled_back(&self) {
+        self.rolled_back.set(true);
+    }
+
+    pub(crate) fn committed(&mut self) {
+        self.committed = true;
+    }
+
+    /// Returns whether the operation should be rollbacked when dropped
+    pub(crate) fn should_rollback(&self) -> bool {
+        self.policy == DropPolicy::RollbackIfNotCommitted
+            && self.executed
+            && !self.committed
+            && !self.rolled_back.get()
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+
+use crate::{Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Wraps an operation to execute it only if a condition holds at execution time
+///
+/// The condition is evaluated when the operation is executed, not when it is added. If it returns `false` execution fails
+/// with `ErrorKind::Other` without running the wrapped operation, and an `Error` from the condition is returned as is.
+/// Rollback does nothing unless the wrapped operation was executed. Cannot be cloned, as the condition is a closure
+pub struct Precondition {
+    condition: Box<dyn Fn() -> io::Result<bool>>,
+    op: Box<dyn RollbackableOperation>,
+    executed: bool,
+}
+
+impl Precondition {
+    /// Constructs a new Precondition operation
+    pub fn new(
+        condition: Box<dyn Fn() -> io::Result<bool>>,
+        op: Box<dyn RollbackableOperation>,
+    ) -> Self {
+        Self {
+            condition,
+            op,
+            executed: false,
+        }
+    }
+}
+
+impl RollbackableOperation for Precondition {
+    fn execute(&mut self) -> io::Result<()> {
+        if !(self.condition)()? {
+            return Err(Error::other(format!(
+                "precondition failed for {}",
+                self.op.description()
+            )));
+        }
+
+        self.executed = true;
+        self.op.execute()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match self.executed {
+            true => self.op.rollback(),
+            false => Ok(()),
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.op.set_temp_dir(temp_dir)
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.op.set_backup_store(store)
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.op.set_backup_buffer_size(size)
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.op.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        match self.executed {
+            true => self.op.changes(),
+            false => vec![],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.op.backup_path()
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.op.owned_backups()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.op.bytes_written()
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        self.op.space_required()
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.op.paths_mut()
+    }
+
+    fn mark_committed(&mut self) {
+        self.op.mark_committed()
+    }
+
+    fn is_reversible(&self) -> bool {
+        self.op.is_reversible()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        match self.executed {
+            true => self.op.undo_steps(),
+            false => Some(vec![]),
+        }
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        self.op.spec()
+    }
+
+    fn description(&self) -> String {
+        format!("Precondition({})", self.op.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CreateFile, Transaction};
+    use std::fs;
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn precondition_works() {
+        const CREATED: &str = "./precondition_created.txt";
+        const GUARDED: &str = "./precondition_guarded.txt";
+        const MARKER: &str = "./precondition_marker.txt";
+
+        let mut tr = Transaction::new()
+            .create_file(CREATED)
+            .add_with_precondition(
+                Box::new(|| Ok(Path::new(MARKER).exists())),
+                Box::new(CreateFile::new(GUARDED)),
+            );
+
+        let err = tr.execute().unwrap_err();
+        assert_eq!(io::ErrorKind::Other, err.kind());
+        assert!(err.to_string().starts_with("precondition failed"));
+        assert!(!Path::new(GUARDED).exists());
+
+        assert_eq!((), tr.rollback().unwrap());
+        assert!(!Path::new(CREATED).exists());
+
+        fs::write(MARKER, "").unwrap();
+
+        let mut op = Precondition::new(
+            Box::new(|| Ok(Path::new(MARKER).exists())),
+            Box::new(CreateFile::new(GUARDED)),
+        );
+        assert_eq!((), op.execute().unwrap());
+        assert!(Path::new(GUARDED).exists());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(GUARDED).exists());
+
+        fs::remove_file(MARKER);
+    }
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+/// Prepends a header to a file unless the file already begins with it
+///
+/// The file is only backed up when the header is actually prepended
+pub struct PrependHeaderIfMissing {
+    file: FileBackup,
+    header: Vec<u8>,
+}
+
+impl PrependHeaderIfMissing {
+    /// Constructs a new PrependHeaderIfMissing operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, header: Vec<u8>) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            header,
+        }
+    }
+}
+
+impl Rewrite for PrependHeaderIfMissing {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        if contents.starts_with(&self.header) {
+            return Ok(None);
+        }
+
+        Ok(Some([self.header.as_slice(), &contents].concat()))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(PrependHeaderIfMissing::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.header.clone(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./prepend_header.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const HEADER: &[u8] = "// License\n".as_bytes();
+    const DATA: &[u8] = "fn main() {}\n".as_bytes();
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn prepend_header_if_missing_works() {
+        fs::write(SOURCE, DATA).unwrap();
+
+        let mut first = PrependHeaderIfMissing::new(SOURCE, TEMP_DIR, HEADER.to_vec());
+        let mut second = PrependHeaderIfMissing::new(SOURCE, TEMP_DIR, HEADER.to_vec());
+
+        assert_eq!((), first.execute().unwrap());
+        assert_eq!((), second.execute().unwrap());
+        assert_eq!([HEADER, DATA].concat(), fs::read(SOURCE).unwrap());
+
+        assert_eq!((), second.rollback().unwrap());
+        assert_eq!([HEADER, DATA].concat(), fs::read(SOURCE).unwrap());
+
+        assert_eq!((), first.rollback().unwrap());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SOURCE);
+    }
+}
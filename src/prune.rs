@@ -0,0 +1,135 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation, UndoStep};
+
+/// Recursively removes the empty directories under a root
+///
+/// Directories that only contain empty directories are removed as well. The root itself is kept
+pub struct PruneEmptyDirs {
+    root: PathBuf,
+    temp_dir: PathBuf,
+    removed: Vec<PathBuf>,
+}
+
+impl PruneEmptyDirs {
+    /// Constructs a new PruneEmptyDirs operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(root: S, temp_dir: T) -> Self {
+        Self {
+            root: root.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            removed: vec![],
+        }
+    }
+
+    /// Returns the directories removed by the operation, deepest first
+    pub fn removed(&self) -> &[PathBuf] {
+        &self.removed
+    }
+
+    /// Prunes the empty directories under `dir` and returns whether `dir` is now empty
+    fn prune(&mut self, dir: &Path) -> io::Result<bool> {
+        let mut empty = true;
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() && self.prune(&entry.path())? {
+                fs::remove_dir(entry.path())?;
+                self.removed.push(entry.path());
+            } else {
+                empty = false;
+            }
+        }
+
+        Ok(empty)
+    }
+}
+
+impl RollbackableOperation for PruneEmptyDirs {
+    fn execute(&mut self) -> io::Result<()> {
+        self.removed.clear();
+
+        let root = self.root.clone();
+        self.prune(&root).map(|_| ())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for dir in self.removed.iter().rev() {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.removed
+            .iter()
+            .map(|dir| Change::new(ChangeKind::Deleted, dir))
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.removed
+                .iter()
+                .rev()
+                .map(|dir| UndoStep::CreateDir(dir.clone()))
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.root, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(PruneEmptyDirs::new(&self.root, &self.temp_dir)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "./prune_empty_dirs";
+    const TEMP_DIR: &str = "./tmp/";
+
+    fn setup() -> io::Result<()> {
+        let root = Path::new(ROOT);
+
+        fs::create_dir_all(root.join("empty"))?;
+        fs::create_dir_all(root.join("nested/empty/deeper"))?;
+        fs::create_dir_all(root.join("kept/empty"))?;
+        fs::write(root.join("kept/file.txt"), "Hello World")
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn prune_empty_dirs_works() {
+        assert_eq!((), setup().unwrap());
+        let root = Path::new(ROOT);
+
+        let mut op = PruneEmptyDirs::new(ROOT, TEMP_DIR);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(5, op.removed().len());
+        assert!(root.exists());
+        assert!(root.join("kept/file.txt").exists());
+        assert!(!root.join("kept/empty").exists());
+        assert!(!root.join("empty").exists());
+        assert!(!root.join("nested").exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(root.join("empty").exists());
+        assert!(root.join("nested/empty/deeper").exists());
+        assert!(root.join("kept/empty").exists());
+
+        fs::remove_dir_all(ROOT);
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation, SpecValue, UndoStep};
+
+/// Renames every file in a directory by applying a transformation to its filename
+///
+/// Subdirectories are left alone, as are files whose name does not change. All new names are checked before
+/// any file is renamed: two files mapping to the same name, or a name that already exists, is an `Error` of kind `AlreadyExists`
+pub struct RenamePattern {
+    dir: PathBuf,
+    transform: Box<dyn Fn(&str) -> String>,
+    renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl RenamePattern {
+    /// Constructs a new RenamePattern operation
+    pub fn new<S: AsRef<Path>>(dir: S, transform: Box<dyn Fn(&str) -> String>) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+            transform,
+            renamed: vec![],
+        }
+    }
+
+    /// Returns the renames performed by the operation, as source and destination pairs
+    pub fn renamed(&self) -> &[(PathBuf, PathBuf)] {
+        &self.renamed
+    }
+
+    fn plan(&self) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+        let mut names = vec![];
+        let mut existing = HashSet::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            existing.insert(entry.file_name());
+
+            if !entry.file_type()?.is_dir() {
+                names.push(entry.file_name());
+            }
+        }
+        names.sort();
+
+        let mut targets = HashSet::new();
+        let mut plan = vec![];
+
+        for name in names {
+            let old = name.to_str().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{:?} is not valid UTF-8", name),
+                )
+            })?;
+            let new = (self.transform)(old);
+
+            if new == old {
+                continue;
+            }
+
+            if existing.contains(OsStr::new(&new)) || !targets.insert(new.clone()) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("Renaming {} would overwrite {}", old, new),
+                ));
+            }
+
+            plan.push((self.dir.join(old), self.dir.join(new)));
+        }
+
+        Ok(plan)
+    }
+}
+
+impl RollbackableOperation for RenamePattern {
+    fn execute(&mut self) -> io::Result<()> {
+        self.renamed.clear();
+
+        for (source, dest) in self.plan()? {
+            fs::rename(&source, &dest)?;
+            self.renamed.push((source, dest));
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (source, dest) in self.renamed.iter().rev() {
+            fs::rename(dest, source)?;
+        }
+
+        Ok(())
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.renamed
+            .iter()
+            .map(|(source, dest)| Change::new(ChangeKind::Moved, source).with_dest(dest))
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.renamed
+                .iter()
+                .rev()
+                .map(|(source, dest)| UndoStep::Rename {
+                    from: dest.clone(),
+                    to: source.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.dir]
+    }
+}
+
+/// Renames every file in a directory to a numbered sequence like `photo0001.jpg`
+///
+/// Files are numbered from 1 in the order of their names and keep their extension, subdirectories are left alone. Every file is
+/// first moved to a temporary name and only then to its new name, so a file already named like a later number is never
+/// overwritten. A new name taken by a subdirectory is an `Error` of kind `AlreadyExists`, checked before any file is renamed
+pub struct RenumberFiles {
+    dir: PathBuf,
+    prefix: String,
+    width: usize,
+    plan: Vec<(PathBuf, PathBuf, PathBuf)>,
+    staged: usize,
+    placed: usize,
+}
+
+impl RenumberFiles {
+    /// Constructs a new RenumberFiles operation, numbers are zero-padded to `width` digits
+    pub fn new<S: AsRef<Path>, T: AsRef<str>>(dir: S, prefix: T, width: usize) -> Self {
+        Self {
+            dir: dir.as_ref().into(),
+            prefix: prefix.as_ref().into(),
+            width,
+            plan: vec![],
+            staged: 0,
+            placed: 0,
+        }
+    }
+
+    /// Returns the renames performed by the operation, as source and destination pairs
+    pub fn renamed(&self) -> Vec<(&Path, &Path)> {
+        self.plan[..self.placed]
+            .iter()
+            .map(|(source, _, dest)| (source.as_path(), dest.as_path()))
+            .collect()
+    }
+
+    fn plan(&self) -> io::Result<Vec<(PathBuf, PathBuf, PathBuf)>> {
+        let mut names = vec![];
+        let mut dirs = HashSet::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() {
+                dirs.insert(entry.file_name());
+            } else {
+                names.push(entry.file_name());
+            }
+        }
+        names.sort();
+
+        let mut plan = vec![];
+
+        for (i, name) in names.into_iter().enumerate() {
+            let mut new = OsString::from(format!(
+                "{}{:0width$}",
+                self.prefix,
+                i + 1,
+                width = self.width
+            ));
+            if let Some(extension) = Path::new(&name).extension() {
+                new.push(".");
+                new.push(extension);
+            }
+
+            if dirs.contains(&new) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!(
+                        "Renaming {:?} would overwrite the directory {:?}",
+                        name, new
+                    ),
+                ));
+            }
+
+            let temp = format!(".{}.renumber", uuid::Uuid::new_v4().to_simple());
+            plan.push((
+                self.dir.join(&name),
+                self.dir.join(temp),
+                self.dir.join(new),
+            ));
+        }
+
+        Ok(plan)
+    }
+}
+
+impl RollbackableOperation for RenumberFiles {
+    fn execute(&mut self) -> io::Result<()> {
+        self.plan = self.plan()?;
+        self.staged = 0;
+        self.placed = 0;
+
+        for (source, temp, _) in &self.plan {
+            fs::rename(source, temp)?;
+            self.staged += 1;
+        }
+
+        for (_, temp, dest) in &self.plan {
+            fs::rename(temp, dest)?;
+            self.placed += 1;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (_, temp, dest) in self.plan[..self.placed].iter().rev() {
+            fs::rename(dest, temp)?;
+        }
+
+        for (source, temp, _) in self.plan[..self.staged].iter().rev() {
+            fs::rename(temp, source)?;
+        }
+
+        Ok(())
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        self.renamed()
+            .into_iter()
+            .filter(|(source, dest)| source != dest)
+            .map(|(source, dest)| Change::new(ChangeKind::Moved, source).with_dest(dest))
+            .collect()
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let unplace = self.plan[..self.placed]
+            .iter()
+            .rev()
+            .map(|(_, temp, dest)| UndoStep::Rename {
+                from: dest.clone(),
+                to: temp.clone(),
+            });
+        let unstage = self.plan[..self.staged]
+            .iter()
+            .rev()
+            .map(|(source, temp, _)| UndoStep::Rename {
+                from: temp.clone(),
+                to: source.clone(),
+            });
+
+        Some(unplace.chain(unstage).collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.dir]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("dir", SpecValue::Path(self.dir.clone())),
+            ("prefix", SpecValue::Text(self.prefix.clone())),
+            ("width", SpecValue::Number(self.width as u64)),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(RenumberFiles::new(
+            &self.dir,
+            &self.prefix,
+            self.width,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn rename_pattern_works() {
+        const DIR: &str = "./rename_pattern_dir";
+
+        fs::create_dir_all(Path::new(DIR).join("nested")).unwrap();
+        fs::write(Path::new(DIR).join("a.txt"), "a").unwrap();
+        fs::write(Path::new(DIR).join("b.txt"), "b").unwrap();
+
+        let mut op = RenamePattern::new(DIR, Box::new(|name| format!("2024-{}", name)));
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(2, op.renamed().len());
+        assert_eq!(
+            "a",
+            fs::read_to_string(Path::new(DIR).join("2024-a.txt")).unwrap()
+        );
+        assert_eq!(
+            "b",
+            fs::read_to_string(Path::new(DIR).join("2024-b.txt")).unwrap()
+        );
+        assert!(Path::new(DIR).join("nested").is_dir());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(Path::new(DIR).join("a.txt").exists());
+        assert!(Path::new(DIR).join("b.txt").exists());
+        assert!(!Path::new(DIR).join("2024-a.txt").exists());
+
+        fs::remove_dir_all(DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn rename_pattern_collision_works() {
+        const DIR: &str = "./rename_pattern_collision_dir";
+
+        fs::create_dir_all(DIR).unwrap();
+        fs::write(Path::new(DIR).join("A.txt"), "upper").unwrap();
+        fs::write(Path::new(DIR).join("B.txt"), "b").unwrap();
+        fs::write(Path::new(DIR).join("a.TXT"), "lower").unwrap();
+
+        let mut op = RenamePattern::new(DIR, Box::new(|name| name.to_lowercase()));
+
+        assert_eq!(ErrorKind::AlreadyExists, op.execute().unwrap_err().kind());
+        assert!(op.renamed().is_empty());
+        assert!(Path::new(DIR).join("B.txt").exists());
+
+        fs::remove_dir_all(DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn renumber_files_works() {
+        const DIR: &str = "./renumber_files_dir";
+        let dir = Path::new(DIR);
+
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("beach.jpg"), "beach").unwrap();
+        fs::write(dir.join("photo0001.jpg"), "first").unwrap();
+        fs::write(dir.join("sunset.png"), "sunset").unwrap();
+        fs::write(dir.join("notes"), "notes").unwrap();
+
+        let mut op = RenumberFiles::new(DIR, "photo", 4);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(4, op.renamed().len());
+        assert_eq!(
+            "beach",
+            fs::read_to_string(dir.join("photo0001.jpg")).unwrap()
+        );
+        assert_eq!("notes", fs::read_to_string(dir.join("photo0002")).unwrap());
+        assert_eq!(
+            "first",
+            fs::read_to_string(dir.join("photo0003.jpg")).unwrap()
+        );
+        assert_eq!(
+            "sunset",
+            fs::read_to_string(dir.join("photo0004.png")).unwrap()
+        );
+        assert!(dir.join("nested").is_dir());
+        assert_eq!(5, fs::read_dir(DIR).unwrap().count());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("beach", fs::read_to_string(dir.join("beach.jpg")).unwrap());
+        assert_eq!(
+            "first",
+            fs::read_to_string(dir.join("photo0001.jpg")).unwrap()
+        );
+        assert_eq!(
+            "sunset",
+            fs::read_to_string(dir.join("sunset.png")).unwrap()
+        );
+        assert_eq!("notes", fs::read_to_string(dir.join("notes")).unwrap());
+        assert_eq!(5, fs::read_dir(DIR).unwrap().count());
+
+        fs::remove_dir_all(DIR);
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    non_empty_path, unique_path, Change, ChangeKind, DirectoryOperation, RollbackableOperation,
+    UndoStep,
+};
+
+/// Replaces a directory with another one
+///
+/// The target directory is moved aside into the temp dir and the new directory is renamed into its place. Both moves are renames, so the temp dir and the new directory should be on the same filesystem as the target
+pub struct ReplaceDirectory {
+    target: PathBuf,
+    new_source: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    replaced: bool,
+}
+
+impl ReplaceDirectory {
+    /// Constructs a new ReplaceDirectory operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        target: S,
+        new_source: T,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            target: target.as_ref().into(),
+            new_source: new_source.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+            replaced: false,
+        }
+    }
+}
+
+impl RollbackableOperation for ReplaceDirectory {
+    fn execute(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.temp_dir)?;
+
+        let backup_path = unique_path(&self.temp_dir);
+        fs::rename(&self.target, &backup_path)?;
+        self.set_backup_path(backup_path);
+
+        fs::rename(&self.new_source, &self.target)?;
+        self.replaced = true;
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if self.replaced {
+            fs::rename(&self.target, &self.new_source)?;
+        }
+
+        match self.backup_path() {
+            Some(backup_path) if backup_path.exists() => fs::rename(backup_path, &self.target),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.get_backup_path().exists()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![
+            Change::new(ChangeKind::Deleted, &self.target),
+            Change::new(ChangeKind::Moved, &self.new_source).with_dest(&self.target),
+        ]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let mut steps = vec![];
+
+        if self.replaced {
+            steps.push(UndoStep::Rename {
+                from: self.target.clone(),
+                to: self.new_source.clone(),
+            });
+        }
+
+        if let Some(backup) = self.backup_path() {
+            steps.push(UndoStep::RestoreDir {
+                backup: backup.into(),
+                path: self.target.clone(),
+            });
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.target, &mut self.new_source, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(ReplaceDirectory::new(
+            &self.target,
+            &self.new_source,
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl DirectoryOperation for ReplaceDirectory {
+    fn get_path(&self) -> &Path {
+        &self.target
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+}
+
+impl Drop for ReplaceDirectory {
+    fn drop(&mut self) {
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    const TARGET_DIR: &str = "./replace_dir_target";
+    const NEW_DIR: &str = "./replace_dir_new";
+    const TEMP_DIR: &str = "./tmp/";
+
+    fn setup() -> std::io::Result<()> {
+        fs::create_dir_all(TARGET_DIR)?;
+        fs::write(Path::new(TARGET_DIR).join("old.txt"), b"Old World")?;
+        fs::create_dir_all(NEW_DIR)?;
+        fs::write(Path::new(NEW_DIR).join("new.txt"), b"New World")
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn replace_dir_works() {
+        assert_eq!((), setup().unwrap());
+
+        let mut op = ReplaceDirectory::new(TARGET_DIR, NEW_DIR, TEMP_DIR);
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(!Path::new(NEW_DIR).exists());
+        assert!(Path::new(TARGET_DIR).join("new.txt").exists());
+        assert!(!Path::new(TARGET_DIR).join("old.txt").exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(Path::new(NEW_DIR).join("new.txt").exists());
+        assert!(Path::new(TARGET_DIR).join("old.txt").exists());
+        assert!(!Path::new(TARGET_DIR).join("new.txt").exists());
+
+        fs::remove_dir_all(TARGET_DIR);
+        fs::remove_dir_all(NEW_DIR);
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+/// Kind of change made to the filesystem by an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A file or directory was created
+    Created,
+    /// A file was modified in place
+    Modified,
+    /// A file or directory was deleted
+    Deleted,
+    /// A file or directory was moved
+    Moved,
+}
+
+/// A single change made to the filesystem by an operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Kind of the change
+    pub kind: ChangeKind,
+    /// Path that was changed
+    pub path: PathBuf,
+    /// Destination path for [Moved](ChangeKind::Moved) changes
+    pub dest: Option<PathBuf>,
+    /// Size of the file before the change, if known
+    pub size_before: Option<u64>,
+    /// Size of the file after the change, if known
+    pub size_after: Option<u64>,
+}
+
+impl Change {
+    /// Constructs a new Change with unknown sizes
+    pub fn new<S: AsRef<Path>>(kind: ChangeKind, path: S) -> Self {
+        Self {
+            kind,
+            path: path.as_ref().into(),
+            dest: None,
+            size_before: None,
+            size_after: None,
+        }
+    }
+
+    /// Sets the destination path
+    pub fn with_dest<S: AsRef<Path>>(mut self, dest: S) -> Self {
+        self.dest = Some(dest.as_ref().into());
+        self
+    }
+
+    /// Sets the sizes before and after the change
+    pub fn with_sizes(mut self, size_before: Option<u64>, size_after: Option<u64>) -> Self {
+        self.size_before = size_before;
+        self.size_after = size_after;
+        self
+    }
+
+    /// Returns the change in size in bytes, if both sizes are known
+    pub fn size_delta(&self) -> Option<i64> {
+        match (self.size_before, self.size_after) {
+            (Some(before), Some(after)) => Some(after as i64 - before as i64),
+            _ => None,
+        }
+    }
+}
+
+/// Summary of the changes made by the executed operations of a transaction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeReport {
+    /// All changes in execution order
+    pub changes: Vec<Change>,
+}
+
+impl ChangeReport {
+    /// Returns the changes of the given kind
+    pub fn of_kind(&self, kind: ChangeKind) -> Vec<&Change> {
+        self.changes.iter().filter(|c| c.kind == kind).collect()
+    }
+
+    /// Returns the created files and directories
+    pub fn created(&self) -> Vec<&Change> {
+        self.of_kind(ChangeKind::Created)
+    }
+
+    /// Returns the modified files
+    pub fn modified(&self) -> Vec<&Change> {
+        self.of_kind(ChangeKind::Modified)
+    }
+
+    /// Returns the deleted files and directories
+    pub fn deleted(&self) -> Vec<&Change> {
+        self.of_kind(ChangeKind::Deleted)
+    }
+
+    /// Returns the moved files and directories
+    pub fn moved(&self) -> Vec<&Change> {
+        self.of_kind(ChangeKind::Moved)
+    }
+}
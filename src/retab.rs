@@ -0,0 +1,208 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::{RollbackableOperation, SpecValue};
+
+/// Direction of the conversion done by [Retab](struct.Retab.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetabDirection {
+    /// Expands tabs to spaces up to the next tab stop
+    TabsToSpaces,
+    /// Replaces spaces reaching a tab stop with tabs
+    SpacesToTabs,
+}
+
+/// Converts the indentation of a text file between tabs and spaces
+///
+/// Only the whitespace at the start of each line is converted, unless [with_convert_all](#method.with_convert_all) is set.
+/// Tab stops are every `tab_width` columns. Line endings are preserved. A `tab_width` of 0 fails with `ErrorKind::InvalidInput`
+pub struct Retab {
+    file: FileBackup,
+    tab_width: usize,
+    direction: RetabDirection,
+    convert_all: bool,
+}
+
+impl Retab {
+    /// Constructs a new Retab operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        tab_width: usize,
+        direction: RetabDirection,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            tab_width,
+            direction,
+            convert_all: false,
+        }
+    }
+
+    /// Converts every run of whitespace, not only the indentation
+    ///
+    /// A single space between words is never turned into a tab
+    pub fn with_convert_all(mut self, convert_all: bool) -> Self {
+        self.convert_all = convert_all;
+        self
+    }
+
+    fn retab(&self, contents: &[u8]) -> Vec<u8> {
+        let mut retabbed = Vec::with_capacity(contents.len());
+
+        for (i, line) in contents.split(|&byte| byte == b'\n').enumerate() {
+            if i > 0 {
+                retabbed.push(b'\n');
+            }
+            self.retab_line(line, &mut retabbed);
+        }
+
+        retabbed
+    }
+
+    fn retab_line(&self, line: &[u8], out: &mut Vec<u8>) {
+        let width = self.tab_width;
+        let is_blank = |byte: u8| byte == b' ' || byte == b'\t';
+        let mut column = 0;
+        let mut leading = true;
+        let mut pos = 0;
+
+        while pos < line.len() {
+            if !is_blank(line[pos]) || !(leading || self.convert_all) {
+                // Continuation bytes of UTF-8 characters do not take up a column
+                if line[pos] & 0xC0 != 0x80 {
+                    column += 1;
+                }
+                leading &= is_blank(line[pos]);
+                out.push(line[pos]);
+                pos += 1;
+                continue;
+            }
+
+            let (start, run_start) = (column, pos);
+            while pos < line.len() && is_blank(line[pos]) {
+                column = match line[pos] {
+                    b'\t' => (column / width + 1) * width,
+                    _ => column + 1,
+                };
+                pos += 1;
+            }
+
+            match self.direction {
+                RetabDirection::TabsToSpaces => out.resize(out.len() + column - start, b' '),
+                RetabDirection::SpacesToTabs if !leading && &line[run_start..pos] == b" " => {
+                    out.push(b' ')
+                }
+                RetabDirection::SpacesToTabs => {
+                    let mut tab_column = start;
+                    while (tab_column / width + 1) * width <= column {
+                        out.push(b'\t');
+                        tab_column = (tab_column / width + 1) * width;
+                    }
+                    out.resize(out.len() + column - tab_column, b' ');
+                }
+            }
+        }
+    }
+}
+
+impl Rewrite for Retab {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        if self.tab_width == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Tab width must be at least 1",
+            ));
+        }
+
+        Ok(Some(self.retab(&contents)))
+    }
+
+    fn params(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.file.path().into())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+            ("tab_width", SpecValue::Number(self.tab_width as u64)),
+            (
+                "direction",
+                SpecValue::Text(format!("{:?}", self.direction)),
+            ),
+            ("convert_all", SpecValue::Flag(self.convert_all)),
+        ]
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(
+            Retab::new(
+                self.file.path(),
+                self.file.temp_dir(),
+                self.tab_width,
+                self.direction,
+            )
+            .with_convert_all(self.convert_all),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./retab.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn retab_works() {
+        const TABS: &str = "fn main() {\n\tlet a\t= 1;\n\t\tcall();\r\n  \tmixed();\n}\n";
+        const SPACES: &str = "fn main() {\n    let a\t= 1;\n        call();\r\n    mixed();\n}\n";
+        fs::write(SOURCE, TABS).unwrap();
+
+        let mut op = Retab::new(SOURCE, TEMP_DIR, 4, RetabDirection::TabsToSpaces);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(SPACES, fs::read_to_string(SOURCE).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(TABS, fs::read_to_string(SOURCE).unwrap());
+
+        fs::write(SOURCE, SPACES).unwrap();
+
+        let mut op = Retab::new(SOURCE, TEMP_DIR, 4, RetabDirection::SpacesToTabs);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "fn main() {\n\tlet a\t= 1;\n\t\tcall();\r\n\tmixed();\n}\n",
+            fs::read_to_string(SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(SPACES, fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    fn retab_convert_all() {
+        let expand =
+            Retab::new(SOURCE, TEMP_DIR, 4, RetabDirection::TabsToSpaces).with_convert_all(true);
+        let unexpand =
+            Retab::new(SOURCE, TEMP_DIR, 4, RetabDirection::SpacesToTabs).with_convert_all(true);
+
+        assert_eq!(b"  ab    c".to_vec(), expand.retab(b"  ab\tc"));
+        assert_eq!("  é c".as_bytes(), expand.retab("  é\tc".as_bytes()));
+        assert_eq!(
+            b"\tlet a\t= 1;".to_vec(),
+            unexpand.retab(b"    let a   = 1;")
+        );
+        assert_eq!(b"a b".to_vec(), unexpand.retab(b"a b"));
+    }
+}
@@ -0,0 +1,284 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::backup::Backup;
+use crate::{
+    back_up_file, file_size, ignore_not_found, non_empty_path, unique_path, Change, ChangeKind,
+    RollbackableOperation, SpecValue, UndoStep, DEFAULT_BACKUP_BUFFER_SIZE,
+};
+
+/// The file an operation overwrites, along with its backup
+///
+/// Shared by operations that back up a single file before writing it. The backup is disposed off once dropped
+pub struct FileBackup {
+    path: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
+    written: Option<u64>,
+}
+
+impl FileBackup {
+    /// Constructs a new FileBackup of `path`, to be created in `temp_dir`
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(path: S, temp_dir: T) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            written: None,
+        }
+    }
+
+    /// Returns path to the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns path to the temp dir
+    pub fn temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    /// Returns path to the backup, if created
+    pub fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(&self.backup_path)
+    }
+
+    /// Returns the size of the backup, if created
+    pub fn backup_size(&self) -> Option<u64> {
+        self.backup_path().and_then(file_size)
+    }
+
+    /// Backs up the file
+    pub fn create(&mut self) -> io::Result<()> {
+        let backup = back_up_file(
+            &self.path,
+            &self.temp_dir,
+            self.backup_store.as_deref(),
+            self.backup_buffer_size,
+        )?;
+
+        self.backup_path = backup.path().into();
+
+        Ok(())
+    }
+
+    /// Backs up the file into a new file created with `mode`, so the backup is never readable by more users than the file
+    ///
+    /// The backup store is skipped, as a shared copy would not keep the mode
+    #[cfg(unix)]
+    pub fn create_with_mode(&mut self, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        fs::create_dir_all(&self.temp_dir)?;
+        let backup_path = unique_path(&self.temp_dir);
+        let mut reader = BufReader::with_capacity(self.backup_buffer_size, File::open(&self.path)?);
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&backup_path)?;
+
+        if let Err(e) = io::copy(&mut reader, &mut writer) {
+            let _ = fs::remove_file(&backup_path);
+            return Err(e);
+        }
+
+        self.backup_path = backup_path;
+
+        Ok(())
+    }
+
+    /// Overwrites the file with `contents`
+    pub fn write(&mut self, contents: &[u8]) -> io::Result<()> {
+        fs::write(&self.path, contents)?;
+        self.written = Some(contents.len() as u64);
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes [written](#method.write), if any
+    pub fn written(&self) -> Option<u64> {
+        self.written
+    }
+
+    /// Restores the file from its backup, if created
+    pub fn restore(&self) -> io::Result<()> {
+        match self.backup_path() {
+            Some(backup_path) => Backup::existing_file(backup_path).restore(&self.path),
+            None => Ok(()),
+        }
+    }
+
+    /// Restores the file from its backup, or removes it if it was `written` without one
+    pub fn revert(&self, written: bool) -> io::Result<()> {
+        match self.backup_path() {
+            Some(_) => self.restore(),
+            None if written => ignore_not_found(fs::remove_file(&self.path)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the step restoring the file from its backup, if created
+    pub fn restore_step(&self) -> Option<UndoStep> {
+        self.backup_path().map(|backup| UndoStep::RestoreFile {
+            backup: backup.into(),
+            path: self.path.clone(),
+        })
+    }
+
+    /// Returns the steps matching [revert](#method.revert)
+    pub fn revert_steps(&self, written: bool) -> Vec<UndoStep> {
+        match self.restore_step() {
+            Some(step) => vec![step],
+            None if written => vec![UndoStep::RemoveFile(self.path.clone())],
+            None => vec![],
+        }
+    }
+
+    /// Returns the change of writing `written` bytes to the file, which was modified if backed up and created otherwise
+    pub fn change(&self, written: Option<u64>) -> Change {
+        match self.backup_path() {
+            Some(_) => Change::new(ChangeKind::Modified, &self.path)
+                .with_sizes(self.backup_size(), written),
+            None => Change::new(ChangeKind::Created, &self.path).with_sizes(None, written),
+        }
+    }
+
+    /// Sets the temp dir the backup is created in
+    pub fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    /// Sets the content-addressed store the backup is created in, with the `hash` feature
+    pub fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    /// Sets the size of the buffer the file is streamed through into its backup
+    pub fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    /// Returns `false` if the backup was created but is gone
+    pub fn can_rollback(&self) -> bool {
+        self.backup_path().is_none_or(Path::exists)
+    }
+
+    /// Returns mutable references to the path of the file and the temp dir
+    pub fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.path, &mut self.temp_dir]
+    }
+}
+
+impl Drop for FileBackup {
+    fn drop(&mut self) {
+        if let Err(e) = Backup::existing_file(&self.backup_path).dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Operation that rewrites the contents of a single file in place
+///
+/// Implementors get [RollbackableOperation](trait.RollbackableOperation.html) for free: on execution the file is read and passed
+/// to [rewrite](#tymethod.rewrite), and only backed up and written if new contents are returned
+pub trait Rewrite {
+    /// Returns the rewritten file
+    fn file(&self) -> &FileBackup;
+
+    /// Returns the rewritten file, mutably
+    fn file_mut(&mut self) -> &mut FileBackup;
+
+    /// Returns the new contents of the file, or `None` to leave it untouched
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>>;
+
+    /// Returns mutable references to the paths of the operation
+    ///
+    /// Defaults to the paths of its [file](#tymethod.file)
+    fn paths(&mut self) -> Vec<&mut PathBuf> {
+        self.file_mut().paths_mut()
+    }
+
+    /// Returns the parameters the operation was constructed with, by name
+    ///
+    /// Defaults to no parameters
+    fn params(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![]
+    }
+
+    /// Returns a fresh copy of the operation
+    ///
+    /// Defaults to `None`
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        None
+    }
+}
+
+impl<T: Rewrite> RollbackableOperation for T {
+    fn execute(&mut self) -> io::Result<()> {
+        let contents = fs::read(self.file().path())?;
+
+        if let Some(rewritten) = self.rewrite(contents)? {
+            self.file_mut().create()?;
+            self.file_mut().write(&rewritten)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file().restore()
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file_mut().set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file_mut().set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file_mut().set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file().can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        match self.file().backup_path() {
+            Some(_) => vec![self.file().change(self.file().written())],
+            None => vec![],
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file().backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.file().written().unwrap_or(0)
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file().restore_step().into_iter().collect())
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.paths()
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        self.params()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        self.clone_rewrite()
+    }
+}
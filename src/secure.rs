@@ -0,0 +1,244 @@
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{Change, RollbackableOperation, UndoStep};
+
+/// Writes data to a file that never has looser permissions than `mode`
+///
+/// The data is written to a sibling created with `mode` and renamed over the file, so neither the new data nor an
+/// existing file is ever exposed under a looser mode. An existing file is backed up and restored with its original mode on rollback
+pub struct WriteSecure {
+    file: FileBackup,
+    data: Vec<u8>,
+    mode: u32,
+    original_mode: Option<u32>,
+    written: bool,
+}
+
+impl WriteSecure {
+    /// Constructs a new WriteSecure operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        data: Vec<u8>,
+        mode: u32,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            data,
+            mode,
+            original_mode: None,
+            written: false,
+        }
+    }
+
+    fn staging_path(&self) -> PathBuf {
+        let mut staging = self.file.path().as_os_str().to_owned();
+        staging.push(format!(".{}.tmp", uuid::Uuid::new_v4().to_simple()));
+        staging.into()
+    }
+
+    fn write_staging(&self, staging: &Path) -> io::Result<()> {
+        let mut file = create_with_mode(staging, self.mode)?;
+        file.write_all(&self.data)?;
+        file.sync_all()
+    }
+
+    fn restore_staging(&self, staging: &Path, backup: &Path, mode: u32) -> io::Result<()> {
+        let mut file = create_with_mode(staging, mode)?;
+        io::copy(&mut File::open(backup)?, &mut file)?;
+        file.sync_all()
+    }
+}
+
+/// Creates a new file at `path` with exactly `mode`
+fn create_with_mode(path: &Path, mode: u32) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)?;
+
+    // The umask may have removed bits from `mode`, never added any
+    file.set_permissions(Permissions::from_mode(mode))?;
+
+    Ok(file)
+}
+
+impl RollbackableOperation for WriteSecure {
+    fn execute(&mut self) -> io::Result<()> {
+        let staging = self.staging_path();
+
+        if let Err(e) = self.write_staging(&staging) {
+            let _ = fs::remove_file(&staging);
+            return Err(e);
+        }
+
+        let result = match fs::metadata(self.file.path()) {
+            Ok(metadata) => {
+                let mode = metadata.permissions().mode() & 0o7777;
+                self.file
+                    .create_with_mode(mode)
+                    .map(|_| self.original_mode = Some(mode))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+        .and_then(|_| fs::rename(&staging, self.file.path()));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&staging);
+        } else {
+            self.written = true;
+        }
+
+        result
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if !self.written {
+            return Ok(());
+        }
+
+        let (backup, mode) = match (self.file.backup_path(), self.original_mode) {
+            (Some(backup), Some(mode)) => (backup, mode),
+            _ => return fs::remove_file(self.file.path()),
+        };
+
+        // Like the data on execution, the backup is restored through a sibling that never has a looser mode
+        let staging = self.staging_path();
+        let result = self
+            .restore_staging(&staging, backup, mode)
+            .and_then(|_| fs::rename(&staging, self.file.path()));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&staging);
+        }
+
+        result
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(Some(self.data.len() as u64))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if !self.written {
+            return Some(vec![]);
+        }
+
+        let mut steps = vec![UndoStep::RemoveFile(self.file.path().into())];
+        if let (Some(step), Some(mode)) = (self.file.restore_step(), self.original_mode) {
+            steps.push(step);
+            steps.push(UndoStep::SetMode {
+                path: self.file.path().into(),
+                mode,
+            });
+        }
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(WriteSecure::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.data.clone(),
+            self.mode,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "./write_secure.key";
+    const NEW_SOURCE: &str = "./write_secure_new.key";
+    const SECRET_SOURCE: &str = "./write_secure_secret.key";
+    const TEMP_DIR: &str = "./tmp/";
+
+    fn mode<P: AsRef<Path>>(path: P) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_secure_works() {
+        fs::write(SOURCE, "public").unwrap();
+        fs::set_permissions(SOURCE, Permissions::from_mode(0o644)).unwrap();
+
+        let mut op = WriteSecure::new(SOURCE, TEMP_DIR, b"secret".to_vec(), 0o600);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("secret", fs::read_to_string(SOURCE).unwrap());
+        assert_eq!(0o600, mode(SOURCE));
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("public", fs::read_to_string(SOURCE).unwrap());
+        assert_eq!(0o644, mode(SOURCE));
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_secure_backup_keeps_mode() {
+        fs::write(SECRET_SOURCE, "old secret").unwrap();
+        fs::set_permissions(SECRET_SOURCE, Permissions::from_mode(0o600)).unwrap();
+
+        let mut op = WriteSecure::new(SECRET_SOURCE, TEMP_DIR, b"new secret".to_vec(), 0o600);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(0o600, mode(op.backup_path().unwrap()));
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("old secret", fs::read_to_string(SECRET_SOURCE).unwrap());
+        assert_eq!(0o600, mode(SECRET_SOURCE));
+
+        fs::remove_file(SECRET_SOURCE);
+    }
+
+    #[test]
+    fn write_secure_new_file_works() {
+        let mut op = WriteSecure::new(NEW_SOURCE, TEMP_DIR, b"secret".to_vec(), 0o400);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(0o400, mode(NEW_SOURCE));
+        assert_eq!(None, op.backup_path());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(NEW_SOURCE).exists());
+    }
+}
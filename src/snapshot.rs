@@ -0,0 +1,141 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::backup::Backup;
+use crate::{non_empty_path, DirectoryOperation, RollbackableOperation, UndoStep};
+
+/// Snapshots a directory tree
+///
+/// The directory is left untouched on execution. On rollback it is restored to the snapshot, discarding any changes made to it in the meantime
+pub struct SnapshotDirectory {
+    source: PathBuf,
+    temp_dir: PathBuf,
+    backup_path: PathBuf,
+}
+
+impl SnapshotDirectory {
+    /// Constructs a new SnapshotDirectory operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            temp_dir: temp_dir.as_ref().into(),
+            backup_path: PathBuf::new(),
+        }
+    }
+}
+
+impl RollbackableOperation for SnapshotDirectory {
+    fn execute(&mut self) -> io::Result<()> {
+        self.create_backup_folder()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        // Never discard the directory without a snapshot to restore from
+        if self.get_backup_path().as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        Backup::existing_dir(self.get_backup_path()).restore(&self.source)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.get_backup_path().exists()
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.backup_path()
+                .map(|backup| UndoStep::RestoreDir {
+                    backup: backup.into(),
+                    path: self.source.clone(),
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.source, &mut self.temp_dir]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SnapshotDirectory::new(
+            &self.source,
+            &self.temp_dir,
+        )))
+    }
+}
+
+impl DirectoryOperation for SnapshotDirectory {
+    fn get_path(&self) -> &Path {
+        &self.source
+    }
+
+    fn get_backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    fn set_backup_path<S: AsRef<Path>>(&mut self, uuid: S) {
+        self.backup_path = uuid.as_ref().into();
+    }
+
+    fn get_temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+}
+
+impl Drop for SnapshotDirectory {
+    fn drop(&mut self) {
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppendFile;
+    use std::fs;
+    use std::path::Path;
+
+    const DIR_SOURCE: &str = "./snapshot_dir_source";
+    const FILE_SOURCE: &str = "./snapshot_dir_source/inner/file.txt";
+    const FILE_CREATED: &str = "./snapshot_dir_source/created.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    fn setup() -> std::io::Result<()> {
+        fs::create_dir_all(Path::new(FILE_SOURCE).parent().unwrap())?;
+        fs::write(FILE_SOURCE, b"Hello World")
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn snapshot_dir_works() {
+        assert_eq!((), setup().unwrap());
+
+        let mut op = SnapshotDirectory::new(DIR_SOURCE, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(b"Hello World".to_vec(), fs::read(FILE_SOURCE).unwrap());
+
+        let mut append = AppendFile::new(FILE_SOURCE, TEMP_DIR, b"!".to_vec());
+        assert_eq!((), append.execute().unwrap());
+        fs::write(FILE_CREATED, b"Yellow World").unwrap();
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(b"Hello World".to_vec(), fs::read(FILE_SOURCE).unwrap());
+        assert!(!Path::new(FILE_CREATED).exists());
+
+        fs::remove_dir_all(DIR_SOURCE);
+    }
+}
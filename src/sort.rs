@@ -0,0 +1,111 @@
+use std::io;
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+/// Sorts the lines of a text file
+///
+/// Lines are compared byte-wise with a stable sort. Whether the file ends with a newline is preserved
+pub struct SortLines {
+    file: FileBackup,
+    descending: bool,
+}
+
+impl SortLines {
+    /// Constructs a new SortLines operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(source: S, temp_dir: T, descending: bool) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            descending,
+        }
+    }
+}
+
+impl Rewrite for SortLines {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let trailing_newline = contents.ends_with(b"\n");
+        let body = if trailing_newline {
+            &contents[..contents.len() - 1]
+        } else {
+            &contents[..]
+        };
+
+        let mut lines: Vec<&[u8]> = body.split(|&byte| byte == b'\n').collect();
+
+        if self.descending {
+            lines.sort_by(|a, b| b.cmp(a));
+        } else {
+            lines.sort();
+        }
+
+        let mut sorted = lines.join(&b'\n');
+        if trailing_newline {
+            sorted.push(b'\n');
+        }
+
+        Ok(Some(sorted))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SortLines::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.descending,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const ASCENDING_SOURCE: &str = "./sort_lines_ascending.txt";
+    const DESCENDING_SOURCE: &str = "./sort_lines_descending.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &str = "pear\napple\nPlum\nbanana\n";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn sort_lines_ascending_works() {
+        fs::write(ASCENDING_SOURCE, DATA).unwrap();
+
+        let mut op = SortLines::new(ASCENDING_SOURCE, TEMP_DIR, false);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "Plum\napple\nbanana\npear\n",
+            fs::read_to_string(ASCENDING_SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read_to_string(ASCENDING_SOURCE).unwrap());
+
+        fs::remove_file(ASCENDING_SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn sort_lines_descending_works() {
+        fs::write(DESCENDING_SOURCE, DATA.trim_end()).unwrap();
+
+        let mut op = SortLines::new(DESCENDING_SOURCE, TEMP_DIR, true);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "pear\nbanana\napple\nPlum",
+            fs::read_to_string(DESCENDING_SOURCE).unwrap()
+        );
+
+        fs::remove_file(DESCENDING_SOURCE);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::rewrite::FileBackup;
+use crate::{
+    ignore_not_found, preserve_metadata, Change, ChangeKind, RollbackableOperation, UndoStep,
+};
+
+/// Writes data to a staging file next to the target, to be promoted over the target later
+///
+/// [execute](trait.RollbackableOperation.html#tymethod.execute) only writes `<target>.staged`, which the caller can inspect at
+/// [staging_path](#method.staging_path). [promote](#method.promote) then backs up the target and renames the staging file over it,
+/// keeping the permissions and owner of the target.
+/// Rollback removes the staging file, or restores the target once promoted. A staging file that was never promoted is removed on drop
+pub struct StageWrite {
+    file: FileBackup,
+    staging: PathBuf,
+    data: Vec<u8>,
+    staged: bool,
+    promoted: bool,
+}
+
+impl StageWrite {
+    /// Constructs a new StageWrite operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(target: S, temp_dir: T, data: Vec<u8>) -> Self {
+        let target: PathBuf = target.as_ref().into();
+        let mut staging = target.clone().into_os_string();
+        staging.push(".staged");
+
+        Self {
+            file: FileBackup::new(target, temp_dir),
+            staging: staging.into(),
+            data,
+            staged: false,
+            promoted: false,
+        }
+    }
+
+    /// Returns the path of the staging file
+    pub fn staging_path(&self) -> &Path {
+        &self.staging
+    }
+
+    /// Replaces the target with the staging file, backing up the target if it exists
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if the operation was not executed or was already promoted
+    pub fn promote(&mut self) -> io::Result<()> {
+        if !self.staged || self.promoted {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Only an executed StageWrite can be promoted, once",
+            ));
+        }
+
+        if self.file.path().exists() {
+            self.file.create()?;
+            preserve_metadata(&fs::metadata(self.file.path())?, &self.staging)?;
+        }
+
+        fs::rename(&self.staging, self.file.path())?;
+        self.promoted = true;
+
+        Ok(())
+    }
+}
+
+impl RollbackableOperation for StageWrite {
+    fn execute(&mut self) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.staging)?;
+        self.staged = true;
+
+        file.write_all(&self.data)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        if !self.promoted {
+            return match self.staged {
+                true => ignore_not_found(fs::remove_file(&self.staging)),
+                false => Ok(()),
+            };
+        }
+
+        self.file.revert(true)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let size = Some(self.data.len() as u64);
+
+        match self.promoted {
+            true => vec![self.file.change(size)],
+            false => {
+                vec![Change::new(ChangeKind::Created, &self.staging).with_sizes(None, size)]
+            }
+        }
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        let steps = match (self.staged, self.promoted) {
+            (false, _) => vec![],
+            (true, false) => vec![UndoStep::RemoveFile(self.staging.clone())],
+            (true, true) => self.file.revert_steps(true),
+        };
+
+        Some(steps)
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = self.file.paths_mut();
+        paths.push(&mut self.staging);
+        paths
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(StageWrite::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.data.clone(),
+        )))
+    }
+}
+
+impl Drop for StageWrite {
+    fn drop(&mut self) {
+        if self.staged && !self.promoted {
+            if let Err(e) = ignore_not_found(fs::remove_file(&self.staging)) {
+                eprintln!("{}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "./stage_write.txt";
+    const NEW_TARGET: &str = "./stage_write_new.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn stage_write_works() {
+        fs::write(TARGET, "old").unwrap();
+
+        let mut op = StageWrite::new(TARGET, TEMP_DIR, b"new".to_vec());
+        assert_eq!(ErrorKind::InvalidInput, op.promote().unwrap_err().kind());
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("new", fs::read_to_string(op.staging_path()).unwrap());
+        assert_eq!("old", fs::read_to_string(TARGET).unwrap());
+
+        assert_eq!((), op.promote().unwrap());
+        assert!(!op.staging_path().exists());
+        assert_eq!("new", fs::read_to_string(TARGET).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("old", fs::read_to_string(TARGET).unwrap());
+
+        fs::remove_file(TARGET);
+    }
+
+    #[test]
+    fn stage_write_unpromoted_works() {
+        let mut op = StageWrite::new(NEW_TARGET, TEMP_DIR, b"new".to_vec());
+        let staging = op.staging_path().to_path_buf();
+
+        assert_eq!((), op.execute().unwrap());
+        assert!(staging.exists());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!staging.exists());
+        assert!(!Path::new(NEW_TARGET).exists());
+
+        assert_eq!((), op.execute().unwrap());
+        drop(op);
+        assert!(!staging.exists());
+    }
+}
@@ -0,0 +1,111 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use regex::bytes::Regex;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::{RollbackableOperation, SpecValue};
+
+/// Replaces every match of a regular expression in a file
+///
+/// The replacement can refer to capture groups with `$1` or `${name}`, see [regex::Regex::replace_all](https://docs.rs/regex/1/regex/struct.Regex.html#method.replace_all).
+/// The file does not need to be valid UTF-8. An invalid pattern fails on execution with `ErrorKind::InvalidInput`
+pub struct RegexReplace {
+    file: FileBackup,
+    pattern: String,
+    replacement: String,
+}
+
+impl RegexReplace {
+    /// Constructs a new RegexReplace operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, P: AsRef<str>, R: AsRef<str>>(
+        source: S,
+        temp_dir: T,
+        pattern: P,
+        replacement: R,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            pattern: pattern.as_ref().into(),
+            replacement: replacement.as_ref().into(),
+        }
+    }
+}
+
+impl Rewrite for RegexReplace {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let regex =
+            Regex::new(&self.pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        Ok(Some(
+            regex
+                .replace_all(&contents, self.replacement.as_bytes())
+                .into_owned(),
+        ))
+    }
+
+    fn params(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.file.path().into())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+            ("pattern", SpecValue::Text(self.pattern.clone())),
+            ("replacement", SpecValue::Text(self.replacement.clone())),
+        ]
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(RegexReplace::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            &self.pattern,
+            &self.replacement,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./regex_replace.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &str = "date: 2024-01-31\ndate: 2025-12-01\n";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn regex_replace_works() {
+        fs::write(SOURCE, DATA).unwrap();
+
+        let mut op = RegexReplace::new(
+            SOURCE,
+            TEMP_DIR,
+            r"(?P<y>\d{4})-(\d{2})-(\d{2})",
+            "$3/$2/${y}",
+        );
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "date: 31/01/2024\ndate: 01/12/2025\n",
+            fs::read_to_string(SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read_to_string(SOURCE).unwrap());
+
+        let mut op = RegexReplace::new(SOURCE, TEMP_DIR, "(unclosed", "");
+        assert_eq!(ErrorKind::InvalidInput, op.execute().unwrap_err().kind());
+        assert!(op.backup_path().is_none());
+        assert_eq!((), op.rollback().unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+}
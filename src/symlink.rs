@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::{Change, ChangeKind, RollbackableOperation};
+
+/// Points an existing symlink at a new target
+///
+/// A new link is created next to the old one and renamed over it, so the link is never missing. Rollback points it back at the
+/// previous target the same way. An absolute target is one of the [paths](trait.RollbackableOperation.html#method.paths_mut) of the
+/// operation, while a relative one is resolved from the directory of the link and left as is
+pub struct RetargetSymlink {
+    link: PathBuf,
+    target: PathBuf,
+    old_target: Option<PathBuf>,
+}
+
+impl RetargetSymlink {
+    /// Constructs a new RetargetSymlink operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(link: S, target: T) -> Self {
+        Self {
+            link: link.as_ref().into(),
+            target: target.as_ref().into(),
+            old_target: None,
+        }
+    }
+
+    /// Returns the target the link pointed at before execution
+    pub fn old_target(&self) -> Option<&Path> {
+        self.old_target.as_deref()
+    }
+}
+
+fn replace_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    let mut staging = link.as_os_str().to_owned();
+    staging.push(format!(".{}.tmp", uuid::Uuid::new_v4().to_simple()));
+    let staging = PathBuf::from(staging);
+
+    symlink(target, &staging)?;
+
+    if let Err(e) = fs::rename(&staging, link) {
+        let _ = fs::remove_file(&staging);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+impl RollbackableOperation for RetargetSymlink {
+    fn execute(&mut self) -> io::Result<()> {
+        if !fs::symlink_metadata(&self.link)?.file_type().is_symlink() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a symlink", self.link.display()),
+            ));
+        }
+
+        let old_target = fs::read_link(&self.link)?;
+        replace_symlink(&self.link, &self.target)?;
+        self.old_target = Some(old_target);
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match &self.old_target {
+            Some(old_target) => replace_symlink(&self.link, old_target),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.link)]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.link];
+        if self.target.is_absolute() {
+            paths.push(&mut self.target);
+        }
+        paths
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(RetargetSymlink::new(&self.link, &self.target)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "./retarget_symlink";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn retarget_symlink_works() {
+        let root = Path::new(ROOT);
+        let link = root.join("current");
+
+        fs::create_dir_all(root.join("blue")).unwrap();
+        fs::create_dir_all(root.join("green")).unwrap();
+        symlink("blue", &link).unwrap();
+
+        let mut op = RetargetSymlink::new(&link, "green");
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(Path::new("green"), fs::read_link(&link).unwrap());
+        assert_eq!(Some(Path::new("blue")), op.old_target());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(Path::new("blue"), fs::read_link(&link).unwrap());
+
+        // Only the link and the two targets are left behind
+        assert_eq!(3, fs::read_dir(root).unwrap().count());
+
+        let mut op = RetargetSymlink::new(root.join("blue"), "green");
+        assert_eq!(ErrorKind::InvalidInput, op.execute().unwrap_err().kind());
+
+        fs::remove_dir_all(ROOT);
+    }
+
+    #[test]
+    fn retarget_symlink_paths_works() {
+        let mut op = RetargetSymlink::new("current", "/srv/green");
+        assert_eq!(
+            vec![Path::new("current"), Path::new("/srv/green")],
+            op.paths_mut()
+        );
+
+        let mut op = RetargetSymlink::new("current", "green");
+        assert_eq!(vec![Path::new("current")], op.paths_mut());
+    }
+}
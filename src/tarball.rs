@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tar::Builder;
+
+use crate::rewrite::FileBackup;
+use crate::{file_size, Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Archives a directory into a gzip-compressed tarball
+///
+/// The tarball is an explicit snapshot, independent of the backups used for rollback. Entries are stored relative to the source dir.
+/// An existing file at the output path is backed up and overwritten. On rollback the tarball is removed and any previous file restored
+pub struct SnapshotToTar {
+    source: PathBuf,
+    file: FileBackup,
+    written: bool,
+}
+
+impl SnapshotToTar {
+    /// Constructs a new SnapshotToTar operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>, U: AsRef<Path>>(
+        source: S,
+        output: T,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            source: source.as_ref().into(),
+            file: FileBackup::new(output, temp_dir),
+            written: false,
+        }
+    }
+
+    fn write_archive(&self) -> io::Result<()> {
+        let encoder = GzEncoder::new(File::create(self.file.path())?, Compression::default());
+        let mut builder = Builder::new(encoder);
+
+        builder.append_dir_all(".", &self.source)?;
+        builder.into_inner()?.finish()?.sync_all()
+    }
+}
+
+impl RollbackableOperation for SnapshotToTar {
+    fn execute(&mut self) -> io::Result<()> {
+        if !self.source.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", self.source.display()),
+            ));
+        }
+
+        if self.file.path().exists() {
+            self.file.create()?;
+        }
+
+        self.written = true;
+        self.write_archive()
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.revert(self.written)
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(file_size(self.file.path()))]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        file_size(self.file.path()).unwrap_or(0)
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.revert_steps(self.written))
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = self.file.paths_mut();
+        paths.push(&mut self.source);
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("output", SpecValue::Path(self.file.path().into())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(SnapshotToTar::new(
+            &self.source,
+            self.file.path(),
+            self.file.temp_dir(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::fs;
+    use tar::Archive;
+
+    const SOURCE: &str = "./snapshot_to_tar";
+    const OUTPUT: &str = "./snapshot_to_tar.tar.gz";
+    const EXTRACTED: &str = "./snapshot_to_tar_extracted";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn snapshot_to_tar_works() {
+        let source = Path::new(SOURCE);
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), "Hello").unwrap();
+        fs::write(source.join("nested/b.txt"), "World").unwrap();
+        fs::write(OUTPUT, "previous archive").unwrap();
+
+        let mut op = SnapshotToTar::new(SOURCE, OUTPUT, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+
+        let decoder = GzDecoder::new(File::open(OUTPUT).unwrap());
+        Archive::new(decoder).unpack(EXTRACTED).unwrap();
+
+        let extracted = Path::new(EXTRACTED);
+        assert_eq!(
+            "Hello",
+            fs::read_to_string(extracted.join("a.txt")).unwrap()
+        );
+        assert_eq!(
+            "World",
+            fs::read_to_string(extracted.join("nested/b.txt")).unwrap()
+        );
+        assert_eq!(2, fs::read_dir(extracted).unwrap().count());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("previous archive", fs::read_to_string(OUTPUT).unwrap());
+
+        fs::remove_file(OUTPUT);
+
+        let mut op = SnapshotToTar::new(SOURCE, OUTPUT, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(OUTPUT).exists());
+
+        fs::remove_dir_all(SOURCE);
+        fs::remove_dir_all(EXTRACTED);
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use handlebars::{no_escape, Handlebars};
+use serde_json::Value;
+
+use crate::rewrite::FileBackup;
+use crate::{Change, RollbackableOperation, SpecValue, UndoStep};
+
+/// Writes a file rendered from a [Handlebars](https://docs.rs/handlebars/6/handlebars/) template like `port = {{port}}`
+///
+/// Values from the context are substituted as is, without HTML escaping. The template is rendered before anything is
+/// touched, an invalid template or a value missing from the context fails with `ErrorKind::InvalidData`. An existing file at the output path
+/// is backed up and overwritten. On rollback the file is removed and any previous file restored
+pub struct RenderTemplate {
+    template: String,
+    context: Value,
+    file: FileBackup,
+}
+
+impl RenderTemplate {
+    /// Constructs a new RenderTemplate operation
+    pub fn new<S: AsRef<str>, T: AsRef<Path>, U: AsRef<Path>>(
+        template: S,
+        context: Value,
+        output: T,
+        temp_dir: U,
+    ) -> Self {
+        Self {
+            template: template.as_ref().into(),
+            context,
+            file: FileBackup::new(output, temp_dir),
+        }
+    }
+
+    fn render(&self) -> io::Result<String> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(no_escape);
+        handlebars.set_strict_mode(true);
+
+        handlebars
+            .render_template(&self.template, &self.context)
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Cannot render {}: {}", self.file.path().display(), e),
+                )
+            })
+    }
+}
+
+impl RollbackableOperation for RenderTemplate {
+    fn execute(&mut self) -> io::Result<()> {
+        let rendered = self.render()?;
+
+        if self.file.path().exists() {
+            self.file.create()?;
+        }
+
+        self.file.write(rendered.as_bytes())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        self.file.revert(self.file.written().is_some())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.file.set_temp_dir(temp_dir);
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.file.set_backup_store(store);
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.file.set_backup_buffer_size(size);
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.file.can_rollback()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![self.file.change(self.file.written())]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        self.file.backup_path()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.file.written().unwrap_or(0)
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(self.file.revert_steps(self.file.written().is_some()))
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.file.paths_mut()
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("template", SpecValue::Text(self.template.clone())),
+            ("context", SpecValue::Text(self.context.to_string())),
+            ("output", SpecValue::Path(self.file.path().into())),
+            ("temp_dir", SpecValue::Path(self.file.temp_dir().into())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(RenderTemplate::new(
+            &self.template,
+            self.context.clone(),
+            self.file.path(),
+            self.file.temp_dir(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    const OUTPUT: &str = "./render_template.conf";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn render_template_works() {
+        let context = json!({ "name": "web <1>", "port": 8080 });
+
+        let mut op = RenderTemplate::new(
+            "name = {{name}}\nport = {{port}}\n",
+            context.clone(),
+            OUTPUT,
+            TEMP_DIR,
+        );
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "name = web <1>\nport = 8080\n",
+            fs::read_to_string(OUTPUT).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(OUTPUT).exists());
+
+        fs::write(OUTPUT, "previous").unwrap();
+
+        let mut op = RenderTemplate::new("name = {{name}}", context.clone(), OUTPUT, TEMP_DIR);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!("name = web <1>", fs::read_to_string(OUTPUT).unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("previous", fs::read_to_string(OUTPUT).unwrap());
+
+        let mut op = RenderTemplate::new("{{missing}}", context, OUTPUT, TEMP_DIR);
+        assert_eq!(ErrorKind::InvalidData, op.execute().unwrap_err().kind());
+        assert_eq!("previous", fs::read_to_string(OUTPUT).unwrap());
+
+        fs::remove_file(OUTPUT);
+    }
+}
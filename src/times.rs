@@ -0,0 +1,104 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+
+use crate::{Change, ChangeKind, RollbackableOperation, SpecValue};
+
+/// Copies the access and modification times of a reference file onto a target file
+pub struct CopyTimes {
+    target: PathBuf,
+    reference: PathBuf,
+    original_times: Option<(FileTime, FileTime)>,
+}
+
+impl CopyTimes {
+    /// Constructs a new CopyTimes operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(target: S, reference: T) -> Self {
+        Self {
+            target: target.as_ref().into(),
+            reference: reference.as_ref().into(),
+            original_times: None,
+        }
+    }
+}
+
+fn times(path: &Path) -> io::Result<(FileTime, FileTime)> {
+    let metadata = fs::metadata(path)?;
+
+    Ok((
+        FileTime::from_last_access_time(&metadata),
+        FileTime::from_last_modification_time(&metadata),
+    ))
+}
+
+impl RollbackableOperation for CopyTimes {
+    fn execute(&mut self) -> io::Result<()> {
+        let (atime, mtime) = times(&self.reference)?;
+
+        self.original_times = Some(times(&self.target)?);
+        filetime::set_file_times(&self.target, atime, mtime)
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        match self.original_times {
+            Some((atime, mtime)) => filetime::set_file_times(&self.target, atime, mtime),
+            None => Ok(()),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        vec![Change::new(ChangeKind::Modified, &self.target)]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![&mut self.target, &mut self.reference]
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("target", SpecValue::Path(self.target.clone())),
+            ("reference", SpecValue::Path(self.reference.clone())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(CopyTimes::new(&self.target, &self.reference)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "./copy_times_target.txt";
+    const REFERENCE: &str = "./copy_times_reference.txt";
+
+    fn mtime(path: &str) -> i64 {
+        FileTime::from_last_modification_time(&fs::metadata(path).unwrap()).unix_seconds()
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn copy_times_works() {
+        fs::write(TARGET, "Hello").unwrap();
+        fs::write(REFERENCE, "World").unwrap();
+
+        let reference_time = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_times(REFERENCE, reference_time, reference_time).unwrap();
+        let original = mtime(TARGET);
+
+        let mut op = CopyTimes::new(TARGET, REFERENCE);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(mtime(REFERENCE), mtime(TARGET));
+        assert_eq!(1_000_000_000, mtime(TARGET));
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(original, mtime(TARGET));
+
+        fs::remove_file(TARGET);
+        fs::remove_file(REFERENCE);
+    }
+}
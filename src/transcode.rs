@@ -0,0 +1,158 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+/// Converts the text encoding of a file
+///
+/// By default invalid sequences in the source and characters the target encoding cannot represent are an `Error` of
+/// kind `InvalidData`. Byte order marks are treated as regular content
+pub struct Transcode {
+    file: FileBackup,
+    from: &'static Encoding,
+    to: &'static Encoding,
+    replace_invalid: bool,
+}
+
+impl Transcode {
+    /// Constructs a new Transcode operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        from: &'static Encoding,
+        to: &'static Encoding,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            from,
+            to,
+            replace_invalid: false,
+        }
+    }
+
+    /// Replaces invalid sequences with U+FFFD and unmappable characters with numeric character references instead
+    /// of failing
+    pub fn with_replacement(mut self, replace_invalid: bool) -> Self {
+        self.replace_invalid = replace_invalid;
+        self
+    }
+
+    fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+        if self.replace_invalid {
+            return Ok(self.from.decode_without_bom_handling(bytes).0.into_owned());
+        }
+
+        match self
+            .from
+            .decode_without_bom_handling_and_without_replacement(bytes)
+        {
+            Some(text) => Ok(text.into_owned()),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid {} sequence", self.from.name()),
+            )),
+        }
+    }
+
+    fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        // encoding_rs only decodes UTF-16, so encode it by hand
+        if self.to == UTF_16LE {
+            return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+        }
+        if self.to == UTF_16BE {
+            return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+        }
+
+        let (bytes, _, unmappable) = self.to.encode(text);
+        if unmappable && !self.replace_invalid {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("text cannot be represented in {}", self.to.name()),
+            ));
+        }
+
+        Ok(bytes.into_owned())
+    }
+}
+
+impl Rewrite for Transcode {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let text = self.decode(&contents)?;
+        self.encode(&text).map(Some)
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(
+            Transcode::new(self.file.path(), self.file.temp_dir(), self.from, self.to)
+                .with_replacement(self.replace_invalid),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_8, WINDOWS_1252};
+    use std::fs;
+
+    const ROUND_TRIP_SOURCE: &str = "./transcode_round_trip.txt";
+    const INVALID_SOURCE: &str = "./transcode_invalid.txt";
+    const TEMP_DIR: &str = "./tmp/";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn transcode_round_trip_works() {
+        let legacy = b"caf\xe9 \x80 na\xefve".to_vec();
+        fs::write(ROUND_TRIP_SOURCE, &legacy).unwrap();
+
+        let mut to_utf8 = Transcode::new(ROUND_TRIP_SOURCE, TEMP_DIR, WINDOWS_1252, UTF_8);
+        assert_eq!((), to_utf8.execute().unwrap());
+        assert_eq!(
+            "café € naïve",
+            fs::read_to_string(ROUND_TRIP_SOURCE).unwrap()
+        );
+
+        let mut to_legacy = Transcode::new(ROUND_TRIP_SOURCE, TEMP_DIR, UTF_8, WINDOWS_1252);
+        assert_eq!((), to_legacy.execute().unwrap());
+        assert_eq!(legacy, fs::read(ROUND_TRIP_SOURCE).unwrap());
+
+        assert_eq!((), to_legacy.rollback().unwrap());
+        assert_eq!((), to_utf8.rollback().unwrap());
+        assert_eq!(legacy, fs::read(ROUND_TRIP_SOURCE).unwrap());
+
+        fs::remove_file(ROUND_TRIP_SOURCE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn transcode_invalid_policy_works() {
+        fs::write(INVALID_SOURCE, b"ok \xff").unwrap();
+
+        let mut strict = Transcode::new(INVALID_SOURCE, TEMP_DIR, UTF_8, WINDOWS_1252);
+        assert_eq!(ErrorKind::InvalidData, strict.execute().unwrap_err().kind());
+        assert_eq!(None, strict.backup_path());
+        assert_eq!((), strict.rollback().unwrap());
+        assert_eq!(b"ok \xff".to_vec(), fs::read(INVALID_SOURCE).unwrap());
+
+        let mut lenient =
+            Transcode::new(INVALID_SOURCE, TEMP_DIR, UTF_8, UTF_16LE).with_replacement(true);
+        assert_eq!((), lenient.execute().unwrap());
+        assert_eq!(
+            b"o\0k\0 \0\xfd\xff".to_vec(),
+            fs::read(INVALID_SOURCE).unwrap()
+        );
+
+        fs::remove_file(INVALID_SOURCE);
+    }
+}
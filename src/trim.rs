@@ -0,0 +1,136 @@
+use std::io;
+use std::path::Path;
+
+use crate::rewrite::{FileBackup, Rewrite};
+use crate::RollbackableOperation;
+
+/// Normalizes the whitespace of a text file
+///
+/// Can trim trailing whitespace from every line and collapse runs of blank lines into a single one.
+/// Line endings, including `\r\n`, and whether the file ends with a newline are preserved
+pub struct TrimWhitespace {
+    file: FileBackup,
+    trim_trailing: bool,
+    collapse_blank_lines: bool,
+}
+
+impl TrimWhitespace {
+    /// Constructs a new TrimWhitespace operation
+    pub fn new<S: AsRef<Path>, T: AsRef<Path>>(
+        source: S,
+        temp_dir: T,
+        trim_trailing: bool,
+        collapse_blank_lines: bool,
+    ) -> Self {
+        Self {
+            file: FileBackup::new(source, temp_dir),
+            trim_trailing,
+            collapse_blank_lines,
+        }
+    }
+
+    fn normalize(&self, contents: &[u8]) -> Vec<u8> {
+        let trailing_newline = contents.ends_with(b"\n");
+        let body = if trailing_newline {
+            &contents[..contents.len() - 1]
+        } else {
+            contents
+        };
+
+        let mut normalized = Vec::with_capacity(contents.len());
+        let mut previous_blank = None;
+
+        for line in body.split(|&byte| byte == b'\n') {
+            let (text, ending): (&[u8], &[u8]) = match line.strip_suffix(b"\r") {
+                Some(text) => (text, b"\r"),
+                None => (line, b""),
+            };
+            let blank = text.iter().all(u8::is_ascii_whitespace);
+
+            match previous_blank {
+                Some(true) if blank && self.collapse_blank_lines => continue,
+                Some(_) => normalized.push(b'\n'),
+                None => {}
+            }
+            previous_blank = Some(blank);
+
+            if self.trim_trailing {
+                let end = text
+                    .iter()
+                    .rposition(|byte| !byte.is_ascii_whitespace())
+                    .map_or(0, |i| i + 1);
+                normalized.extend_from_slice(&text[..end]);
+                normalized.extend_from_slice(ending);
+            } else {
+                normalized.extend_from_slice(line);
+            }
+        }
+
+        if trailing_newline {
+            normalized.push(b'\n');
+        }
+
+        normalized
+    }
+}
+
+impl Rewrite for TrimWhitespace {
+    fn file(&self) -> &FileBackup {
+        &self.file
+    }
+
+    fn file_mut(&mut self) -> &mut FileBackup {
+        &mut self.file
+    }
+
+    fn rewrite(&mut self, contents: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        Ok(Some(self.normalize(&contents)))
+    }
+
+    fn clone_rewrite(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(TrimWhitespace::new(
+            self.file.path(),
+            self.file.temp_dir(),
+            self.trim_trailing,
+            self.collapse_blank_lines,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str = "./trim_whitespace.txt";
+    const TEMP_DIR: &str = "./tmp/";
+    const DATA: &str = "fn main() {  \n\n\n \t\n    body();\t\r\n}  ";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn trim_whitespace_works() {
+        fs::write(SOURCE, DATA).unwrap();
+
+        let mut op = TrimWhitespace::new(SOURCE, TEMP_DIR, true, true);
+
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(
+            "fn main() {\n\n    body();\r\n}",
+            fs::read_to_string(SOURCE).unwrap()
+        );
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read_to_string(SOURCE).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+
+    #[test]
+    fn trim_whitespace_flags() {
+        let collapse = TrimWhitespace::new(SOURCE, TEMP_DIR, false, true);
+        let trim = TrimWhitespace::new(SOURCE, TEMP_DIR, true, false);
+
+        assert_eq!(b"a \n\nb\n".to_vec(), collapse.normalize(b"a \n\n\n\nb\n"));
+        assert_eq!(b"a\n\n\n\nb\n".to_vec(), trim.normalize(b"a \n\n \n\nb\n"));
+    }
+}
@@ -0,0 +1,333 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::backup::Backup;
+use crate::{copy_dir, ignore_not_found};
+
+/// A single step needed to undo an executed operation
+///
+/// Steps are produced by [RollbackableOperation::undo_steps](trait.RollbackableOperation.html#method.undo_steps) and can be exported with [Transaction::export_undo](struct.Transaction.html#method.export_undo)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoStep {
+    /// Removes a file
+    RemoveFile(PathBuf),
+    /// Removes a directory and its contents
+    RemoveDir(PathBuf),
+    /// Creates a directory and its missing parents
+    CreateDir(PathBuf),
+    /// Restores the content of a file from a backup file
+    RestoreFile {
+        /// Path to the backup file
+        backup: PathBuf,
+        /// Path to the restored file
+        path: PathBuf,
+    },
+    /// Replaces a directory with a copy of a backup directory
+    RestoreDir {
+        /// Path to the backup directory
+        backup: PathBuf,
+        /// Path to the restored directory
+        path: PathBuf,
+    },
+    /// Renames a file or directory
+    Rename {
+        /// Current path
+        from: PathBuf,
+        /// New path
+        to: PathBuf,
+    },
+    /// Sets the Unix mode of a file or directory
+    SetMode {
+        /// Path to the file or directory
+        path: PathBuf,
+        /// Mode to set
+        mode: u32,
+    },
+    /// Sets the Unix owner and group of a file or directory
+    SetOwner {
+        /// Path to the file or directory
+        path: PathBuf,
+        /// User id to set
+        uid: u32,
+        /// Group id to set
+        gid: u32,
+    },
+}
+
+impl UndoStep {
+    /// Performs the step
+    pub fn apply(&self) -> io::Result<()> {
+        match self {
+            UndoStep::RemoveFile(path) => fs::remove_file(path),
+            UndoStep::RemoveDir(path) => fs::remove_dir_all(path),
+            UndoStep::CreateDir(path) => fs::create_dir_all(path),
+            UndoStep::RestoreFile { backup, path } => Backup::existing_file(backup).restore(path),
+            UndoStep::RestoreDir { backup, path } => Backup::existing_dir(backup).restore(path),
+            UndoStep::Rename { from, to } => fs::rename(from, to),
+            UndoStep::SetMode { path, mode } => set_mode(path, *mode),
+            UndoStep::SetOwner { path, uid, gid } => set_owner(path, *uid, *gid),
+        }
+    }
+
+    fn backup_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            UndoStep::RestoreFile { backup, .. } | UndoStep::RestoreDir { backup, .. } => {
+                Some(backup)
+            }
+            _ => None,
+        }
+    }
+
+    fn to_line(&self) -> io::Result<String> {
+        let fields = match self {
+            UndoStep::RemoveFile(path) => vec!["RemoveFile".into(), encode(path)?],
+            UndoStep::RemoveDir(path) => vec!["RemoveDir".into(), encode(path)?],
+            UndoStep::CreateDir(path) => vec!["CreateDir".into(), encode(path)?],
+            UndoStep::RestoreFile { backup, path } => {
+                vec!["RestoreFile".into(), encode(backup)?, encode(path)?]
+            }
+            UndoStep::RestoreDir { backup, path } => {
+                vec!["RestoreDir".into(), encode(backup)?, encode(path)?]
+            }
+            UndoStep::Rename { from, to } => vec!["Rename".into(), encode(from)?, encode(to)?],
+            UndoStep::SetMode { path, mode } => {
+                vec!["SetMode".into(), encode(path)?, format!("{:o}", mode)]
+            }
+            UndoStep::SetOwner { path, uid, gid } => vec![
+                "SetOwner".into(),
+                encode(path)?,
+                uid.to_string(),
+                gid.to_string(),
+            ],
+        };
+
+        Ok(fields.join("\t"))
+    }
+
+    fn from_line(line: &str) -> io::Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid undo step: {}", line),
+            )
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        let step = match fields.as_slice() {
+            ["RemoveFile", path] => UndoStep::RemoveFile(path.into()),
+            ["RemoveDir", path] => UndoStep::RemoveDir(path.into()),
+            ["CreateDir", path] => UndoStep::CreateDir(path.into()),
+            ["RestoreFile", backup, path] => UndoStep::RestoreFile {
+                backup: backup.into(),
+                path: path.into(),
+            },
+            ["RestoreDir", backup, path] => UndoStep::RestoreDir {
+                backup: backup.into(),
+                path: path.into(),
+            },
+            ["Rename", from, to] => UndoStep::Rename {
+                from: from.into(),
+                to: to.into(),
+            },
+            ["SetMode", path, mode] => UndoStep::SetMode {
+                path: path.into(),
+                mode: u32::from_str_radix(mode, 8).map_err(|_| invalid())?,
+            },
+            ["SetOwner", path, uid, gid] => UndoStep::SetOwner {
+                path: path.into(),
+                uid: uid.parse().map_err(|_| invalid())?,
+                gid: gid.parse().map_err(|_| invalid())?,
+            },
+            _ => return Err(invalid()),
+        };
+
+        Ok(step)
+    }
+}
+
+fn encode(path: &Path) -> io::Result<String> {
+    match path.to_str() {
+        Some(s) if !s.contains('\t') && !s.contains('\n') => Ok(s.to_string()),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Path cannot be exported: {}", path.display()),
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "Setting the mode is only supported on Unix",
+    ))
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    std::os::unix::fs::chown(path, Some(uid), Some(gid))
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _uid: u32, _gid: u32) -> io::Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "Setting the owner is only supported on Unix",
+    ))
+}
+
+fn backups_dir(path: &Path) -> PathBuf {
+    let mut dir = path.as_os_str().to_owned();
+    dir.push(".backups");
+    dir.into()
+}
+
+/// Writes the steps to `path`, copying every referenced backup next to it so it outlives the operations
+pub(crate) fn export_undo(path: &Path, mut steps: Vec<UndoStep>) -> io::Result<()> {
+    let dir = backups_dir(path);
+
+    ignore_not_found(fs::remove_dir_all(&dir))?;
+    fs::create_dir_all(&dir)?;
+
+    for (i, step) in steps.iter_mut().enumerate() {
+        if let Some(backup) = step.backup_mut() {
+            let copy = dir.join(i.to_string());
+
+            if backup.is_dir() {
+                copy_dir(&backup, &copy)?;
+            } else {
+                fs::copy(&backup, &copy)?;
+            }
+
+            *backup = copy;
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+
+    for step in &steps {
+        writeln!(file, "{}", step.to_line()?)?;
+    }
+
+    file.sync_all()
+}
+
+/// Undoes a transaction from the data written by [Transaction::export_undo](struct.Transaction.html#method.export_undo)
+///
+/// The steps are performed in order and the exported data is removed once all of them succeed
+pub fn replay_undo<S: AsRef<Path>>(path: S) -> io::Result<()> {
+    let path = path.as_ref();
+    let file = OpenOptions::new().read(true).open(path)?;
+
+    let mut steps = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+
+        if !line.is_empty() {
+            steps.push(UndoStep::from_line(&line)?);
+        }
+    }
+
+    for step in &steps {
+        step.apply()?;
+    }
+
+    ignore_not_found(fs::remove_dir_all(backups_dir(path)))?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RollbackableOperation, Transaction};
+
+    use super::*;
+
+    const CREATED: &str = "./undo_created.txt";
+    const WRITTEN: &str = "./undo_written.txt";
+    const DELETED: &str = "./undo_deleted.txt";
+    const MOVED: &str = "./undo_moved.txt";
+    const MOVED_DEST: &str = "./undo_moved_dest.txt";
+    const UNDO_FILE: &str = "./tmp/undo_replay/undo.txt";
+    const TEMP_DIR: &str = "./tmp/undo_replay";
+
+    fn setup() -> io::Result<()> {
+        fs::write(WRITTEN, b"Yellow World")?;
+        fs::write(DELETED, b"Hello World")?;
+        fs::write(MOVED, b"Moved World")
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn replay_undo_works() {
+        assert_eq!((), setup().unwrap());
+
+        let mut tr = Transaction::new()
+            .create_file(CREATED)
+            .write_file(WRITTEN, TEMP_DIR, b"Hello".to_vec())
+            .delete_file(DELETED, TEMP_DIR)
+            .move_file(MOVED, MOVED_DEST);
+
+        assert_eq!((), tr.execute().unwrap());
+        assert_eq!((), tr.export_undo(UNDO_FILE).unwrap());
+        drop(tr);
+
+        assert_eq!((), replay_undo(UNDO_FILE).unwrap());
+        assert!(!Path::new(CREATED).exists());
+        assert_eq!(b"Yellow World".to_vec(), fs::read(WRITTEN).unwrap());
+        assert_eq!(b"Hello World".to_vec(), fs::read(DELETED).unwrap());
+        assert_eq!(b"Moved World".to_vec(), fs::read(MOVED).unwrap());
+        assert!(!Path::new(MOVED_DEST).exists());
+        assert!(!Path::new(UNDO_FILE).exists());
+
+        fs::remove_file(WRITTEN);
+        fs::remove_file(DELETED);
+        fs::remove_file(MOVED);
+        fs::remove_dir_all(TEMP_DIR);
+    }
+
+    #[test]
+    fn undo_step_lines_round_trip() {
+        let steps = vec![
+            UndoStep::RemoveFile("./a.txt".into()),
+            UndoStep::RemoveDir("./a".into()),
+            UndoStep::CreateDir("./a/b".into()),
+            UndoStep::RestoreFile {
+                backup: "./tmp/b".into(),
+                path: "./b.txt".into(),
+            },
+            UndoStep::RestoreDir {
+                backup: "./tmp/c".into(),
+                path: "./c".into(),
+            },
+            UndoStep::Rename {
+                from: "./d".into(),
+                to: "./e".into(),
+            },
+            UndoStep::SetMode {
+                path: "./f".into(),
+                mode: 0o644,
+            },
+            UndoStep::SetOwner {
+                path: "./g".into(),
+                uid: 1000,
+                gid: 100,
+            },
+        ];
+
+        for step in steps {
+            assert_eq!(step, UndoStep::from_line(&step.to_line().unwrap()).unwrap());
+        }
+    }
+}
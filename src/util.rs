@@ -0,0 +1,164 @@
+//! Helpers shared by operations that are also useful on their own
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns whether the file at `path` contains exactly `data`
+///
+/// Compares raw bytes, so content that is not valid UTF-8 is handled correctly. The file is streamed in chunks
+/// and the comparison stops at the first difference
+pub fn contents_equal(path: &Path, data: &[u8]) -> io::Result<bool> {
+    if fs::metadata(path)?.len() != data.len() as u64 {
+        return Ok(false);
+    }
+
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, File::open(path)?);
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut offset = 0;
+
+    loop {
+        let read = read_full(&mut reader, &mut buffer)?;
+
+        if read == 0 {
+            return Ok(offset == data.len());
+        }
+
+        if offset + read > data.len() || buffer[..read] != data[offset..offset + read] {
+            return Ok(false);
+        }
+
+        offset += read;
+    }
+}
+
+/// Returns whether the files at `a` and `b` have identical contents
+///
+/// Like [contents_equal](fn.contents_equal.html), both files are streamed and compared as raw bytes
+pub fn files_equal(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut a = BufReader::with_capacity(CHUNK_SIZE, File::open(a)?);
+    let mut b = BufReader::with_capacity(CHUNK_SIZE, File::open(b)?);
+    let mut buffer_a = vec![0; CHUNK_SIZE];
+    let mut buffer_b = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read_a = read_full(&mut a, &mut buffer_a)?;
+        let read_b = read_full(&mut b, &mut buffer_b)?;
+
+        if buffer_a[..read_a] != buffer_b[..read_b] {
+            return Ok(false);
+        }
+
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Returns whether a `/`-separated relative path matches a glob pattern
+///
+/// `*` matches any run of characters within a path component, `?` a single one and `**` any run of components,
+/// so `**/*.rs` matches `.rs` files at any depth. A pattern without `/` is matched against the last component only
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let text = match pattern.contains('/') {
+        true => path,
+        false => path.rsplit('/').next().unwrap_or(path),
+    };
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] if glob_match_chars(rest, text) => true,
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_match_chars(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match_chars(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != '/') && glob_match_chars(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match_chars(rest, &text[1..]),
+    }
+}
+
+/// Reads until `buffer` is full or the reader is exhausted
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINARY: &[u8] = &[0xff, 0xfe, 0x00, 0xc3, 0x28, 0x80];
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn contents_equal_binary_works() {
+        const SOURCE: &str = "./contents_equal_binary.bin";
+        const OTHER: &str = "./contents_equal_binary_other.bin";
+
+        fs::write(SOURCE, BINARY).unwrap();
+        assert!(contents_equal(Path::new(SOURCE), BINARY).unwrap());
+
+        // Both differ from BINARY only in invalid UTF-8 bytes, which a lossy comparison maps to U+FFFD
+        assert!(!contents_equal(Path::new(SOURCE), &[0xfe, 0xfe, 0x00, 0xc3, 0x28, 0x80]).unwrap());
+        assert!(!contents_equal(Path::new(SOURCE), &BINARY[..5]).unwrap());
+
+        fs::write(OTHER, [0xfe, 0xff, 0x00, 0xc3, 0x28, 0x80]).unwrap();
+        assert!(!files_equal(Path::new(SOURCE), Path::new(OTHER)).unwrap());
+        fs::write(OTHER, BINARY).unwrap();
+        assert!(files_equal(Path::new(SOURCE), Path::new(OTHER)).unwrap());
+
+        fs::remove_file(SOURCE);
+        fs::remove_file(OTHER);
+    }
+
+    #[test]
+    fn glob_matches_works() {
+        assert!(glob_matches("*.rs", "src/lib.rs"));
+        assert!(!glob_matches("*.rs", "src/lib.rst"));
+        assert!(glob_matches("src/*.rs", "src/lib.rs"));
+        assert!(!glob_matches("src/*.rs", "src/nested/lib.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/nested/deep/lib.rs"));
+        assert!(glob_matches("**", "any/path"));
+        assert!(glob_matches("file_?.txt", "file_1.txt"));
+        assert!(!glob_matches("file_?.txt", "file_10.txt"));
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn contents_equal_large_works() {
+        const SOURCE: &str = "./contents_equal_large.bin";
+
+        let mut data: Vec<u8> = (0..CHUNK_SIZE * 3 + 17).map(|i| (i % 251) as u8).collect();
+        fs::write(SOURCE, &data).unwrap();
+        assert!(contents_equal(Path::new(SOURCE), &data).unwrap());
+
+        *data.last_mut().unwrap() ^= 0xff;
+        assert!(!contents_equal(Path::new(SOURCE), &data).unwrap());
+
+        fs::remove_file(SOURCE);
+    }
+}
@@ -1,17 +1,42 @@
-use std::io::{self, Read, Write};
+use std::io::{self, Error, ErrorKind, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{
-    fs::OpenOptions,
+    fs::{self, OpenOptions},
     path::{Path, PathBuf},
 };
 
-use crate::{RollbackableOperation, SingleFileOperation};
+#[cfg(feature = "hash")]
+use std::io::Read;
+
+use crate::backup::Backup;
+#[cfg(feature = "hash")]
+use crate::hash_reader;
+use crate::util::contents_equal;
+use crate::{
+    file_size, ignore_not_found, non_empty_path, preserve_metadata, Change, ChangeKind, DropPolicy,
+    DropState, RollbackableOperation, SingleFileOperation, SpecValue, UndoStep,
+    DEFAULT_BACKUP_BUFFER_SIZE,
+};
 
 /// Writes data to a file
 pub struct WriteFile {
     source: PathBuf,
     temp_dir: PathBuf,
+    backup_dir: Option<PathBuf>,
     backup_path: PathBuf,
+    backup_store: Option<PathBuf>,
+    backup_buffer_size: usize,
     data: Vec<u8>,
+    #[cfg(feature = "hash")]
+    expected_hash: Option<String>,
+    dated_backup: bool,
+    rotation: usize,
+    rotated: Vec<usize>,
+    dropped_rotation: Option<Backup>,
+    if_changed: bool,
+    require_backup: bool,
+    skipped: bool,
+    drop_state: DropState,
 }
 
 impl WriteFile {
@@ -20,33 +45,429 @@ impl WriteFile {
         Self {
             source: source.as_ref().into(),
             temp_dir: temp_dir.as_ref().into(),
+            backup_dir: None,
             backup_path: PathBuf::new(),
-            data: data,
+            backup_store: None,
+            backup_buffer_size: DEFAULT_BACKUP_BUFFER_SIZE,
+            drop_state: DropState::default(),
+            data,
+            #[cfg(feature = "hash")]
+            expected_hash: None,
+            dated_backup: false,
+            rotation: 0,
+            rotated: vec![],
+            dropped_rotation: None,
+            if_changed: false,
+            require_backup: false,
+            skipped: false,
+        }
+    }
+
+    /// Creates the backup in `dir` instead of the temp dir
+    ///
+    /// Unlike the temp dir, it is kept when the operation is added to a [scoped](struct.Transaction.html#method.scoped) transaction
+    pub fn with_backup_dir<S: AsRef<Path>>(mut self, dir: S) -> Self {
+        self.backup_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Skips the write, and the backup, when the file already contains exactly the data
+    pub fn with_if_changed(mut self, if_changed: bool) -> Self {
+        self.if_changed = if_changed;
+        self
+    }
+
+    /// Refuses to write unless the backup of the original file can be read back in full
+    ///
+    /// After the backup is created it is read and compared against the size of the original. An incomplete or missing
+    /// backup fails the operation with `ErrorKind::Other` before the file is touched, and rollback leaves the file as is.
+    /// Has no effect with [rotation](#method.with_rotation) or [dated backups](#method.with_dated_backup), which rename the original instead
+    pub fn with_require_backup(mut self, require_backup: bool) -> Self {
+        self.require_backup = require_backup;
+        self
+    }
+
+    fn verify_backup(&self, original_len: u64) -> io::Result<()> {
+        let backup_len = match self.backup_path() {
+            Some(backup_path) => io::copy(&mut fs::File::open(backup_path)?, &mut io::sink())?,
+            None => {
+                return Err(Error::other(format!(
+                    "No backup of {} was created",
+                    self.source.display()
+                )))
+            }
+        };
+
+        if backup_len == original_len {
+            Ok(())
+        } else {
+            Err(Error::other(format!(
+                "Backup of {} is incomplete: {} of {} bytes",
+                self.source.display(),
+                backup_len,
+                original_len
+            )))
+        }
+    }
+
+    /// Rotates the original file through numbered siblings instead of backing it up in the temp dir
+    ///
+    /// Like `logrotate`, `<name>.1` is shifted to `<name>.2` and so on, then the file becomes `<name>.1`, keeping at most `keep` of them.
+    /// The oldest sibling shifted out is backed up in the temp dir, so rollback can undo the whole rotation.
+    /// Siblings are kept after the operation is dropped. A `keep` of 0 disables rotation
+    pub fn with_rotation(mut self, keep: usize) -> Self {
+        self.rotation = keep;
+        self
+    }
+
+    fn rotation_path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            return self.source.clone();
+        }
+
+        let mut path = self.source.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.rotated.clear();
+
+        let oldest = self.rotation_path(self.rotation);
+        if oldest.exists() {
+            let backup_dir = self.get_backup_dir().unwrap_or(&self.temp_dir);
+            let backup = Backup::of_file(&oldest, backup_dir)?;
+            self.dropped_rotation = Some(backup);
+            fs::remove_file(&oldest)?;
+        }
+
+        for index in (0..self.rotation).rev() {
+            let from = self.rotation_path(index);
+
+            if from.exists() {
+                fs::rename(&from, self.rotation_path(index + 1))?;
+                self.rotated.push(index);
+            }
+        }
+
+        if self.rotated.last() == Some(&0) {
+            self.set_backup_path(self.rotation_path(1));
+        }
+
+        Ok(())
+    }
+
+    fn unrotate(&self) -> io::Result<()> {
+        if self.rotated.last() != Some(&0) {
+            ignore_not_found(fs::remove_file(&self.source))?;
+        }
+
+        for &index in self.rotated.iter().rev() {
+            fs::rename(self.rotation_path(index + 1), self.rotation_path(index))?;
+        }
+
+        match &self.dropped_rotation {
+            Some(backup) => backup.restore(self.rotation_path(self.rotation)),
+            None => Ok(()),
+        }
+    }
+
+    /// Renames the original file to a timestamped sibling instead of backing it up in the temp dir
+    ///
+    /// The sibling is named `<name>.YYYYMMDD-HHMMSS.bak` using the current UTC time, with a counter appended to the
+    /// timestamp on collisions. It is renamed back on rollback and kept after the operation is dropped
+    pub fn with_dated_backup(mut self, dated_backup: bool) -> Self {
+        self.dated_backup = dated_backup;
+        self
+    }
+
+    fn create_dated_backup(&mut self) -> io::Result<()> {
+        let name = self
+            .source
+            .file_name()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Source has no file name"))?
+            .to_string_lossy()
+            .into_owned();
+        let stamp = utc_timestamp();
+
+        let mut backup_path = self
+            .source
+            .with_file_name(format!("{}.{}.bak", name, stamp));
+        let mut counter = 1;
+        while backup_path.exists() {
+            backup_path = self
+                .source
+                .with_file_name(format!("{}.{}-{}.bak", name, stamp, counter));
+            counter += 1;
+        }
+
+        fs::rename(&self.source, &backup_path)?;
+        self.set_backup_path(backup_path);
+
+        Ok(())
+    }
+
+    /// Sets the policy applied when the operation is dropped
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_state.set_policy(policy);
+    }
+
+    /// Verifies the written data against an expected SHA-256 digest
+    ///
+    /// After writing, the data is read back from the file and hashed. A mismatch fails the operation with `ErrorKind::InvalidData`
+    #[cfg(feature = "hash")]
+    pub fn with_expected_hash(mut self, hex: String) -> Self {
+        self.expected_hash = Some(hex.to_lowercase());
+        self
+    }
+
+    #[cfg(feature = "hash")]
+    fn verify_hash(&self) -> io::Result<()> {
+        let expected = match &self.expected_hash {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+
+        let file = OpenOptions::new().read(true).open(self.get_path())?;
+        let actual = hash_reader(file.take(self.data.len() as u64))?;
+
+        if &actual == expected {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Hash mismatch: expected {}, found {}", expected, actual),
+            ))
         }
     }
 }
 
 impl RollbackableOperation for WriteFile {
     fn execute(&mut self) -> io::Result<()> {
-        self.create_backup_file()?;
+        if self.if_changed {
+            // A missing file always differs from the data
+            let unchanged = match contents_equal(&self.source, &self.data) {
+                Err(e) if e.kind() == ErrorKind::NotFound => false,
+                result => result?,
+            };
 
-        OpenOptions::new()
+            if unchanged {
+                self.skipped = true;
+                return Ok(());
+            }
+        }
+
+        // Both modes move the original away and write a new file, which has to inherit its mode
+        let original = match self.rotation > 0 || self.dated_backup {
+            true => fs::metadata(&self.source).ok(),
+            false => None,
+        };
+
+        if self.rotation > 0 {
+            self.rotate()?;
+        } else if self.dated_backup {
+            self.create_dated_backup()?;
+        } else if self.require_backup {
+            let original_len = fs::metadata(&self.source)?.len();
+            self.create_backup_file()?;
+
+            // Rollback must not restore an incomplete backup over the untouched original
+            if let Err(e) = self.verify_backup(original_len) {
+                self.dispose()?;
+                self.set_backup_path("");
+                self.skipped = true;
+                return Err(e);
+            }
+        } else {
+            self.create_backup_file()?;
+        }
+        self.drop_state.executed();
+
+        let mut options = OpenOptions::new();
+        options
             .write(true)
-            .open(self.get_path())?
-            .write_all(&self.data)
+            .create(self.dated_backup || self.rotation > 0);
+
+        #[cfg(unix)]
+        if let Some(original) = &original {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+            options.mode(original.permissions().mode() & 0o7777);
+        }
+
+        let mut file = options.open(self.get_path())?;
+
+        // Applied before writing, so the data is never readable under a looser mode
+        if let Some(original) = &original {
+            preserve_metadata(original, self.get_path())?;
+        }
+
+        file.write_all(&self.data)?;
+
+        #[cfg(feature = "hash")]
+        self.verify_hash()?;
+
+        Ok(())
     }
 
     fn rollback(&self) -> io::Result<()> {
-        let mut buffer = Vec::<u8>::new();
-        let mut backup_file = OpenOptions::new().read(true).open(self.get_backup_path())?;
+        if self.skipped {
+            return Ok(());
+        }
 
-        backup_file.read_to_end(&mut buffer)?;
+        self.drop_state.rolled_back();
 
-        OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(self.get_path())?
-            .write_all(&buffer)
+        if self.rotation > 0 {
+            return self.unrotate();
+        }
+
+        match self.backup_path() {
+            Some(backup_path) if self.dated_backup => fs::rename(backup_path, &self.source),
+            _ => Backup::existing_file(self.get_backup_path()).restore(self.get_path()),
+        }
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn set_backup_store(&mut self, store: &Path) {
+        self.backup_store = Some(store.into());
+    }
+
+    fn set_backup_buffer_size(&mut self, size: usize) {
+        self.backup_buffer_size = size;
+    }
+
+    fn can_rollback(&self) -> bool {
+        if self.rotation > 0 {
+            return self.dropped_rotation.as_ref().is_none_or(Backup::exists);
+        }
+
+        self.skipped || self.get_backup_path().exists()
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        // Rotated and dated siblings are the backup, but are kept after the operation is dropped
+        if self.rotation > 0 {
+            return self.dropped_rotation.iter().map(Backup::path).collect();
+        }
+
+        match self.dated_backup {
+            true => vec![],
+            false => self.backup_path().into_iter().collect(),
+        }
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        if self.skipped {
+            return vec![];
+        }
+
+        let size_before = file_size(self.get_backup_path());
+        let size_after = size_before.map(|size| size.max(self.data.len() as u64));
+
+        vec![Change::new(ChangeKind::Modified, &self.source).with_sizes(size_before, size_after)]
+    }
+
+    fn backup_path(&self) -> Option<&Path> {
+        non_empty_path(self.get_backup_path())
+    }
+
+    fn bytes_written(&self) -> u64 {
+        if self.skipped {
+            0
+        } else {
+            self.data.len() as u64
+        }
+    }
+
+    fn mark_committed(&mut self) {
+        self.drop_state.committed();
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        if self.rotation > 0 && !self.skipped {
+            let mut steps = vec![];
+
+            if self.rotated.last() != Some(&0) {
+                steps.push(UndoStep::RemoveFile(self.source.clone()));
+            }
+            for &index in self.rotated.iter().rev() {
+                steps.push(UndoStep::Rename {
+                    from: self.rotation_path(index + 1),
+                    to: self.rotation_path(index),
+                });
+            }
+            if let Some(backup) = &self.dropped_rotation {
+                steps.push(UndoStep::RestoreFile {
+                    backup: backup.path().into(),
+                    path: self.rotation_path(self.rotation),
+                });
+            }
+
+            return Some(steps);
+        }
+
+        Some(
+            self.backup_path()
+                .map(|backup| {
+                    if self.dated_backup {
+                        UndoStep::Rename {
+                            from: backup.into(),
+                            to: self.source.clone(),
+                        }
+                    } else {
+                        UndoStep::RestoreFile {
+                            backup: backup.into(),
+                            path: self.source.clone(),
+                        }
+                    }
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        let backup_dir = self.get_backup_dir().unwrap_or(&self.temp_dir);
+
+        vec![
+            (backup_dir.into(), file_size(&self.source).unwrap_or(0)),
+            (self.source.clone(), self.data.len() as u64),
+        ]
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.source, &mut self.temp_dir];
+        if let Some(backup_dir) = &mut self.backup_dir {
+            paths.push(backup_dir);
+        }
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("source", SpecValue::Path(self.source.clone())),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+            ("data", SpecValue::Bytes(self.data.len())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        let mut op = WriteFile::new(&self.source, &self.temp_dir, self.data.clone());
+        #[cfg(feature = "hash")]
+        {
+            op.expected_hash = self.expected_hash.clone();
+        }
+        op.dated_backup = self.dated_backup;
+        op.rotation = self.rotation;
+        op.if_changed = self.if_changed;
+        op.require_backup = self.require_backup;
+        op.backup_dir = self.backup_dir.clone();
+        op.drop_state = self.drop_state.reset();
+
+        Some(Box::new(op))
     }
 }
 
@@ -66,13 +487,231 @@ impl SingleFileOperation for WriteFile {
     fn get_temp_dir(&self) -> &Path {
         &self.temp_dir
     }
+
+    fn get_backup_dir(&self) -> Option<&Path> {
+        self.backup_dir.as_deref()
+    }
+
+    fn get_backup_store(&self) -> Option<&Path> {
+        self.backup_store.as_deref()
+    }
+
+    fn backup_buffer_size(&self) -> usize {
+        self.backup_buffer_size
+    }
+
+    fn dispose(&self) -> io::Result<()> {
+        if self.rotation > 0 {
+            match &self.dropped_rotation {
+                Some(backup) => backup.dispose(),
+                None => Ok(()),
+            }
+        } else if self.dated_backup {
+            Ok(())
+        } else {
+            Backup::existing_file(self.get_backup_path()).dispose()
+        }
+    }
+}
+
+/// Formats the current UTC time as `YYYYMMDD-HHMMSS`
+fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, time) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
 }
 
 impl Drop for WriteFile {
     fn drop(&mut self) {
-        match self.dispose() {
-            Err(e) => eprintln!("{}", e),
-            _ => {}
+        if self.drop_state.should_rollback() {
+            if let Err(e) = self.rollback() {
+                eprintln!("{}", e)
+            }
+        }
+
+        if let Err(e) = self.dispose() {
+            eprintln!("{}", e)
+        }
+    }
+}
+
+/// Writes the same data to many files in a single operation
+///
+/// Existing files are backed up into the temp dir and overwritten, missing ones are created. If a write fails, rollback only
+/// reverts the files written so far
+pub struct WriteMany {
+    paths: Vec<PathBuf>,
+    temp_dir: PathBuf,
+    data: Vec<u8>,
+    written: Vec<Option<Backup>>,
+}
+
+impl WriteMany {
+    /// Constructs a new WriteMany operation
+    pub fn new<S: AsRef<Path>>(paths: Vec<PathBuf>, temp_dir: S, data: Vec<u8>) -> Self {
+        Self {
+            paths,
+            temp_dir: temp_dir.as_ref().into(),
+            data,
+            written: vec![],
+        }
+    }
+
+    /// Returns the number of files written so far
+    pub fn written(&self) -> usize {
+        self.written.len()
+    }
+
+    /// Returns the files that did not exist before they were written
+    pub fn created(&self) -> Vec<&Path> {
+        self.written
+            .iter()
+            .zip(&self.paths)
+            .filter(|(backup, _)| backup.is_none())
+            .map(|(_, path)| path.as_path())
+            .collect()
+    }
+}
+
+impl RollbackableOperation for WriteMany {
+    fn execute(&mut self) -> io::Result<()> {
+        for i in self.written()..self.paths.len() {
+            let path = &self.paths[i];
+            let backup = match path.exists() {
+                true => Some(Backup::of_file(path, &self.temp_dir)?),
+                false => None,
+            };
+
+            // Recorded before writing, a failed write may already have truncated or created the file
+            self.written.push(backup);
+            fs::write(path, &self.data)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&self) -> io::Result<()> {
+        for (backup, path) in self.written.iter().zip(&self.paths).rev() {
+            match backup {
+                Some(backup) => backup.restore(path)?,
+                None => ignore_not_found(fs::remove_file(path))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_temp_dir(&mut self, temp_dir: &Path) {
+        self.temp_dir = temp_dir.into();
+    }
+
+    fn can_rollback(&self) -> bool {
+        self.written.iter().flatten().all(Backup::exists)
+    }
+
+    fn owned_backups(&self) -> Vec<&Path> {
+        self.written.iter().flatten().map(Backup::path).collect()
+    }
+
+    fn changes(&self) -> Vec<Change> {
+        let size = Some(self.data.len() as u64);
+
+        self.written
+            .iter()
+            .zip(&self.paths)
+            .map(|(backup, path)| match backup {
+                Some(backup) => Change::new(ChangeKind::Modified, path)
+                    .with_sizes(file_size(backup.path()), size),
+                None => Change::new(ChangeKind::Created, path).with_sizes(None, size),
+            })
+            .collect()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.written.len() as u64 * self.data.len() as u64
+    }
+
+    fn undo_steps(&self) -> Option<Vec<UndoStep>> {
+        Some(
+            self.written
+                .iter()
+                .zip(&self.paths)
+                .rev()
+                .map(|(backup, path)| match backup {
+                    Some(backup) => UndoStep::RestoreFile {
+                        backup: backup.path().into(),
+                        path: path.clone(),
+                    },
+                    None => UndoStep::RemoveFile(path.clone()),
+                })
+                .collect(),
+        )
+    }
+
+    fn space_required(&self) -> Vec<(PathBuf, u64)> {
+        let backups = self.paths.iter().filter_map(file_size).sum();
+        let mut required = vec![(self.temp_dir.clone(), backups)];
+
+        required.extend(
+            self.paths
+                .iter()
+                .map(|path| (path.clone(), self.data.len() as u64)),
+        );
+        required
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut paths = vec![&mut self.temp_dir];
+        paths.extend(self.paths.iter_mut());
+        paths
+    }
+
+    fn spec(&self) -> Vec<(&'static str, SpecValue)> {
+        vec![
+            ("files", SpecValue::Number(self.paths.len() as u64)),
+            ("temp_dir", SpecValue::Path(self.temp_dir.clone())),
+            ("data", SpecValue::Bytes(self.data.len())),
+        ]
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RollbackableOperation>> {
+        Some(Box::new(WriteMany::new(
+            self.paths.clone(),
+            &self.temp_dir,
+            self.data.clone(),
+        )))
+    }
+}
+
+impl Drop for WriteMany {
+    fn drop(&mut self) {
+        for backup in self.written.iter().flatten() {
+            if let Err(e) = backup.dispose() {
+                eprintln!("{}", e)
+            }
         }
     }
 }
@@ -114,4 +753,314 @@ mod tests {
 
         fs::remove_file(FILE_SOURCE);
     }
+
+    #[cfg(feature = "hash")]
+    const HASH_SOURCE: &str = "./write_file_hash.txt";
+
+    #[test]
+    #[cfg(feature = "hash")]
+    #[allow(unused_must_use)]
+    fn write_file_expected_hash_works() {
+        use crate::Transaction;
+
+        fs::write(HASH_SOURCE, INITIAL_DATA).expect("Unable to write file");
+
+        // SHA-256 of "Hello World"
+        let hash = "A591A6D40BF420404A011733CFB7B190D62C65BF0BCDA32B57B277D9AD9F146E";
+        let mut op = WriteFile::new(HASH_SOURCE, TEMP_DIR, WRITTEN_DATA.to_vec())
+            .with_expected_hash(hash.to_string());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        let mut tr = Transaction::new().add_operation(Box::new(
+            WriteFile::new(HASH_SOURCE, TEMP_DIR, WRITTEN_DATA.to_vec())
+                .with_expected_hash("0".repeat(64)),
+        ));
+
+        let err = tr.execute().unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+
+        assert_eq!((), tr.rollback().unwrap());
+        let data = fs::read_to_string(HASH_SOURCE).expect("Unable to read file");
+        assert_eq!(String::from_utf8(INITIAL_DATA.to_vec()).unwrap(), data);
+
+        fs::remove_file(HASH_SOURCE);
+    }
+
+    const DATED_DIR: &str = "./write_file_dated";
+    const DATED_SOURCE: &str = "./write_file_dated/config.toml";
+
+    fn dated_backups() -> Vec<PathBuf> {
+        fs::read_dir(DATED_DIR)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bak"))
+            .collect()
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_file_dated_backup_works() {
+        fs::create_dir_all(DATED_DIR).unwrap();
+        fs::write(DATED_SOURCE, INITIAL_DATA).unwrap();
+
+        let mut first =
+            WriteFile::new(DATED_SOURCE, TEMP_DIR, b"Hello".to_vec()).with_dated_backup(true);
+        let mut second =
+            WriteFile::new(DATED_SOURCE, TEMP_DIR, b"World".to_vec()).with_dated_backup(true);
+
+        assert_eq!((), first.execute().unwrap());
+        assert_eq!((), second.execute().unwrap());
+        assert_eq!(b"World".to_vec(), fs::read(DATED_SOURCE).unwrap());
+
+        let backups = dated_backups();
+        assert_eq!(2, backups.len());
+        let name = first
+            .get_backup_path()
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(name.starts_with("config.toml.") && name.ends_with(".bak"));
+        assert_eq!(
+            INITIAL_DATA,
+            fs::read(first.get_backup_path()).unwrap().as_slice()
+        );
+
+        assert_eq!((), second.rollback().unwrap());
+        assert_eq!((), first.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(DATED_SOURCE).unwrap().as_slice());
+        assert!(dated_backups().is_empty());
+
+        fs::remove_dir_all(DATED_DIR);
+    }
+
+    #[test]
+    fn utc_timestamp_format() {
+        let stamp = utc_timestamp();
+
+        assert_eq!(15, stamp.len());
+        assert_eq!(Some('-'), stamp.chars().nth(8));
+        assert!(stamp.starts_with("20"));
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_file_backup_dir_works() {
+        const SOURCE: &str = "./write_file_backup_dir.txt";
+        const BACKUP_DIR: &str = "./write_file_backup_dir";
+
+        fs::write(SOURCE, INITIAL_DATA).unwrap();
+        fs::create_dir_all(BACKUP_DIR).unwrap();
+
+        let mut op =
+            WriteFile::new(SOURCE, TEMP_DIR, WRITTEN_DATA.to_vec()).with_backup_dir(BACKUP_DIR);
+        op.set_temp_dir(Path::new("./write_file_backup_dir_unused"));
+
+        assert_eq!((), op.execute().unwrap());
+        let backup = op.backup_path().unwrap().to_path_buf();
+        assert_eq!(Some(Path::new(BACKUP_DIR)), backup.parent());
+        assert_eq!(INITIAL_DATA, fs::read(&backup).unwrap().as_slice());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        drop(op);
+        assert_eq!(0, fs::read_dir(BACKUP_DIR).unwrap().count());
+
+        fs::remove_file(SOURCE);
+        fs::remove_dir(BACKUP_DIR);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_file_require_backup_works() {
+        const SOURCE: &str = "./write_file_require_backup.txt";
+
+        fs::write(SOURCE, INITIAL_DATA).unwrap();
+
+        let mut op =
+            WriteFile::new(SOURCE, TEMP_DIR, WRITTEN_DATA.to_vec()).with_require_backup(true);
+        assert_eq!((), op.execute().unwrap());
+        assert!(fs::read(SOURCE).unwrap().starts_with(WRITTEN_DATA));
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SOURCE);
+    }
+
+    /// A truncated blob in the backup store makes the backup of an identical file short
+    #[test]
+    #[cfg(feature = "hash")]
+    #[allow(unused_must_use)]
+    fn write_file_require_backup_refuses_short_backup() {
+        const SOURCE: &str = "./write_file_short_backup.txt";
+        const STORE: &str = "./write_file_short_backup_store";
+
+        fs::write(SOURCE, INITIAL_DATA).unwrap();
+        fs::create_dir_all(STORE).unwrap();
+        let blob = Path::new(STORE).join(crate::hash_file(SOURCE).unwrap());
+        fs::write(&blob, &INITIAL_DATA[..4]).unwrap();
+
+        let mut op =
+            WriteFile::new(SOURCE, TEMP_DIR, WRITTEN_DATA.to_vec()).with_require_backup(true);
+        op.set_backup_store(Path::new(STORE));
+
+        let err = op.execute().unwrap_err();
+        assert_eq!(ErrorKind::Other, err.kind());
+        assert!(err.to_string().contains("4 of 12 bytes"));
+        assert_eq!(INITIAL_DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(INITIAL_DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        drop(op);
+        fs::remove_file(SOURCE);
+        fs::remove_dir_all(STORE);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_file_if_changed_works() {
+        const SOURCE: &str = "./write_file_if_changed.bin";
+        const DATA: &[u8] = &[0xc3, 0x28, 0xff];
+
+        fs::write(SOURCE, DATA).unwrap();
+        let modified = fs::metadata(SOURCE).unwrap().modified().unwrap();
+
+        let mut op = WriteFile::new(SOURCE, TEMP_DIR, DATA.to_vec()).with_if_changed(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(None, op.backup_path());
+        assert_eq!(0, op.bytes_written());
+        assert_eq!(modified, fs::metadata(SOURCE).unwrap().modified().unwrap());
+        assert_eq!((), op.rollback().unwrap());
+
+        let mut op = WriteFile::new(SOURCE, TEMP_DIR, vec![0xc3, 0x29, 0xff]).with_if_changed(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(vec![0xc3, 0x29, 0xff], fs::read(SOURCE).unwrap());
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+
+        fs::remove_file(SOURCE);
+
+        // A missing file counts as changed
+        let mut op = WriteFile::new(SOURCE, TEMP_DIR, DATA.to_vec())
+            .with_rotation(1)
+            .with_if_changed(true);
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(DATA, fs::read(SOURCE).unwrap().as_slice());
+        assert_eq!((), op.rollback().unwrap());
+        assert!(!Path::new(SOURCE).exists());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_file_rotation_works() {
+        const SOURCE: &str = "./write_file_rotated.log";
+
+        let read = |suffix: &str| fs::read_to_string(format!("{}{}", SOURCE, suffix)).ok();
+
+        let mut ops = vec![];
+        for i in 1..=4 {
+            let mut op = WriteFile::new(SOURCE, TEMP_DIR, format!("run {}", i).into_bytes())
+                .with_rotation(2);
+            assert_eq!((), op.execute().unwrap());
+            ops.push(op);
+        }
+
+        assert_eq!(Some("run 4".into()), read(""));
+        assert_eq!(Some("run 3".into()), read(".1"));
+        assert_eq!(Some("run 2".into()), read(".2"));
+        assert_eq!(None, read(".3"));
+        assert_eq!(
+            Some(Path::new("./write_file_rotated.log.1")),
+            ops[3].backup_path()
+        );
+
+        assert_eq!((), ops[3].rollback().unwrap());
+        assert_eq!(Some("run 3".into()), read(""));
+        assert_eq!(Some("run 2".into()), read(".1"));
+        assert_eq!(Some("run 1".into()), read(".2"));
+
+        for op in ops[..3].iter().rev() {
+            assert_eq!((), op.rollback().unwrap());
+        }
+        assert_eq!(None, read(""));
+        assert_eq!(None, read(".1"));
+        assert_eq!(None, read(".2"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[allow(unused_must_use)]
+    fn write_file_rotation_preserves_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        const SOURCE: &str = "./write_file_mode.log";
+
+        let mode = || fs::metadata(SOURCE).unwrap().permissions().mode() & 0o777;
+
+        for dated in [false, true] {
+            fs::write(SOURCE, "Hello World").unwrap();
+            fs::set_permissions(SOURCE, fs::Permissions::from_mode(0o600)).unwrap();
+
+            let mut op = WriteFile::new(SOURCE, TEMP_DIR, b"Goodbye World".to_vec());
+            op = match dated {
+                true => op.with_dated_backup(true),
+                false => op.with_rotation(1),
+            };
+
+            assert_eq!((), op.execute().unwrap());
+            assert_eq!("Goodbye World", fs::read_to_string(SOURCE).unwrap());
+            assert_eq!(0o600, mode());
+
+            assert_eq!((), op.rollback().unwrap());
+            assert_eq!("Hello World", fs::read_to_string(SOURCE).unwrap());
+            assert_eq!(0o600, mode());
+
+            fs::remove_file(SOURCE);
+        }
+    }
+
+    const MANY_DIR: &str = "./write_many";
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn write_many_works() {
+        fs::create_dir_all(MANY_DIR).unwrap();
+
+        let paths: Vec<PathBuf> = (0..4)
+            .map(|i| Path::new(MANY_DIR).join(format!("config_{}.toml", i)))
+            .collect();
+        fs::write(&paths[0], "old 0").unwrap();
+        fs::write(&paths[2], "old 2").unwrap();
+
+        let mut op = WriteMany::new(paths.clone(), TEMP_DIR, b"shared = true".to_vec());
+        assert_eq!((), op.execute().unwrap());
+        assert_eq!(4, op.written());
+        assert_eq!(vec![paths[1].as_path(), paths[3].as_path()], op.created());
+        for path in &paths {
+            assert_eq!("shared = true", fs::read_to_string(path).unwrap());
+        }
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("old 0", fs::read_to_string(&paths[0]).unwrap());
+        assert_eq!("old 2", fs::read_to_string(&paths[2]).unwrap());
+        assert_eq!(2, fs::read_dir(MANY_DIR).unwrap().count());
+
+        // A path in a missing dir stops the writes partway, rollback only reverts the files before it
+        let mut partial = paths.clone();
+        partial.insert(2, Path::new(MANY_DIR).join("missing/config.toml"));
+
+        let mut op = WriteMany::new(partial, TEMP_DIR, b"shared = true".to_vec());
+        assert!(op.execute().is_err());
+        assert_eq!(3, op.written());
+        assert_eq!("old 2", fs::read_to_string(&paths[2]).unwrap());
+
+        assert_eq!((), op.rollback().unwrap());
+        assert_eq!("old 0", fs::read_to_string(&paths[0]).unwrap());
+        assert_eq!(2, fs::read_dir(MANY_DIR).unwrap().count());
+
+        fs::remove_dir_all(MANY_DIR);
+    }
 }
@@ -0,0 +1,37 @@
+// Changing the current dir affects every thread of the process, so this test has a binary of its own
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use tfio::{RollbackableOperation, Transaction};
+
+#[test]
+#[allow(unused_must_use)]
+fn pin_cwd_works() {
+    let original = env::current_dir().unwrap();
+    let base = env::temp_dir().join(format!("tfio-pin-cwd-{}", std::process::id()));
+    let (first, second) = (base.join("first"), base.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+
+    env::set_current_dir(&first).unwrap();
+    let mut tr = Transaction::new().create_file("created.txt");
+    tr.pin_cwd().unwrap();
+    let mut tr = tr.write_file("created.txt", "tmp", b"Hello".to_vec());
+
+    env::set_current_dir(&second).unwrap();
+    assert_eq!((), tr.execute().unwrap());
+    assert_eq!(
+        "Hello",
+        fs::read_to_string(first.join("created.txt")).unwrap()
+    );
+    assert!(first.join("tmp").is_dir());
+    assert!(!Path::new("created.txt").exists());
+
+    assert_eq!((), tr.rollback().unwrap());
+    assert!(!first.join("created.txt").exists());
+
+    env::set_current_dir(original).unwrap();
+    fs::remove_dir_all(base);
+}